@@ -0,0 +1,287 @@
+// Player accounts, so nobody else can record games under a registered name
+//
+// Passwords are only stored hashed with bcrypt. Logging in gives a random token, which the
+// frontend sends back as "Authorization: Bearer <token>" until it expires or the player logs
+// out. A game posted with a token links the logged in player to their account; anyone else
+// playing under a registered name is recorded as a guest (see link_accounts()).
+use std::fmt;
+use std::sync::Mutex;
+use chrono::{Duration, Utc};
+use rand::{rngs::OsRng, RngCore};
+use crate::storage::{name_key, AccountRecord, HistoryFilter, HistoryQuery, HistoryRecord, HistoryStorage, PlayerId, SessionRecord};
+
+const SESSION_DAYS: i64 = 30;
+const HASH_COST: u32 = 10; // about half a second in a debug build, bcrypt's default of 12 is four times that
+const MAX_NAME_LENGTH: usize = 20;
+const MIN_PASSWORD_LENGTH: usize = 8;
+const MAX_PASSWORD_LENGTH: usize = 72; // bcrypt ignores the rest
+const TOKEN_BYTES: usize = 32;
+// the history uses these for something else
+const RESERVED_NAMES: &[&str] = &["Computer", "Draw"];
+
+#[derive(Debug, PartialEq)]
+pub enum AccountError {
+    Invalid(String), // the name or password isn't allowed
+    NameTaken,
+    NameHasHistory, // guests played under the name, their games and rating aren't the account's
+    WrongPassword, // or no account with the name, which isn't told apart
+    Storage(String),
+}
+
+impl fmt::Display for AccountError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AccountError::Invalid(reason) => write!(f, "{}", reason),
+            AccountError::NameTaken => write!(f, "the name is taken"),
+            AccountError::NameHasHistory => write!(f, "games are already recorded under the name"),
+            AccountError::WrongPassword => write!(f, "wrong name or password"),
+            AccountError::Storage(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl From<String> for AccountError {
+    fn from(err: String) -> Self {
+        AccountError::Storage(err)
+    }
+}
+
+// letters, digits, spaces, - and _, so a name can't look like "Computer (Hard)" or a guest's
+fn check_name(name: &str) -> Result<(), AccountError> {
+    if name.trim().is_empty() || name.chars().count() > MAX_NAME_LENGTH {
+        return Err(AccountError::Invalid(format!("names have 1 to {} characters", MAX_NAME_LENGTH)));
+    }
+    if name.trim() != name || !name.chars().all(|c| c.is_alphanumeric() || c == ' ' || c == '-' || c == '_') {
+        return Err(AccountError::Invalid(String::from(
+            "names have letters, digits, spaces, - and _ only, without spaces at the ends",
+        )));
+    }
+    if RESERVED_NAMES.iter().any(|reserved| reserved.eq_ignore_ascii_case(name)) {
+        return Err(AccountError::Invalid(format!("{} can't be registered", name)));
+    }
+    Ok(())
+}
+
+fn check_password(password: &str) -> Result<(), AccountError> {
+    if password.chars().count() < MIN_PASSWORD_LENGTH || password.len() > MAX_PASSWORD_LENGTH {
+        return Err(AccountError::Invalid(format!(
+            "passwords have at least {} characters and at most {} bytes",
+            MIN_PASSWORD_LENGTH, MAX_PASSWORD_LENGTH
+        )));
+    }
+    Ok(())
+}
+
+// registrations take turns, two of the same name at once would otherwise both find it free
+#[derive(Default)]
+pub struct Accounts {
+    registering: Mutex<()>,
+}
+
+impl Accounts {
+    // the new account, logged in
+    pub fn register(&self, storage: &dyn HistoryStorage, name: &str, password: &str) -> Result<(AccountRecord, SessionRecord), AccountError> {
+        check_name(name)?;
+        check_password(password)?;
+        let password_hash = bcrypt::hash(password, HASH_COST)
+            .map_err(|err| AccountError::Storage(format!("failed to hash the password: {}", err)))?;
+
+        let _registering = self.registering.lock().unwrap();
+        if storage.account_by_name(name)?.is_some() {
+            return Err(AccountError::NameTaken);
+        }
+        if has_history(storage, name)? {
+            return Err(AccountError::NameHasHistory);
+        }
+        let mut account = AccountRecord { id: String::new(), name: String::from(name), password_hash, created: Utc::now() };
+        account.id = storage.add_account(account.clone())?;
+        let session = start_session(storage, &account)?;
+        Ok((account, session))
+    }
+}
+
+// guests' games and ratings stay theirs, the account would be listed next to them under the
+// same name
+fn has_history(storage: &dyn HistoryStorage, name: &str) -> Result<bool, String> {
+    let guest = PlayerId::Name(String::from(name));
+    if storage.rating(&guest)?.is_some() {
+        return Ok(true);
+    }
+    let filter = HistoryFilter { player: Some(guest), ..HistoryFilter::default() };
+    let page = storage.histories(&HistoryQuery { filter, limit: 0, ..HistoryQuery::default() })?;
+    Ok(page.total > 0)
+}
+
+// who a name in a query means: the account registered under it, or else the guests or the
+// computer who played under it
+pub fn player_id(storage: &dyn HistoryStorage, name: &str) -> Result<PlayerId, String> {
+    Ok(match storage.account_by_name(name)? {
+        Some(account) => PlayerId::Account(account.id),
+        None => PlayerId::Name(String::from(name)),
+    })
+}
+
+pub fn login(storage: &dyn HistoryStorage, name: &str, password: &str) -> Result<(AccountRecord, SessionRecord), AccountError> {
+    let account = storage.account_by_name(name)?.ok_or(AccountError::WrongPassword)?;
+    if !bcrypt::verify(password, &account.password_hash).unwrap_or(false) {
+        return Err(AccountError::WrongPassword);
+    }
+    let session = start_session(storage, &account)?;
+    Ok((account, session))
+}
+
+fn start_session(storage: &dyn HistoryStorage, account: &AccountRecord) -> Result<SessionRecord, String> {
+    let mut bytes = [0u8; TOKEN_BYTES];
+    OsRng.fill_bytes(&mut bytes);
+    let session = SessionRecord {
+        token: bytes.iter().map(|byte| format!("{:02x}", byte)).collect(),
+        account_id: account.id.clone(),
+        expires: Utc::now() + Duration::days(SESSION_DAYS),
+    };
+    storage.add_session(session.clone())?;
+    Ok(session)
+}
+
+// the account logged in with the token, None if it was never given out, has expired or the
+// player logged out
+pub fn logged_in(storage: &dyn HistoryStorage, token: &str) -> Result<Option<AccountRecord>, String> {
+    let session = match storage.session(token)? {
+        Some(session) => session,
+        None => return Ok(None),
+    };
+    if session.expires <= Utc::now() {
+        storage.remove_session(token)?;
+        return Ok(None);
+    }
+    storage.account(&session.account_id)
+}
+
+// links the player with the logged in account's name to it. Any other player using a
+// registered name is a guest and is recorded as "Alice (guest)", so the account's games,
+// statistics and rating are only its own.
+pub fn link_accounts(storage: &dyn HistoryStorage, record: &mut HistoryRecord, account: Option<&AccountRecord>) -> Result<(), String> {
+    let mut linked = false;
+    let mut renamed = Vec::new();
+    for (player, player_account) in vec![
+        (&mut record.player1, &mut record.player1_account),
+        (&mut record.player2, &mut record.player2_account),
+    ] {
+        match account {
+            // the same name for both players links the first one only
+            // recorded with the account's spelling of the name, the one shown for it
+            Some(account) if !linked && name_key(player) == name_key(&account.name) => {
+                *player_account = Some(account.id.clone());
+                linked = true;
+                if *player != account.name {
+                    renamed.push((player.clone(), account.name.clone()));
+                    *player = account.name.clone();
+                }
+            },
+            _ => {
+                *player_account = None;
+                if storage.account_by_name(player)?.is_some() {
+                    let guest = format!("{} (guest)", player);
+                    renamed.push((player.clone(), guest.clone()));
+                    *player = guest;
+                }
+            },
+        }
+    }
+    for field in vec![&mut record.winner, &mut record.first_player] {
+        if let Some((_, new_name)) = renamed.iter().find(|(name, _)| name == field) {
+            *field = new_name.clone();
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemoryStorage;
+
+    fn game(player1: &str, player2: &str) -> HistoryRecord {
+        HistoryRecord {
+            id: String::new(),
+            game_type: String::from("Connect-4"),
+            player1: String::from(player1),
+            player2: String::from(player2),
+            winner: String::from(player1),
+            difficulty: String::from("N/A"),
+            player1_hints: 0,
+            player2_hints: 0,
+            game_date: Some(Utc::now()),
+            board_rows: 6,
+            board_columns: 7,
+            moves: Vec::new(),
+            first_player: String::from(player1),
+            duration_seconds: 0,
+            ai_seed: None,
+            ai_level: None,
+            player1_account: None,
+            player2_account: None,
+        }
+    }
+
+    #[test]
+    fn register_and_login() {
+        let storage = MemoryStorage::default();
+        let accounts = Accounts::default();
+        let (account, session) = accounts.register(&storage, "Alice", "correct horse").unwrap();
+        assert_ne!(account.password_hash, "correct horse");
+        assert_eq!(logged_in(&storage, &session.token).unwrap(), Some(account.clone()));
+
+        // names are compared without case
+        let (same, other_session) = login(&storage, "alice", "correct horse").unwrap();
+        assert_eq!(same, account);
+        assert_ne!(other_session.token, session.token);
+        assert_eq!(login(&storage, "Alice", "wrong horse").unwrap_err(), AccountError::WrongPassword);
+        assert_eq!(login(&storage, "Bob", "correct horse").unwrap_err(), AccountError::WrongPassword);
+        assert_eq!(accounts.register(&storage, "ALICE", "another password").unwrap_err(), AccountError::NameTaken);
+
+        storage.remove_session(&session.token).unwrap();
+        assert_eq!(logged_in(&storage, &session.token).unwrap(), None);
+        assert_eq!(logged_in(&storage, "not a token").unwrap(), None);
+    }
+
+    #[test]
+    fn expired_sessions() {
+        let storage = MemoryStorage::default();
+        let (account, _) = Accounts::default().register(&storage, "Alice", "correct horse").unwrap();
+        let session = SessionRecord { token: String::from("old"), account_id: account.id, expires: Utc::now() - Duration::days(1) };
+        storage.add_session(session).unwrap();
+        assert_eq!(logged_in(&storage, "old").unwrap(), None);
+        assert_eq!(storage.session("old").unwrap(), None);
+    }
+
+    #[test]
+    fn names_and_passwords() {
+        let storage = MemoryStorage::default();
+        let accounts = Accounts::default();
+        for name in ["", " Alice", "Computer (Hard)", "draw", "a name much too long for it"].iter() {
+            assert!(matches!(accounts.register(&storage, name, "correct horse"), Err(AccountError::Invalid(_))), "{}", name);
+        }
+        assert!(matches!(accounts.register(&storage, "Alice", "short"), Err(AccountError::Invalid(_))));
+
+        // the games played under the name before stay a guest's
+        storage.insert(game("Bob", "Carol")).unwrap();
+        assert_eq!(accounts.register(&storage, "Bob", "correct horse").unwrap_err(), AccountError::NameHasHistory);
+    }
+
+    #[test]
+    fn guests_under_registered_names() {
+        let storage = MemoryStorage::default();
+        let (alice, _) = Accounts::default().register(&storage, "Alice", "correct horse").unwrap();
+
+        let mut record = game("alice", "Alice");
+        link_accounts(&storage, &mut record, Some(&alice)).unwrap();
+        assert_eq!((record.player1.as_str(), record.player1_account.as_ref()), ("Alice", Some(&alice.id)));
+        assert_eq!((record.player2.as_str(), record.player2_account), ("Alice (guest)", None));
+        assert_eq!((record.winner.as_str(), record.first_player.as_str()), ("Alice", "Alice"));
+
+        let mut record = game("Alice", "Bob");
+        link_accounts(&storage, &mut record, None).unwrap();
+        assert_eq!((record.player1.as_str(), record.player2.as_str()), ("Alice (guest)", "Bob"));
+        assert_eq!(record.winner, "Alice (guest)");
+    }
+}
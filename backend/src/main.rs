@@ -2,93 +2,526 @@
 
 #[macro_use] extern crate rocket;
 
+mod storage;
+mod mongo_storage;
+mod sqlite_storage;
+mod stats;
+mod ratings;
+mod accounts;
+
+use std::env;
 use std::path::{Path, PathBuf};
 use rocket::response::NamedFile;
-use mongodb::{Client, Collection, error::Error};
 use rocket::config::{Config, Environment};
 use rocket::http::Method;
 use rocket_cors::{AllowedHeaders, AllowedOrigins};
 
 mod database {
-    use mongodb::Collection;
-    use bson::{doc, Bson};
     use chrono::Utc;
-    use rocket::State;
+    use rocket::{Outcome, State};
+    use rocket::http::Status;
+    use rocket::request::{self, FromRequest, LenientForm, Request};
+    use rocket::response::status::Custom;
     use rocket_contrib::json::Json;
     use serde::{Serialize, Deserialize};
+    use crate::accounts::{self, AccountError, Accounts};
+    use crate::ratings::{self, PlayerRatings, RatedPlayer, Ratings};
+    use crate::stats::{self, LeaderboardEntry, PlayerStats, Ranking};
+    use crate::storage::{
+        parse_date, AccountRecord, Cursor, HistoryFilter, HistoryQuery, HistoryRecord, PlayerId, PlayerResults, SessionRecord,
+        SortKey, Storage, DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE,
+    };
+
+    const DEFAULT_LEADERBOARD_SIZE: usize = 10;
+    const DEFAULT_RATINGS_SIZE: usize = 50;
 
     #[derive(Serialize, Deserialize, Debug)]
     pub struct HistoryInfo {
+        #[serde(default)]
+        pub id: String, // given by the backend, ignored when a game is posted
         pub game_type: String,
         pub player1: String,
         pub player2: String,
         pub winner: String,
         pub difficulty: String,
         pub time_played: String,
+        #[serde(default)]
+        pub player1_hints: u32,
+        #[serde(default)]
+        pub player2_hints: u32,
+        // 0 or empty for games recorded before these were kept
+        #[serde(default)]
+        pub board_rows: u32,
+        #[serde(default)]
+        pub board_columns: u32,
+        #[serde(default)]
+        pub moves: Vec<String>, // in order, "3" in Connect 4 and "3T" in TOOT-OTTO
+        #[serde(default)]
+        pub first_player: String,
+        #[serde(default)]
+        pub duration_seconds: u32,
+        #[serde(default)]
+        pub ai_seed: Option<u64>,
+        #[serde(default)]
+        pub ai_level: Option<f64>,
+        // given by the backend from the login, ignored when a game is posted
+        #[serde(default)]
+        pub player1_account: Option<String>,
+        #[serde(default)]
+        pub player2_account: Option<String>,
     }
 
-    #[get("/history")]
-    pub fn get_histories(collection: State<Collection>) -> Json<Vec<HistoryInfo>> {
-        let mut history_info: Vec<HistoryInfo> = Vec::new();
-        
-        match collection.find(None, None) {
-            Ok(cursor) => {
-                for result in cursor {
-                    match result {
-                        Ok(document) => {
-                            let game_type = String::from(document.get("gameType").and_then(Bson::as_str).unwrap_or(""));
-                            let player1 = String::from(document.get("Player1Name").and_then(Bson::as_str).unwrap_or(""));
-                            let player2 = String::from(document.get("Player2Name").and_then(Bson::as_str).unwrap_or(""));
-                            let difficulty = String::from(document.get("Difficulty").and_then(Bson::as_str).unwrap_or("N/A"));
-                            let winner = String::from(document.get("WinnerName").and_then(Bson::as_str).unwrap_or(""));
-                            let mut time_played: String = "unknown".to_string();
-                            if let Some(date_time) = document.get("GameDate").and_then(Bson::as_utc_date_time) {
-                                let time_rfc2822 = date_time.to_rfc2822();
-                                let len = time_rfc2822.len();
-                                // remove the "+0000" part from the String
-                                // see https://docs.rs/chrono/0.4.5/chrono/struct.DateTime.html#method.to_rfc2822 for more detail
-                                time_played = time_rfc2822[..len - 6].to_string();
-                            }
-
-                            history_info.push(HistoryInfo {
-                                game_type,
-                                player1,
-                                player2,
-                                difficulty,
-                                winner,
-                                time_played
-                            });
-                        },
-                        Err(_) => eprintln!("result is not a document")
+    impl From<HistoryRecord> for HistoryInfo {
+        fn from(record: HistoryRecord) -> Self {
+            let mut time_played: String = "unknown".to_string();
+            if let Some(date_time) = record.game_date {
+                let time_rfc2822 = date_time.to_rfc2822();
+                let len = time_rfc2822.len();
+                // remove the "+0000" part from the String
+                // see https://docs.rs/chrono/0.4.5/chrono/struct.DateTime.html#method.to_rfc2822 for more detail
+                time_played = time_rfc2822[..len - 6].to_string();
+            }
+
+            HistoryInfo {
+                id: record.id,
+                game_type: record.game_type,
+                player1: record.player1,
+                player2: record.player2,
+                winner: record.winner,
+                difficulty: record.difficulty,
+                time_played,
+                player1_hints: record.player1_hints,
+                player2_hints: record.player2_hints,
+                board_rows: record.board_rows,
+                board_columns: record.board_columns,
+                moves: record.moves,
+                first_player: record.first_player,
+                duration_seconds: record.duration_seconds,
+                ai_seed: record.ai_seed,
+                ai_level: record.ai_level,
+                player1_account: record.player1_account,
+                player2_account: record.player2_account,
+            }
+        }
+    }
+
+    fn internal_error(err: String) -> Custom<String> {
+        eprintln!("{}", err);
+        Custom(Status::InternalServerError, err)
+    }
+
+    // the query string of GET /history, every field is optional. A registered name stands for
+    // its account, see accounts::player_id().
+    #[derive(FromForm, Debug)]
+    pub struct HistoryParams {
+        game_type: Option<String>,
+        player: Option<String>, // either player
+        player1: Option<String>,
+        player2: Option<String>,
+        account: Option<String>, // id of either player's account, instead of player
+        difficulty: Option<String>,
+        winner: Option<String>,
+        board_rows: Option<u32>,
+        board_columns: Option<u32>,
+        from: Option<String>, // 2020-11-30 or 2020-11-30T12:00:00Z
+        to: Option<String>,   // a day is included whole
+        sort: Option<String>, // date by default
+        order: Option<String>, // asc (default) or desc
+        limit: Option<usize>,
+        page: Option<usize>,  // from 1
+        cursor: Option<String>, // "next" of the previous page, page is then ignored
+    }
+
+    impl HistoryParams {
+        // the filter of the players the query names, the rest of it is left to query()
+        fn players(&self, storage: &Storage) -> Result<HistoryFilter, Custom<String>> {
+            let player_id = |name: &Option<String>| {
+                name.as_ref().map(|name| accounts::player_id(&**storage, name)).transpose().map_err(internal_error)
+            };
+            let player = match (&self.account, &self.player) {
+                (Some(_), Some(_)) => return Err(Custom(Status::BadRequest, String::from("player and account can't be used together"))),
+                (Some(account), None) => Some(PlayerId::Account(account.clone())),
+                (None, player) => player_id(player)?,
+            };
+            Ok(HistoryFilter {
+                player,
+                player1: player_id(&self.player1)?,
+                player2: player_id(&self.player2)?,
+                winner: player_id(&self.winner)?,
+                ..HistoryFilter::default()
+            })
+        }
+
+        fn query(self, players: HistoryFilter) -> Result<HistoryQuery, String> {
+            let sort = match &self.sort {
+                Some(sort) => sort.parse::<SortKey>()?,
+                None => SortKey::Date,
+            };
+            let descending = match self.order.as_deref() {
+                None | Some("asc") => false,
+                Some("desc") => true,
+                Some(order) => return Err(format!("order is asc or desc, not {}", order)),
+            };
+            let limit = self.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+            if limit > MAX_PAGE_SIZE {
+                return Err(format!("limit is at most {}", MAX_PAGE_SIZE));
+            }
+            let offset = match self.page {
+                Some(0) => return Err(String::from("pages are counted from 1")),
+                Some(page) => (page - 1) * limit,
+                None => 0,
+            };
+            let after = match &self.cursor {
+                Some(cursor) => {
+                    let cursor = Cursor::decode(cursor)?;
+                    if cursor.sort != sort {
+                        return Err(format!("the cursor is for sorting by {}", cursor.sort));
                     }
+                    Some(cursor)
+                },
+                None => None,
+            };
+            let filter = HistoryFilter {
+                game_type: self.game_type,
+                difficulty: self.difficulty,
+                board_rows: self.board_rows,
+                board_columns: self.board_columns,
+                from: self.from.map(|from| parse_date(&from, false)).transpose()?,
+                to: self.to.map(|to| parse_date(&to, true)).transpose()?,
+                ..players
+            };
+            Ok(HistoryQuery { filter, sort, descending, limit, offset, after })
+        }
+    }
+
+    #[derive(Serialize, Deserialize, Debug)]
+    pub struct HistoryPage {
+        pub histories: Vec<HistoryInfo>,
+        pub total: u64, // games matching the filters, on all pages
+        pub next: Option<String>, // cursor for the next page, None on the last one
+    }
+
+    // a page of the games, oldest first unless the query says otherwise (see HistoryParams)
+    #[get("/history?<params..>")]
+    pub fn get_histories(storage: State<Storage>, params: LenientForm<HistoryParams>) -> Result<Json<HistoryPage>, Custom<String>> {
+        let params = params.into_inner();
+        let players = params.players(&storage)?;
+        let query = params.query(players).map_err(|err| Custom(Status::BadRequest, err))?;
+        match storage.histories(&query) {
+            Ok(page) => Ok(Json(HistoryPage {
+                histories: page.records.into_iter().map(HistoryInfo::from).collect(),
+                total: page.total,
+                next: page.next.map(|cursor| cursor.encode()),
+            })),
+            Err(err) => {
+                eprintln!("{}", err);
+                Err(Custom(Status::InternalServerError, err))
+            }
+        }
+    }
+
+    // the query string of the statistics and leaderboard, which games to count and how to rank
+    // the players, every field is optional
+    #[derive(FromForm, Debug)]
+    pub struct StatsParams {
+        game_type: Option<String>,
+        difficulty: Option<String>,
+        board_rows: Option<u32>,
+        board_columns: Option<u32>,
+        from: Option<String>,
+        to: Option<String>,
+        by: Option<String>,      // leaderboard only, wins (default), win_rate or games
+        limit: Option<usize>,    // leaderboard only, 10 players by default
+        min_games: Option<u64>, // leaderboard only, 1 by default
+    }
+
+    impl StatsParams {
+        fn filter(&self) -> Result<HistoryFilter, String> {
+            Ok(HistoryFilter {
+                game_type: self.game_type.clone(),
+                difficulty: self.difficulty.clone(),
+                board_rows: self.board_rows,
+                board_columns: self.board_columns,
+                from: self.from.as_ref().map(|from| parse_date(from, false)).transpose()?,
+                to: self.to.as_ref().map(|to| parse_date(to, true)).transpose()?,
+                ..HistoryFilter::default()
+            })
+        }
+    }
+
+    fn results(storage: &Storage, filter: &HistoryFilter) -> Result<Vec<PlayerResults>, Custom<String>> {
+        storage.results(filter).map_err(internal_error)
+    }
+
+    // every player's wins, losses and draws, in total, by difficulty and by game and board size
+    #[get("/stats/players?<params..>")]
+    pub fn get_player_stats(storage: State<Storage>, params: LenientForm<StatsParams>) -> Result<Json<Vec<PlayerStats>>, Custom<String>> {
+        let filter = params.filter().map_err(|err| Custom(Status::BadRequest, err))?;
+        Ok(Json(stats::player_stats(&results(&storage, &filter)?)))
+    }
+
+    // None if the player has no games, a registered name is its account (see accounts::player_id())
+    #[get("/stats/players/<player>?<params..>")]
+    pub fn get_one_player_stats(
+        storage: State<Storage>,
+        player: String,
+        params: LenientForm<StatsParams>,
+    ) -> Result<Option<Json<PlayerStats>>, Custom<String>> {
+        let mut filter = params.filter().map_err(|err| Custom(Status::BadRequest, err))?;
+        let player = accounts::player_id(&**storage, &player).map_err(internal_error)?;
+        filter.player = Some(player.clone());
+        let players = stats::player_stats(&results(&storage, &filter)?);
+        Ok(players.into_iter().find(|stats| PlayerId::new(&stats.player, &stats.account) == player).map(Json))
+    }
+
+    #[get("/leaderboard?<params..>")]
+    pub fn get_leaderboard(storage: State<Storage>, params: LenientForm<StatsParams>) -> Result<Json<Vec<LeaderboardEntry>>, Custom<String>> {
+        let filter = params.filter().map_err(|err| Custom(Status::BadRequest, err))?;
+        let ranking = match &params.by {
+            Some(by) => by.parse::<Ranking>().map_err(|err| Custom(Status::BadRequest, err))?,
+            None => Ranking::Wins,
+        };
+        let limit = params.limit.unwrap_or(DEFAULT_LEADERBOARD_SIZE);
+        let min_games = params.min_games.unwrap_or(1);
+        Ok(Json(stats::leaderboard(&results(&storage, &filter)?, ranking, limit, min_games)))
+    }
+
+    #[derive(FromForm, Debug)]
+    pub struct RatingsParams {
+        limit: Option<usize>,    // 50 players by default
+        min_games: Option<u32>, // 1 by default
+        computer: Option<bool>, // whether to list the difficulties of the computer, false by default
+    }
+
+    // the best rated players
+    #[get("/ratings?<params..>")]
+    pub fn get_ratings(storage: State<Storage>, params: LenientForm<RatingsParams>) -> Result<Json<Vec<RatedPlayer>>, Custom<String>> {
+        let current = storage.ratings().map_err(|err| {
+            eprintln!("{}", err);
+            Custom(Status::InternalServerError, err)
+        })?;
+        let limit = params.limit.unwrap_or(DEFAULT_RATINGS_SIZE);
+        let min_games = params.min_games.unwrap_or(1);
+        Ok(Json(ratings::ranking(current, limit, min_games, params.computer.unwrap_or(false))))
+    }
+
+    // a player's rating with how it went after each game, None without rated games. A registered
+    // name is its account, the difficulties of the computer are "Computer (Hard)" and so on.
+    #[get("/ratings/<player>")]
+    pub fn get_player_ratings(storage: State<Storage>, player: String) -> Result<Option<Json<PlayerRatings>>, Custom<String>> {
+        let player = accounts::player_id(&**storage, &player).map_err(internal_error)?;
+        let current = match storage.rating(&player).map_err(internal_error)? {
+            Some(current) => current,
+            None => return Ok(None),
+        };
+        let history = storage.rating_history(&player).map_err(internal_error)?;
+        Ok(Some(Json(ratings::player_ratings(current, history))))
+    }
+
+    // the account of the "Authorization: Bearer <token>" header, requests without a valid one
+    // are answered with 401 Unauthorized, or go on as a guest with Option<LoggedIn>
+    pub struct LoggedIn {
+        token: String,
+        account: AccountRecord,
+    }
+
+    fn bearer_token<'r>(request: &'r Request) -> Option<&'r str> {
+        request.headers().get_one("Authorization").and_then(|value| value.strip_prefix("Bearer "))
+    }
+
+    impl<'a, 'r> FromRequest<'a, 'r> for LoggedIn {
+        type Error = String;
+
+        fn from_request(request: &'a Request<'r>) -> request::Outcome<Self, Self::Error> {
+            let token = match bearer_token(request) {
+                Some(token) => String::from(token),
+                None => return Outcome::Failure((Status::Unauthorized, String::from("not logged in"))),
+            };
+            let storage = match request.guard::<State<Storage>>() {
+                Outcome::Success(storage) => storage,
+                _ => return Outcome::Failure((Status::InternalServerError, String::from("no storage"))),
+            };
+            match accounts::logged_in(&**storage, &token) {
+                Ok(Some(account)) => Outcome::Success(LoggedIn { token, account }),
+                Ok(None) => Outcome::Failure((Status::Unauthorized, String::from("the login has expired"))),
+                Err(err) => {
+                    eprintln!("{}", err);
+                    Outcome::Failure((Status::InternalServerError, err))
                 }
-            },
-            Err(_) => eprintln!("collection find failed")
+            }
         }
+    }
 
-        Json(history_info)
+    // the ADMIN_TOKEN the server was started with, None turns the admin requests off
+    pub struct AdminToken(pub Option<String>);
+
+    // "Authorization: Bearer <ADMIN_TOKEN>". Requests without a token are answered with 401
+    // Unauthorized and any other token, a player's login included, with 403 Forbidden.
+    pub struct Admin;
+
+    // takes as long for every token of the same length, so it can't be guessed a byte at a time
+    fn same_token(a: &str, b: &str) -> bool {
+        a.len() == b.len() && a.bytes().zip(b.bytes()).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
     }
 
+    impl<'a, 'r> FromRequest<'a, 'r> for Admin {
+        type Error = String;
+
+        fn from_request(request: &'a Request<'r>) -> request::Outcome<Self, Self::Error> {
+            let token = match bearer_token(request) {
+                Some(token) => token,
+                None => return Outcome::Failure((Status::Unauthorized, String::from("no admin token"))),
+            };
+            match request.guard::<State<AdminToken>>() {
+                Outcome::Success(admin) => match &admin.0 {
+                    Some(admin_token) if same_token(token, admin_token) => Outcome::Success(Admin),
+                    _ => Outcome::Failure((Status::Forbidden, String::from("not the admin token"))),
+                },
+                _ => Outcome::Failure((Status::InternalServerError, String::from("no admin token state"))),
+            }
+        }
+    }
+
+    #[derive(Deserialize, Debug)]
+    pub struct Credentials {
+        pub name: String,
+        pub password: String,
+    }
+
+    // an account without its password
+    #[derive(Serialize, Debug)]
+    pub struct AccountInfo {
+        pub id: String,
+        pub name: String,
+        pub created: String, // RFC 3339
+    }
+
+    impl From<AccountRecord> for AccountInfo {
+        fn from(account: AccountRecord) -> Self {
+            AccountInfo { id: account.id, name: account.name, created: account.created.to_rfc3339() }
+        }
+    }
+
+    #[derive(Serialize, Debug)]
+    pub struct SessionInfo {
+        pub token: String, // for the Authorization header
+        pub expires: String, // RFC 3339
+        pub account: AccountInfo,
+    }
+
+    fn logged_in_as((account, session): (AccountRecord, SessionRecord)) -> Json<SessionInfo> {
+        Json(SessionInfo { token: session.token, expires: session.expires.to_rfc3339(), account: AccountInfo::from(account) })
+    }
+
+    fn account_error(err: AccountError) -> Custom<String> {
+        let status = match err {
+            AccountError::Invalid(_) => Status::BadRequest,
+            AccountError::NameTaken | AccountError::NameHasHistory => Status::Conflict,
+            AccountError::WrongPassword => Status::Unauthorized,
+            AccountError::Storage(_) => {
+                eprintln!("{}", err);
+                Status::InternalServerError
+            }
+        };
+        Custom(status, err.to_string())
+    }
+
+    // a new account, logged in right away
+    #[post("/accounts", format="json", data="<credentials>")]
+    pub fn register(storage: State<Storage>, accounts: State<Accounts>, credentials: Json<Credentials>) -> Result<Json<SessionInfo>, Custom<String>> {
+        accounts.register(&**storage, &credentials.name, &credentials.password)
+            .map(logged_in_as)
+            .map_err(account_error)
+    }
+
+    #[post("/login", format="json", data="<credentials>")]
+    pub fn login(storage: State<Storage>, credentials: Json<Credentials>) -> Result<Json<SessionInfo>, Custom<String>> {
+        accounts::login(&**storage, &credentials.name, &credentials.password)
+            .map(logged_in_as)
+            .map_err(account_error)
+    }
+
+    // the token stops working, other logins of the account go on
+    #[post("/logout")]
+    pub fn logout(storage: State<Storage>, logged_in: LoggedIn) -> Result<(), Status> {
+        storage.remove_session(&logged_in.token).map_err(|err| {
+            eprintln!("{}", err);
+            Status::InternalServerError
+        })
+    }
+
+    #[get("/account")]
+    pub fn get_account(logged_in: LoggedIn) -> Json<AccountInfo> {
+        Json(AccountInfo::from(logged_in.account))
+    }
+
+    // one game with all its moves, to replay it
+    #[get("/history/<id>")]
+    pub fn get_history(storage: State<Storage>, id: String) -> Option<Json<HistoryInfo>> {
+        match storage.history(&id) {
+            Ok(record) => record.map(|record| Json(HistoryInfo::from(record))),
+            Err(err) => {
+                eprintln!("{}", err);
+                None
+            }
+        }
+    }
+
+    // answers with the id of the recorded game, its players are rated too. The logged in
+    // player is linked to their account, see accounts::link_accounts().
     #[post("/history", format="json", data="<history>")]
-    pub fn insert_history(collection: State<Collection>, history: Json<HistoryInfo>) {
+    pub fn insert_history(
+        storage: State<Storage>,
+        ratings: State<Ratings>,
+        logged_in: Option<LoggedIn>,
+        history: Json<HistoryInfo>,
+    ) -> Result<String, Status> {
         let body = history.into_inner();
 
         // the time_played field from HistoryInfo is ignored here
         // front end can pass any string since it won't be used
-        let doc = doc! {
-            "gameType": body.game_type,
-            "Player1Name": body.player1,
-            "Player2Name": body.player2,
-            "WinnerName": body.winner,
-            "Difficulty": body.difficulty,
-            "GameDate": Utc::now()
+        let mut record = HistoryRecord {
+            id: String::new(),
+            game_type: body.game_type,
+            player1: body.player1,
+            player2: body.player2,
+            winner: body.winner,
+            difficulty: body.difficulty,
+            player1_hints: body.player1_hints,
+            player2_hints: body.player2_hints,
+            game_date: Some(Utc::now()),
+            board_rows: body.board_rows,
+            board_columns: body.board_columns,
+            moves: body.moves,
+            first_player: body.first_player,
+            duration_seconds: body.duration_seconds,
+            ai_seed: body.ai_seed,
+            ai_level: body.ai_level,
+            player1_account: None,
+            player2_account: None,
         };
-        let _ = collection.insert_one(doc, None);
+        let account = logged_in.as_ref().map(|logged_in| &logged_in.account);
+        accounts::link_accounts(&**storage, &mut record, account).map_err(|err| {
+            eprintln!("{}", err);
+            Status::InternalServerError
+        })?;
+        record.id = storage.insert(record.clone()).map_err(|err| {
+            eprintln!("{}", err);
+            Status::InternalServerError
+        })?;
+        // the game is recorded either way
+        if let Err(err) = ratings.record_game(&**storage, &record) {
+            eprintln!("failed to rate game {}: {}", record.id, err);
+        }
+        Ok(record.id)
     }
 
+    // the ratings too, only with the admin token
     #[delete("/history")]
-    pub fn delete_history(collection: State<Collection>) {
-        collection.drop(None);
+    pub fn delete_history(storage: State<Storage>, _admin: Admin) {
+        if let Err(err) = storage.clear() {
+            eprintln!("{}", err);
+        }
     }
 }
 
@@ -97,23 +530,17 @@ fn index() -> Option<NamedFile> {
     NamedFile::open(Path::new("target/deploy/index.html")).ok()
 }
 
-#[get("/<file..>")]
+// ranked below the API routes, /<file..> matches /history/<id> and /ratings/<player> as well
+#[get("/<file..>", rank = 10)]
 fn files(file: PathBuf) -> Option<NamedFile> {
     NamedFile::open(Path::new("target/deploy/").join(file)).ok()
 }
 
-fn connect_to_collection() -> Result<Collection, Error> {
-    let collection = Client::with_uri_str("mongodb://localhost:27017/")?
-            .database("Connect4DB")
-            .collection("games");
-
-    Ok(collection)
-}
-
-// using rocket_cors crate example: https://github.com/lawliet89/rocket_cors/blob/master/examples/fairing.rs
-fn main() {
+// the server with its routes, without starting it
+fn rocket(storage: storage::Storage, ratings: ratings::Ratings, admin_token: Option<String>) -> rocket::Rocket {
     // This will allow the frontend to make HTTP GET and POST requests
     // otherwise the request is rejected due to CORS error
+    // using rocket_cors crate example: https://github.com/lawliet89/rocket_cors/blob/master/examples/fairing.rs
     let allowed_origins = AllowedOrigins::all();
 
     let cors = rocket_cors::CorsOptions {
@@ -124,28 +551,100 @@ fn main() {
         ..Default::default()
     }.to_cors().unwrap();
 
-    let result = connect_to_collection();
-
-    match result {
-        Ok(collection) => {
-            // making the address just "localhost" doesn't work for frontend
-            let config = Config::build(Environment::Staging)
-                .address("127.0.0.1")
-                .port(8000)
-                .finalize()
-                .unwrap();
-            
-            let rocket = rocket::custom(config).manage(collection)
-                .mount("/", routes![
-                    index,
-                    files,
-                    database::get_histories,
-                    database::insert_history,
-                    database::delete_history,
-                ])
-                .attach(cors);
-            rocket.launch();
+    // making the address just "localhost" doesn't work for frontend
+    let config = Config::build(Environment::Staging)
+        .address("127.0.0.1")
+        .port(8000)
+        .finalize()
+        .unwrap();
+
+    rocket::custom(config)
+        .manage(storage)
+        .manage(ratings)
+        .manage(accounts::Accounts::default())
+        .manage(database::AdminToken(admin_token))
+        .mount("/", routes![
+            index,
+            files,
+            database::get_histories,
+            database::get_history,
+            database::get_player_stats,
+            database::get_one_player_stats,
+            database::get_leaderboard,
+            database::get_ratings,
+            database::get_player_ratings,
+            database::insert_history,
+            database::delete_history,
+            database::register,
+            database::login,
+            database::logout,
+            database::get_account,
+        ])
+        .attach(cors)
+}
+
+fn main() {
+    // the storage is picked with the HISTORY_STORAGE environment variable, see storage.rs
+    match storage::from_env() {
+        Ok(storage) => {
+            // games recorded before ratings were kept are rated once, in the order they were played
+            let ratings = ratings::Ratings::default();
+            match storage.ratings() {
+                Ok(current) if current.is_empty() => match ratings.rate_history(&*storage) {
+                    Ok(0) => (),
+                    Ok(games) => println!("Rated the players of {} recorded games", games),
+                    Err(err) => eprintln!("Failed to rate the recorded games: {}", err),
+                },
+                Ok(_) => (),
+                Err(err) => eprintln!("Failed to read the ratings: {}", err),
+            }
+            // DELETE /history is turned off without one
+            let admin_token = env::var("ADMIN_TOKEN").ok().filter(|token| !token.is_empty());
+            rocket(storage, ratings, admin_token).launch();
         },
         Err(err) => eprintln!("Failed to start backend: {}", err),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use rocket::http::{Header, Status};
+    use rocket::local::Client;
+    use crate::accounts::Accounts;
+    use crate::storage::{MemoryStorage, Storage};
+
+    const ADMIN_TOKEN: &str = "admin secret";
+
+    // a server with one account, and its login token
+    fn client() -> (Client, String) {
+        let storage: Storage = Box::new(MemoryStorage::default());
+        let (_, session) = Accounts::default().register(&*storage, "Alice", "correct horse").unwrap();
+        let rocket = super::rocket(storage, crate::ratings::Ratings::default(), Some(String::from(ADMIN_TOKEN)));
+        (Client::new(rocket).unwrap(), session.token)
+    }
+
+    fn delete_history(client: &Client, token: Option<&str>) -> Status {
+        let mut request = client.delete("/history");
+        if let Some(token) = token {
+            request = request.header(Header::new("Authorization", format!("Bearer {}", token)));
+        }
+        request.dispatch().status()
+    }
+
+    #[test]
+    fn only_the_admin_clears_the_history() {
+        let (client, player_token) = client();
+        assert_eq!(delete_history(&client, None), Status::Unauthorized);
+        assert_eq!(delete_history(&client, Some(&player_token)), Status::Forbidden);
+        assert_eq!(delete_history(&client, Some("admin")), Status::Forbidden);
+        assert_eq!(delete_history(&client, Some(ADMIN_TOKEN)), Status::Ok);
+    }
+
+    #[test]
+    fn no_admin_without_a_token() {
+        let storage: Storage = Box::new(MemoryStorage::default());
+        let client = Client::new(super::rocket(storage, crate::ratings::Ratings::default(), None)).unwrap();
+        assert_eq!(delete_history(&client, Some("")), Status::Forbidden);
+        assert_eq!(delete_history(&client, Some(ADMIN_TOKEN)), Status::Forbidden);
+    }
+}
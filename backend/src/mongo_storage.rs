@@ -0,0 +1,530 @@
+// History in the "games" collection of the Connect4DB MongoDB database, the current ratings in
+// "ratings", every rating after each game in "ratingHistory", and the players' accounts and
+// logins in "accounts" and "sessions"
+//
+// The field names are the ones the server always used, so existing databases keep working.
+use bson::{doc, oid::ObjectId, Bson, Document};
+use chrono::TimeZone;
+use mongodb::{
+    options::{FindOptions, ReplaceOptions},
+    Client, Collection, Database,
+};
+use crate::storage::{
+    name_key, seed_from_i64, seed_to_i64, AccountRecord, HistoryFilter, HistoryPage, HistoryQuery, HistoryRecord, HistoryStorage,
+    PlayerId, PlayerResults, RatingRecord, SessionRecord, SortKey, SortValue,
+};
+
+pub struct MongoStorage {
+    collection: Collection,
+    ratings: Collection,
+    rating_history: Collection,
+    accounts: Collection,
+    sessions: Collection,
+}
+
+// nothing is done for the indexes that exist
+fn create_indexes(database: &Database, collection: &str, indexes: &[Document]) -> Result<(), String> {
+    let indexes: Vec<Bson> = indexes.iter()
+        .map(|keys| {
+            let name = keys.keys().map(String::as_str).collect::<Vec<&str>>().join("_");
+            Bson::Document(doc! { "key": keys.clone(), "name": name })
+        })
+        .collect();
+    run_create_indexes(database, collection, indexes)
+}
+
+// no two documents of the collection can have the same value of the field
+fn create_unique_index(database: &Database, collection: &str, field: &str) -> Result<(), String> {
+    let mut keys = Document::new();
+    keys.insert(field, 1);
+    let index = doc! { "key": keys, "name": format!("{}_unique", field), "unique": true };
+    run_create_indexes(database, collection, vec![Bson::Document(index)])
+}
+
+fn run_create_indexes(database: &Database, collection: &str, indexes: Vec<Bson>) -> Result<(), String> {
+    database.run_command(doc! { "createIndexes": collection, "indexes": indexes }, None)
+        .map(|_| ())
+        .map_err(|err| format!("failed to create the indexes of {}: {}", collection, err))
+}
+
+// Mongo only compares values of the same type, so a game without one of the sort fields would
+// never be past a page's cursor. Games recorded before the field was kept get the value
+// to_record() reads for them instead, which is also the cursor's value for them.
+fn fill_sort_fields(games: &Collection) -> Result<(), String> {
+    let defaults = [
+        ("GameDate", Bson::from(chrono::Utc.timestamp_opt(0, 0).unwrap())),
+        ("DurationSeconds", Bson::I32(0)),
+        ("gameType", Bson::from("")),
+        ("Player1Name", Bson::from("")),
+        ("Player2Name", Bson::from("")),
+        ("WinnerName", Bson::from("")),
+        ("Difficulty", Bson::from("N/A")),
+    ];
+    for (field, value) in defaults.iter() {
+        let mut missing = Document::new();
+        missing.insert(*field, doc! { "$exists": false });
+        let mut default = Document::new();
+        default.insert(*field, value.clone());
+        games.update_many(missing, doc! { "$set": default }, None)
+            .map_err(|err| format!("failed to fill in {} of older games: {}", field, err))?;
+    }
+    Ok(())
+}
+
+// ratings used to be kept by name, they are made again from the games when the server starts
+// without any (see main())
+fn drop_name_ratings(database: &Database) -> Result<(), String> {
+    let by_name = database.collection("ratings").count_documents(doc! { "PlayerKey": { "$exists": false } }, None)
+        .map_err(|err| format!("collection count failed: {}", err))?;
+    if by_name > 0 {
+        for collection in ["ratings", "ratingHistory"].iter() {
+            database.collection(collection).delete_many(doc! {}, None)
+                .map_err(|err| format!("failed to remove the ratings kept by name: {}", err))?;
+        }
+    }
+    Ok(())
+}
+
+impl MongoStorage {
+    pub fn connect(uri: &str) -> Result<Self, String> {
+        let database = Client::with_uri_str(uri)
+            .map_err(|err| format!("invalid MongoDB URI {}: {}", uri, err))?
+            .database("Connect4DB");
+        // the client only connects on its first operation, so fail now rather than on the first request
+        database.run_command(doc! { "ping": 1 }, None)
+            .map_err(|err| format!("failed to connect to MongoDB at {}: {}", uri, err))?;
+        // for the filters and sort keys of the history list
+        create_indexes(&database, "games", &[
+            doc! { "GameDate": 1 },
+            doc! { "gameType": 1, "GameDate": 1 },
+            doc! { "Player1Name": 1, "GameDate": 1 },
+            doc! { "Player2Name": 1, "GameDate": 1 },
+            doc! { "WinnerName": 1, "GameDate": 1 },
+            doc! { "Difficulty": 1, "GameDate": 1 },
+            doc! { "BoardRows": 1, "BoardColumns": 1 },
+            doc! { "Player1Account": 1, "GameDate": 1 },
+            doc! { "Player2Account": 1, "GameDate": 1 },
+        ])?;
+        fill_sort_fields(&database.collection("games"))?;
+        drop_name_ratings(&database)?;
+        create_indexes(&database, "ratings", &[doc! { "PlayerKey": 1 }])?;
+        create_indexes(&database, "ratingHistory", &[doc! { "PlayerKey": 1, "_id": 1 }])?;
+        create_unique_index(&database, "accounts", "NameKey")?;
+        create_unique_index(&database, "sessions", "Token")?;
+        Ok(Self {
+            collection: database.collection("games"),
+            ratings: database.collection("ratings"),
+            rating_history: database.collection("ratingHistory"),
+            accounts: database.collection("accounts"),
+            sessions: database.collection("sessions"),
+        })
+    }
+}
+
+fn sort_field(sort: SortKey) -> &'static str {
+    match sort {
+        SortKey::Date => "GameDate",
+        SortKey::Duration => "DurationSeconds",
+        SortKey::GameType => "gameType",
+        SortKey::Player1 => "Player1Name",
+        SortKey::Player2 => "Player2Name",
+        SortKey::Winner => "WinnerName",
+        SortKey::Difficulty => "Difficulty",
+    }
+}
+
+fn sort_value(value: &SortValue) -> Bson {
+    match value {
+        SortValue::Date(date) => Bson::from(*date),
+        // stored as a 32 bit integer, numbers of different sizes compare the same
+        SortValue::Number(number) => Bson::from(*number),
+        SortValue::Text(text) => Bson::from(text.as_str()),
+    }
+}
+
+fn filter_document(filter: &HistoryFilter) -> Document {
+    let mut conditions = Vec::new();
+    let texts = [
+        ("gameType", &filter.game_type),
+        ("Difficulty", &filter.difficulty),
+    ];
+    for (field, value) in texts.iter() {
+        if let Some(value) = value {
+            let mut condition = Document::new();
+            condition.insert(*field, value.as_str());
+            conditions.push(condition);
+        }
+    }
+    if let Some(player) = &filter.player {
+        conditions.push(doc! { "$or": [player_condition(player, "Player1"), player_condition(player, "Player2")] });
+    }
+    if let Some(player) = &filter.player1 {
+        conditions.push(player_condition(player, "Player1"));
+    }
+    if let Some(player) = &filter.player2 {
+        conditions.push(player_condition(player, "Player2"));
+    }
+    // the winner is stored by name, which is one of the players' unless it was a draw
+    let won = |side: &str| {
+        let mut condition = Document::new();
+        condition.insert(format!("{}Account", side), doc! { "$ne": Bson::Null });
+        condition.insert("$expr", doc! { "$eq": ["$WinnerName", format!("${}Name", side)] });
+        condition
+    };
+    match &filter.winner {
+        Some(PlayerId::Account(account)) => {
+            let (mut player1, mut player2) = (won("Player1"), won("Player2"));
+            player1.insert("Player1Account", account.as_str());
+            player2.insert("Player2Account", account.as_str());
+            conditions.push(doc! { "$or": [player1, player2] });
+        },
+        Some(PlayerId::Name(name)) => conditions.push(doc! { "WinnerName": name.as_str(), "$nor": [won("Player1"), won("Player2")] }),
+        None => (),
+    }
+    if let Some(rows) = filter.board_rows {
+        conditions.push(doc! { "BoardRows": rows as i32 });
+    }
+    if let Some(columns) = filter.board_columns {
+        conditions.push(doc! { "BoardColumns": columns as i32 });
+    }
+    if let Some(from) = filter.from {
+        conditions.push(doc! { "GameDate": { "$gte": from } });
+    }
+    if let Some(to) = filter.to {
+        conditions.push(doc! { "GameDate": { "$lt": to } });
+    }
+    and(conditions)
+}
+
+// the player on one side of the game, `side` is Player1 or Player2
+fn player_condition(player: &PlayerId, side: &str) -> Document {
+    let mut condition = Document::new();
+    match player {
+        PlayerId::Account(account) => {
+            condition.insert(format!("{}Account", side), account.as_str());
+        },
+        // null matches games without the field too
+        PlayerId::Name(name) => {
+            condition.insert(format!("{}Name", side), name.as_str());
+            condition.insert(format!("{}Account", side), Bson::Null);
+        },
+    }
+    condition
+}
+
+fn and(conditions: Vec<Document>) -> Document {
+    if conditions.is_empty() {
+        Document::new()
+    } else {
+        doc! { "$and": conditions.into_iter().map(Bson::Document).collect::<Vec<Bson>>() }
+    }
+}
+
+// $sum counts in 32 bits and moves to 64 when it has to
+fn count(value: Option<&Bson>) -> u64 {
+    match value {
+        Some(Bson::I32(count)) => *count as u64,
+        Some(Bson::I64(count)) => *count as u64,
+        _ => 0,
+    }
+}
+
+fn to_rating(document: &Document) -> RatingRecord {
+    RatingRecord {
+        player: String::from(document.get("Player").and_then(Bson::as_str).unwrap_or("")),
+        account: document.get("Account").and_then(Bson::as_str).map(String::from),
+        rating: document.get("Rating").and_then(Bson::as_f64).unwrap_or(0.0),
+        deviation: document.get("Deviation").and_then(Bson::as_f64).unwrap_or(0.0),
+        volatility: document.get("Volatility").and_then(Bson::as_f64).unwrap_or(0.0),
+        games: document.get("Games").and_then(Bson::as_i32).unwrap_or(0) as u32,
+        game_id: String::from(document.get("GameId").and_then(Bson::as_str).unwrap_or("")),
+        date: document.get("Date").and_then(Bson::as_utc_date_time).cloned(),
+    }
+}
+
+fn to_ratings(cursor: mongodb::Cursor) -> Result<Vec<RatingRecord>, String> {
+    cursor
+        .map(|result| result.map(|document| to_rating(&document)).map_err(|err| format!("collection find failed: {}", err)))
+        .collect()
+}
+
+fn to_record(document: &Document) -> HistoryRecord {
+    HistoryRecord {
+        id: document.get("_id").and_then(Bson::as_object_id).map(ObjectId::to_hex).unwrap_or_default(),
+        game_type: String::from(document.get("gameType").and_then(Bson::as_str).unwrap_or("")),
+        player1: String::from(document.get("Player1Name").and_then(Bson::as_str).unwrap_or("")),
+        player2: String::from(document.get("Player2Name").and_then(Bson::as_str).unwrap_or("")),
+        winner: String::from(document.get("WinnerName").and_then(Bson::as_str).unwrap_or("")),
+        difficulty: String::from(document.get("Difficulty").and_then(Bson::as_str).unwrap_or("N/A")),
+        // games recorded before hints existed don't have these fields
+        player1_hints: document.get("Player1Hints").and_then(Bson::as_i32).unwrap_or(0) as u32,
+        player2_hints: document.get("Player2Hints").and_then(Bson::as_i32).unwrap_or(0) as u32,
+        // the start of 1970 was filled in for games without a date, see fill_sort_fields()
+        game_date: document.get("GameDate").and_then(Bson::as_utc_date_time).cloned().filter(|date| date.timestamp() != 0),
+        board_rows: document.get("BoardRows").and_then(Bson::as_i32).unwrap_or(0) as u32,
+        board_columns: document.get("BoardColumns").and_then(Bson::as_i32).unwrap_or(0) as u32,
+        moves: document.get("Moves")
+            .and_then(Bson::as_array)
+            .map(|moves| moves.iter().filter_map(Bson::as_str).map(String::from).collect())
+            .unwrap_or_default(),
+        first_player: String::from(document.get("FirstPlayer").and_then(Bson::as_str).unwrap_or("")),
+        duration_seconds: document.get("DurationSeconds").and_then(Bson::as_i32).unwrap_or(0) as u32,
+        ai_seed: document.get("AISeed").and_then(Bson::as_i64).map(seed_from_i64),
+        ai_level: document.get("AILevel").and_then(Bson::as_f64),
+        player1_account: document.get("Player1Account").and_then(Bson::as_str).map(String::from),
+        player2_account: document.get("Player2Account").and_then(Bson::as_str).map(String::from),
+    }
+}
+
+fn to_account(document: &Document) -> AccountRecord {
+    AccountRecord {
+        id: document.get("_id").and_then(Bson::as_object_id).map(ObjectId::to_hex).unwrap_or_default(),
+        name: String::from(document.get("Name").and_then(Bson::as_str).unwrap_or("")),
+        password_hash: String::from(document.get("PasswordHash").and_then(Bson::as_str).unwrap_or("")),
+        created: document.get("Created").and_then(Bson::as_utc_date_time).cloned().unwrap_or_else(chrono::Utc::now),
+    }
+}
+
+impl HistoryStorage for MongoStorage {
+    fn histories(&self, query: &HistoryQuery) -> Result<HistoryPage, String> {
+        let filter = filter_document(&query.filter);
+        let total = self.collection.count_documents(filter.clone(), None)
+            .map_err(|err| format!("collection count failed: {}", err))?;
+
+        let field = sort_field(query.sort);
+        let (direction, after) = if query.descending { (-1, "$lt") } else { (1, "$gt") };
+        let mut conditions = vec![filter];
+        let mut skip = query.offset as i64;
+        if let Some(cursor) = &query.after {
+            let id = ObjectId::with_string(&cursor.id).map_err(|_| String::from("invalid cursor"))?;
+            let value = sort_value(&cursor.value);
+            let mut past_value = Document::new();
+            past_value.insert(field, doc! { after: value.clone() });
+            let mut same_value = Document::new();
+            same_value.insert(field, value);
+            same_value.insert("_id", doc! { after: id });
+            conditions.push(doc! { "$or": [past_value, same_value] });
+            skip = 0;
+        }
+        let mut sort = Document::new();
+        sort.insert(field, direction);
+        sort.insert("_id", direction);
+        let options = FindOptions {
+            sort: Some(sort),
+            skip: Some(skip),
+            limit: Some(query.limit as i64 + 1),
+            ..Default::default()
+        };
+
+        let cursor = self.collection.find(and(conditions), options).map_err(|err| format!("collection find failed: {}", err))?;
+        let mut records = Vec::new();
+        for result in cursor {
+            match result {
+                Ok(document) => records.push(to_record(&document)),
+                Err(_) => eprintln!("result is not a document"),
+            }
+        }
+        Ok(query.page(records, total as u64))
+    }
+
+    fn results(&self, filter: &HistoryFilter) -> Result<Vec<PlayerResults>, String> {
+        // every game once for each player, grouped by player, game type, board size and difficulty
+        let pipeline = vec![
+            doc! { "$match": filter_document(filter) },
+            doc! { "$project": {
+                "gameType": 1, "BoardRows": 1, "BoardColumns": 1, "Difficulty": 1, "WinnerName": 1,
+                "player": [
+                    { "name": "$Player1Name", "account": "$Player1Account" },
+                    { "name": "$Player2Name", "account": "$Player2Account" }
+                ]
+            } },
+            doc! { "$unwind": "$player" },
+            doc! { "$group": {
+                "_id": {
+                    "player": "$player.name",
+                    "account": "$player.account",
+                    "gameType": "$gameType",
+                    "BoardRows": "$BoardRows",
+                    "BoardColumns": "$BoardColumns",
+                    "Difficulty": "$Difficulty"
+                },
+                "games": { "$sum": 1 },
+                "wins": { "$sum": { "$cond": [{ "$eq": ["$WinnerName", "$player.name"] }, 1, 0] } },
+                "draws": { "$sum": { "$cond": [{ "$eq": ["$WinnerName", "Draw"] }, 1, 0] } }
+            } },
+        ];
+        let cursor = self.collection.aggregate(pipeline, None).map_err(|err| format!("aggregate failed: {}", err))?;
+        let mut results = Vec::new();
+        for result in cursor {
+            let document = result.map_err(|err| format!("aggregate failed: {}", err))?;
+            let group = match document.get("_id").and_then(Bson::as_document) {
+                Some(group) => group,
+                None => continue,
+            };
+            // games recorded before the board size was kept don't have one
+            let text = |field, default| String::from(group.get(field).and_then(Bson::as_str).unwrap_or(default));
+            let size = |field| group.get(field).and_then(Bson::as_i32).unwrap_or(0) as u32;
+            // guests have no account
+            let account = group.get("account").and_then(Bson::as_str).map(String::from);
+            results.push(PlayerResults::from_counts(
+                (text("player", ""), account),
+                text("gameType", ""),
+                (size("BoardRows"), size("BoardColumns")),
+                text("Difficulty", "N/A"),
+                (count(document.get("games")), count(document.get("wins")), count(document.get("draws"))),
+            ));
+        }
+        Ok(results)
+    }
+
+    fn history(&self, id: &str) -> Result<Option<HistoryRecord>, String> {
+        // not an id this storage could have given out
+        let object_id = match ObjectId::with_string(id) {
+            Ok(object_id) => object_id,
+            Err(_) => return Ok(None),
+        };
+        self.collection.find_one(doc! { "_id": object_id }, None)
+            .map(|document| document.as_ref().map(to_record))
+            .map_err(|err| format!("collection find failed: {}", err))
+    }
+
+    fn insert(&self, record: HistoryRecord) -> Result<String, String> {
+        let mut document = doc! {
+            "gameType": record.game_type,
+            "Player1Name": record.player1,
+            "Player2Name": record.player2,
+            "WinnerName": record.winner,
+            "Difficulty": record.difficulty,
+            "Player1Hints": record.player1_hints as i32,
+            "Player2Hints": record.player2_hints as i32,
+            "GameDate": record.game_date.unwrap_or_else(chrono::Utc::now),
+            "BoardRows": record.board_rows as i32,
+            "BoardColumns": record.board_columns as i32,
+            "Moves": record.moves,
+            "FirstPlayer": record.first_player,
+            "DurationSeconds": record.duration_seconds as i32
+        };
+        if let Some(seed) = record.ai_seed {
+            document.insert("AISeed", seed_to_i64(seed));
+        }
+        if let Some(level) = record.ai_level {
+            document.insert("AILevel", level);
+        }
+        // the hex of the account's ObjectId
+        if let Some(account) = record.player1_account {
+            document.insert("Player1Account", account);
+        }
+        if let Some(account) = record.player2_account {
+            document.insert("Player2Account", account);
+        }
+        let result = self.collection.insert_one(document, None).map_err(|err| format!("insert failed: {}", err))?;
+        Ok(result.inserted_id.as_object_id().map(ObjectId::to_hex).unwrap_or_default())
+    }
+
+    fn clear(&self) -> Result<(), String> {
+        for collection in [&self.collection, &self.ratings, &self.rating_history].iter() {
+            // not drop(), the indexes are only created by connect()
+            collection.delete_many(doc! {}, None).map_err(|err| format!("delete failed: {}", err))?;
+        }
+        Ok(())
+    }
+
+    fn ratings(&self) -> Result<Vec<RatingRecord>, String> {
+        let cursor = self.ratings.find(None, None).map_err(|err| format!("collection find failed: {}", err))?;
+        to_ratings(cursor)
+    }
+
+    fn rating(&self, player: &PlayerId) -> Result<Option<RatingRecord>, String> {
+        self.ratings.find_one(doc! { "PlayerKey": player.key() }, None)
+            .map(|document| document.as_ref().map(to_rating))
+            .map_err(|err| format!("collection find failed: {}", err))
+    }
+
+    fn rating_history(&self, player: &PlayerId) -> Result<Vec<RatingRecord>, String> {
+        let options = FindOptions { sort: Some(doc! { "_id": 1 }), ..Default::default() };
+        let cursor = self.rating_history.find(doc! { "PlayerKey": player.key() }, options)
+            .map_err(|err| format!("collection find failed: {}", err))?;
+        to_ratings(cursor)
+    }
+
+    fn add_rating(&self, rating: RatingRecord) -> Result<(), String> {
+        let key = rating.player_id().key();
+        let mut document = doc! {
+            "PlayerKey": key.as_str(),
+            "Player": rating.player.as_str(),
+            "Rating": rating.rating,
+            "Deviation": rating.deviation,
+            "Volatility": rating.volatility,
+            "Games": rating.games as i32,
+            "GameId": rating.game_id
+        };
+        if let Some(date) = rating.date {
+            document.insert("Date", date);
+        }
+        if let Some(account) = rating.account {
+            document.insert("Account", account);
+        }
+        // a player has one current rating, replaced every game
+        let options = ReplaceOptions { upsert: Some(true), ..Default::default() };
+        self.ratings.replace_one(doc! { "PlayerKey": key.as_str() }, document.clone(), options)
+            .map_err(|err| format!("replace failed: {}", err))?;
+        self.rating_history.insert_one(document, None)
+            .map(|_| ())
+            .map_err(|err| format!("insert failed: {}", err))
+    }
+
+    fn add_account(&self, account: AccountRecord) -> Result<String, String> {
+        let document = doc! {
+            "NameKey": name_key(&account.name),
+            "Name": account.name,
+            "PasswordHash": account.password_hash,
+            "Created": account.created
+        };
+        let result = self.accounts.insert_one(document, None).map_err(|err| format!("insert failed: {}", err))?;
+        Ok(result.inserted_id.as_object_id().map(ObjectId::to_hex).unwrap_or_default())
+    }
+
+    fn account(&self, id: &str) -> Result<Option<AccountRecord>, String> {
+        let object_id = match ObjectId::with_string(id) {
+            Ok(object_id) => object_id,
+            Err(_) => return Ok(None),
+        };
+        self.accounts.find_one(doc! { "_id": object_id }, None)
+            .map(|document| document.as_ref().map(to_account))
+            .map_err(|err| format!("collection find failed: {}", err))
+    }
+
+    fn account_by_name(&self, name: &str) -> Result<Option<AccountRecord>, String> {
+        self.accounts.find_one(doc! { "NameKey": name_key(name) }, None)
+            .map(|document| document.as_ref().map(to_account))
+            .map_err(|err| format!("collection find failed: {}", err))
+    }
+
+    fn add_session(&self, session: SessionRecord) -> Result<(), String> {
+        let document = doc! {
+            "Token": session.token,
+            "AccountId": session.account_id,
+            "Expires": session.expires
+        };
+        self.sessions.insert_one(document, None)
+            .map(|_| ())
+            .map_err(|err| format!("insert failed: {}", err))
+    }
+
+    fn session(&self, token: &str) -> Result<Option<SessionRecord>, String> {
+        let document = self.sessions.find_one(doc! { "Token": token }, None)
+            .map_err(|err| format!("collection find failed: {}", err))?;
+        Ok(document.map(|document| SessionRecord {
+            token: String::from(token),
+            account_id: String::from(document.get("AccountId").and_then(Bson::as_str).unwrap_or("")),
+            // a session without a time has expired
+            expires: document.get("Expires").and_then(Bson::as_utc_date_time).cloned().unwrap_or_else(chrono::Utc::now),
+        }))
+    }
+
+    fn remove_session(&self, token: &str) -> Result<(), String> {
+        self.sessions.delete_one(doc! { "Token": token }, None)
+            .map(|_| ())
+            .map_err(|err| format!("delete failed: {}", err))
+    }
+}
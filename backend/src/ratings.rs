@@ -0,0 +1,351 @@
+// Glicko-2 ratings of the players, updated after every recorded game
+//
+// Every game is a rating period of its own, both players are rated from their ratings before
+// it. In a game against the computer the difficulty it played at is rated as a player of its
+// own, "Computer (Hard)", so beating Hard counts for more than beating Easy. The formulas are
+// from http://www.glicko.net/glicko/glicko2.pdf
+use std::f64::consts::PI;
+use std::sync::Mutex;
+use serde::Serialize;
+use crate::storage::{HistoryQuery, HistoryRecord, HistoryStorage, PlayerId, RatingRecord, MAX_PAGE_SIZE};
+
+pub const COMPUTER: &str = "Computer"; // name of the computer player in the history
+
+const INITIAL_RATING: f64 = 1500.0;
+const INITIAL_DEVIATION: f64 = 350.0;
+const INITIAL_VOLATILITY: f64 = 0.06;
+const TAU: f64 = 0.5;         // how fast the volatility can change
+const SCALE: f64 = 173.7178;  // from the Glicko scale to the Glicko-2 one
+const TOLERANCE: f64 = 0.000001;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rating {
+    pub rating: f64,
+    pub deviation: f64,
+    pub volatility: f64,
+}
+
+impl Default for Rating {
+    // a player without games
+    fn default() -> Self {
+        Rating { rating: INITIAL_RATING, deviation: INITIAL_DEVIATION, volatility: INITIAL_VOLATILITY }
+    }
+}
+
+impl From<&RatingRecord> for Rating {
+    fn from(record: &RatingRecord) -> Self {
+        Rating { rating: record.rating, deviation: record.deviation, volatility: record.volatility }
+    }
+}
+
+impl Rating {
+    // after a rating period with these games, each against an opponent with a score of 1 for a
+    // win, 0.5 for a draw and 0 for a loss
+    pub fn update(self, games: &[(Rating, f64)]) -> Rating {
+        let mu = (self.rating - INITIAL_RATING) / SCALE;
+        let phi = self.deviation / SCALE;
+        if games.is_empty() {
+            // only the deviation grows
+            let phi = (phi * phi + self.volatility * self.volatility).sqrt();
+            return Rating { deviation: phi * SCALE, ..self };
+        }
+
+        // step 3 and 4: the estimated variance and improvement from the games
+        let mut variance_inverse = 0.0;
+        let mut improvement = 0.0;
+        for (opponent, score) in games {
+            let mu_opponent = (opponent.rating - INITIAL_RATING) / SCALE;
+            let phi_opponent = opponent.deviation / SCALE;
+            let g = 1.0 / (1.0 + 3.0 * phi_opponent * phi_opponent / (PI * PI)).sqrt();
+            let expected = 1.0 / (1.0 + (-g * (mu - mu_opponent)).exp());
+            variance_inverse += g * g * expected * (1.0 - expected);
+            improvement += g * (score - expected);
+        }
+        let variance = 1.0 / variance_inverse;
+        let delta = variance * improvement;
+
+        // step 5: the new volatility, by the Illinois algorithm
+        let a = (self.volatility * self.volatility).ln();
+        let f = |x: f64| {
+            let e = x.exp();
+            e * (delta * delta - phi * phi - variance - e) / (2.0 * (phi * phi + variance + e).powi(2)) - (x - a) / (TAU * TAU)
+        };
+        let mut lower = a;
+        let mut upper = if delta * delta > phi * phi + variance {
+            (delta * delta - phi * phi - variance).ln()
+        } else {
+            let mut k = 1.0;
+            while f(a - k * TAU) < 0.0 {
+                k += 1.0;
+            }
+            a - k * TAU
+        };
+        let (mut f_lower, mut f_upper) = (f(lower), f(upper));
+        while (upper - lower).abs() > TOLERANCE {
+            let middle = lower + (lower - upper) * f_lower / (f_upper - f_lower);
+            let f_middle = f(middle);
+            if f_middle * f_upper <= 0.0 {
+                lower = upper;
+                f_lower = f_upper;
+            } else {
+                f_lower /= 2.0;
+            }
+            upper = middle;
+            f_upper = f_middle;
+        }
+        let volatility = (lower / 2.0).exp();
+
+        // step 6 to 8: the new deviation and rating
+        let phi_before = (phi * phi + volatility * volatility).sqrt();
+        let phi = 1.0 / (1.0 / (phi_before * phi_before) + 1.0 / variance).sqrt();
+        let mu = mu + phi * phi * improvement;
+        Rating { rating: mu * SCALE + INITIAL_RATING, deviation: phi * SCALE, volatility }
+    }
+}
+
+// who the players of a game are rated as, with the name shown for them
+pub fn rated_players(record: &HistoryRecord) -> ((PlayerId, String), (PlayerId, String)) {
+    let against_computer = !record.difficulty.is_empty() && record.difficulty != "N/A";
+    let rated = |player: PlayerId, name: &str| {
+        if against_computer && player == PlayerId::Name(String::from(COMPUTER)) {
+            let difficulty = format!("{} ({})", COMPUTER, record.difficulty);
+            (PlayerId::Name(difficulty.clone()), difficulty)
+        } else {
+            (player, String::from(name))
+        }
+    };
+    (rated(record.player1_id(), &record.player1), rated(record.player2_id(), &record.player2))
+}
+
+// computer difficulties are rated as players, see rated_players()
+pub fn is_computer(rating: &RatingRecord) -> bool {
+    let player = &rating.player;
+    rating.account.is_none() && (player == COMPUTER || (player.starts_with(&format!("{} (", COMPUTER)) && player.ends_with(')')))
+}
+
+// updates happen one game at a time, two games recorded at once would otherwise both start
+// from the same rating
+#[derive(Default)]
+pub struct Ratings {
+    updating: Mutex<()>,
+}
+
+impl Ratings {
+    // rates both players of a recorded game, which has its id
+    pub fn record_game(&self, storage: &dyn HistoryStorage, record: &HistoryRecord) -> Result<(), String> {
+        let _updating = self.updating.lock().unwrap();
+        rate_game(storage, record)
+    }
+
+    // rates every recorded game in order, for a history recorded before ratings were kept.
+    // Returns the number of games.
+    pub fn rate_history(&self, storage: &dyn HistoryStorage) -> Result<usize, String> {
+        let _updating = self.updating.lock().unwrap();
+        let mut query = HistoryQuery { limit: MAX_PAGE_SIZE, ..HistoryQuery::default() };
+        let mut games = 0;
+        loop {
+            let page = storage.histories(&query)?;
+            for record in &page.records {
+                rate_game(storage, record)?;
+            }
+            games += page.records.len();
+            match page.next {
+                Some(cursor) => query.after = Some(cursor),
+                None => return Ok(games),
+            }
+        }
+    }
+}
+
+fn rate_game(storage: &dyn HistoryStorage, record: &HistoryRecord) -> Result<(), String> {
+    let ((player1, name1), (player2, name2)) = rated_players(record);
+    // nobody gains anything playing themselves
+    if player1 == player2 {
+        return Ok(());
+    }
+    let score = if record.winner == record.player1 {
+        1.0
+    } else if record.winner == record.player2 {
+        0.0
+    } else {
+        0.5
+    };
+    let before1 = storage.rating(&player1)?;
+    let before2 = storage.rating(&player2)?;
+    let rating1 = before1.as_ref().map(Rating::from).unwrap_or_default();
+    let rating2 = before2.as_ref().map(Rating::from).unwrap_or_default();
+    let updates = [
+        (player1, name1, rating1.update(&[(rating2, score)]), before1),
+        (player2, name2, rating2.update(&[(rating1, 1.0 - score)]), before2),
+    ];
+    for (player, name, after, before) in updates.iter() {
+        storage.add_rating(RatingRecord {
+            player: name.clone(),
+            account: player.clone().into_account(),
+            rating: after.rating,
+            deviation: after.deviation,
+            volatility: after.volatility,
+            games: before.as_ref().map(|before| before.games).unwrap_or(0) + 1,
+            game_id: record.id.clone(),
+            date: record.game_date,
+        })?;
+    }
+    Ok(())
+}
+
+#[derive(Serialize, Debug)]
+pub struct RatedPlayer {
+    pub rank: usize, // from 1
+    pub player: String,
+    pub account: Option<String>, // None for guests and the computer
+    pub rating: f64,
+    pub deviation: f64, // the rating is within twice this 95% of the time
+    pub volatility: f64,
+    pub games: u32,
+    pub computer: bool, // a difficulty of the computer
+}
+
+// the best rated players with at least `min_games` games, without the computer unless asked for
+pub fn ranking(ratings: Vec<RatingRecord>, limit: usize, min_games: u32, computer: bool) -> Vec<RatedPlayer> {
+    let mut ratings: Vec<RatingRecord> = ratings.into_iter()
+        .filter(|rating| rating.games >= min_games && (computer || !is_computer(rating)))
+        .collect();
+    ratings.sort_by(|a, b| b.rating.partial_cmp(&a.rating).unwrap().then(a.player.cmp(&b.player)));
+    ratings.into_iter()
+        .take(limit)
+        .enumerate()
+        .map(|(index, rating)| RatedPlayer {
+            rank: index + 1,
+            computer: is_computer(&rating),
+            player: rating.player,
+            account: rating.account,
+            rating: rating.rating,
+            deviation: rating.deviation,
+            volatility: rating.volatility,
+            games: rating.games,
+        })
+        .collect()
+}
+
+#[derive(Serialize, Debug)]
+pub struct RatingPoint {
+    pub game_id: String,
+    pub date: String, // RFC 3339, empty if unknown
+    pub rating: f64,
+    pub deviation: f64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct PlayerRatings {
+    pub player: String,
+    pub account: Option<String>,
+    pub rating: f64,
+    pub deviation: f64,
+    pub volatility: f64,
+    pub games: u32,
+    pub computer: bool,
+    pub history: Vec<RatingPoint>, // after each game, oldest first
+}
+
+pub fn player_ratings(current: RatingRecord, history: Vec<RatingRecord>) -> PlayerRatings {
+    PlayerRatings {
+        computer: is_computer(&current),
+        player: current.player,
+        account: current.account,
+        rating: current.rating,
+        deviation: current.deviation,
+        volatility: current.volatility,
+        games: current.games,
+        history: history.into_iter()
+            .map(|rating| RatingPoint {
+                game_id: rating.game_id,
+                date: rating.date.map(|date| date.to_rfc3339()).unwrap_or_default(),
+                rating: rating.rating,
+                deviation: rating.deviation,
+            })
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemoryStorage;
+
+    fn assert_near(value: f64, expected: f64, tolerance: f64) {
+        assert!((value - expected).abs() < tolerance, "{} is not {}", value, expected);
+    }
+
+    // the example worked through in the paper
+    #[test]
+    fn glickman_example() {
+        let player = Rating { rating: 1500.0, deviation: 200.0, volatility: 0.06 };
+        let games = [
+            (Rating { rating: 1400.0, deviation: 30.0, volatility: 0.06 }, 1.0),
+            (Rating { rating: 1550.0, deviation: 100.0, volatility: 0.06 }, 0.0),
+            (Rating { rating: 1700.0, deviation: 300.0, volatility: 0.06 }, 0.0),
+        ];
+        let after = player.update(&games);
+        assert_near(after.rating, 1464.06, 0.01);
+        assert_near(after.deviation, 151.52, 0.01);
+        assert_near(after.volatility, 0.05999, 0.00001);
+    }
+
+    #[test]
+    fn no_games() {
+        let player = Rating { rating: 1500.0, deviation: 200.0, volatility: 0.06 };
+        let after = player.update(&[]);
+        assert_eq!((after.rating, after.volatility), (1500.0, 0.06));
+        assert_near(after.deviation, 200.27, 0.01);
+    }
+
+    #[test]
+    fn rating_recorded_games() {
+        let storage = MemoryStorage::default();
+        let mut record = HistoryRecord {
+            id: String::from("1"),
+            game_type: String::from("Connect-4"),
+            player1: String::from("alice"),
+            player2: String::from(COMPUTER),
+            winner: String::from("alice"),
+            difficulty: String::from("Hard"),
+            player1_hints: 0,
+            player2_hints: 0,
+            game_date: None,
+            board_rows: 6,
+            board_columns: 7,
+            moves: Vec::new(),
+            first_player: String::from("alice"),
+            duration_seconds: 0,
+            ai_seed: None,
+            ai_level: None,
+            player1_account: Some(String::from("1")),
+            player2_account: None,
+        };
+        let alice = PlayerId::Account(String::from("1"));
+        let computer = PlayerId::Name(String::from("Computer (Hard)"));
+        assert_eq!(
+            rated_players(&record),
+            ((alice.clone(), String::from("alice")), (computer.clone(), String::from("Computer (Hard)"))),
+        );
+        let ratings = Ratings::default();
+        ratings.record_game(&storage, &record).unwrap();
+        record.id = String::from("2");
+        ratings.record_game(&storage, &record).unwrap();
+
+        // a guest under the same name is someone else
+        assert!(storage.rating(&PlayerId::Name(String::from("alice"))).unwrap().is_none());
+        let alice = storage.rating(&alice).unwrap().unwrap();
+        let computer = storage.rating(&computer).unwrap().unwrap();
+        assert_eq!((alice.games, computer.games), (2, 2));
+        assert_eq!(alice.game_id, "2");
+        assert!(alice.rating > INITIAL_RATING && computer.rating < INITIAL_RATING);
+        assert_near(alice.rating - INITIAL_RATING, INITIAL_RATING - computer.rating, 0.000001);
+        assert_eq!(storage.rating_history(&PlayerId::Account(String::from("1"))).unwrap().len(), 2);
+
+        let ranked = ranking(storage.ratings().unwrap(), 10, 1, false);
+        assert_eq!(ranked.len(), 1);
+        assert_eq!((ranked[0].rank, ranked[0].player.as_str(), ranked[0].computer), (1, "alice", false));
+        assert_eq!(ranked[0].account.as_deref(), Some("1"));
+    }
+}
@@ -0,0 +1,524 @@
+// History in a SQLite file, for running the server without a database server
+//
+// The table is created when the file is opened, and columns added since are added to older
+// files. SQLite is bundled into the server, nothing has to be installed.
+use std::sync::Mutex;
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection, OptionalExtension, Row, ToSql, NO_PARAMS};
+use crate::storage::{
+    name_key, seed_from_i64, seed_to_i64, AccountRecord, HistoryFilter, HistoryPage, HistoryQuery, HistoryRecord, HistoryStorage,
+    PlayerId, PlayerResults, RatingRecord, SessionRecord, SortKey, SortValue,
+};
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS games (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        game_type TEXT NOT NULL,
+        player1 TEXT NOT NULL,
+        player2 TEXT NOT NULL,
+        winner TEXT NOT NULL,
+        difficulty TEXT NOT NULL,
+        player1_hints INTEGER NOT NULL DEFAULT 0,
+        player2_hints INTEGER NOT NULL DEFAULT 0,
+        game_date TEXT
+    );
+    CREATE TABLE IF NOT EXISTS ratings (
+        player_key TEXT PRIMARY KEY, -- see PlayerId::key()
+        player TEXT NOT NULL,
+        account INTEGER,
+        rating REAL NOT NULL,
+        deviation REAL NOT NULL,
+        volatility REAL NOT NULL,
+        games INTEGER NOT NULL,
+        game_id TEXT NOT NULL,
+        date TEXT
+    );
+    CREATE TABLE IF NOT EXISTS rating_history (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        player_key TEXT NOT NULL,
+        player TEXT NOT NULL,
+        account INTEGER,
+        rating REAL NOT NULL,
+        deviation REAL NOT NULL,
+        volatility REAL NOT NULL,
+        games INTEGER NOT NULL,
+        game_id TEXT NOT NULL,
+        date TEXT
+    );
+    CREATE TABLE IF NOT EXISTS accounts (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        name TEXT NOT NULL,
+        name_key TEXT NOT NULL UNIQUE, -- see storage::name_key()
+        password_hash TEXT NOT NULL,
+        created TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS sessions (
+        token TEXT PRIMARY KEY,
+        account_id INTEGER NOT NULL REFERENCES accounts (id),
+        expires TEXT NOT NULL
+    );";
+
+// added after the first version of the table, in order
+const ADDED_COLUMNS: &[(&str, &str)] = &[
+    ("board_rows", "INTEGER NOT NULL DEFAULT 0"),
+    ("board_columns", "INTEGER NOT NULL DEFAULT 0"),
+    ("moves", "TEXT NOT NULL DEFAULT ''"), // separated by spaces
+    ("first_player", "TEXT NOT NULL DEFAULT ''"),
+    ("duration_seconds", "INTEGER NOT NULL DEFAULT 0"),
+    ("ai_seed", "INTEGER"),
+    ("player1_account", "INTEGER"), // ids in the accounts table, NULL for guests
+    ("player2_account", "INTEGER"),
+    ("ai_level", "REAL"),
+];
+
+// for the filters and sort keys of the history list, made after the columns are added
+const INDEXES: &str = "
+    CREATE INDEX IF NOT EXISTS games_game_date ON games (game_date, id);
+    CREATE INDEX IF NOT EXISTS games_game_type ON games (game_type, game_date);
+    CREATE INDEX IF NOT EXISTS games_player1 ON games (player1, game_date);
+    CREATE INDEX IF NOT EXISTS games_player2 ON games (player2, game_date);
+    CREATE INDEX IF NOT EXISTS games_winner ON games (winner, game_date);
+    CREATE INDEX IF NOT EXISTS games_difficulty ON games (difficulty, game_date);
+    CREATE INDEX IF NOT EXISTS games_board_size ON games (board_rows, board_columns);
+    CREATE INDEX IF NOT EXISTS games_player1_account ON games (player1_account, game_date);
+    CREATE INDEX IF NOT EXISTS games_player2_account ON games (player2_account, game_date);
+    CREATE INDEX IF NOT EXISTS rating_history_player_key ON rating_history (player_key, id);";
+
+const RATING_COLUMNS: &str = "player, account, rating, deviation, volatility, games, game_id, date";
+
+const COLUMNS: &str = "id, game_type, player1, player2, winner, difficulty, player1_hints, player2_hints, game_date, \
+                       board_rows, board_columns, moves, first_player, duration_seconds, ai_seed, player1_account, \
+                       player2_account, ai_level";
+
+const ACCOUNT_COLUMNS: &str = "id, name, password_hash, created";
+
+// a connection can't be shared between threads, requests take turns with it
+pub struct SqliteStorage {
+    connection: Mutex<Connection>,
+}
+
+impl SqliteStorage {
+    pub fn open(path: &str) -> Result<Self, String> {
+        let connection = Connection::open(path).map_err(|err| format!("failed to open {}: {}", path, err))?;
+        drop_name_ratings(&connection).map_err(|err| format!("failed to update the tables in {}: {}", path, err))?;
+        connection.execute_batch(SCHEMA).map_err(|err| format!("failed to create the tables in {}: {}", path, err))?;
+        add_columns(&connection).map_err(|err| format!("failed to update the tables in {}: {}", path, err))?;
+        connection.execute_batch(INDEXES).map_err(|err| format!("failed to create the indexes in {}: {}", path, err))?;
+        Ok(Self { connection: Mutex::new(connection) })
+    }
+}
+
+fn columns(connection: &Connection, table: &str) -> rusqlite::Result<Vec<String>> {
+    let mut statement = connection.prepare(&format!("PRAGMA table_info({})", table))?;
+    let columns = statement.query_map(NO_PARAMS, |row| row.get::<_, String>(1))?;
+    columns.collect()
+}
+
+// ratings used to be kept by name, they are made again from the games when the server starts
+// without any (see main())
+fn drop_name_ratings(connection: &Connection) -> rusqlite::Result<()> {
+    let existing = columns(connection, "ratings")?;
+    if !existing.is_empty() && !existing.iter().any(|column| column == "player_key") {
+        connection.execute_batch("DROP TABLE ratings; DROP TABLE IF EXISTS rating_history;")?;
+    }
+    Ok(())
+}
+
+fn add_columns(connection: &Connection) -> rusqlite::Result<()> {
+    let existing = columns(connection, "games")?;
+    for (name, definition) in ADDED_COLUMNS {
+        if !existing.iter().any(|column| column == name) {
+            connection.execute(&format!("ALTER TABLE games ADD COLUMN {} {}", name, definition), NO_PARAMS)?;
+        }
+    }
+    Ok(())
+}
+
+fn sort_column(sort: SortKey) -> &'static str {
+    match sort {
+        SortKey::Date => "game_date",
+        SortKey::Duration => "duration_seconds",
+        SortKey::GameType => "game_type",
+        SortKey::Player1 => "player1",
+        SortKey::Player2 => "player2",
+        SortKey::Winner => "winner",
+        SortKey::Difficulty => "difficulty",
+    }
+}
+
+fn sort_value(value: &SortValue) -> Box<dyn ToSql> {
+    match value {
+        SortValue::Date(date) => Box::new(*date),
+        SortValue::Number(number) => Box::new(*number),
+        SortValue::Text(text) => Box::new(text.clone()),
+    }
+}
+
+// a WHERE clause, with the values for its ?s in order
+#[derive(Default)]
+struct Conditions {
+    sql: Vec<String>,
+    values: Vec<Box<dyn ToSql>>,
+}
+
+impl Conditions {
+    fn add(&mut self, sql: &str, values: Vec<Box<dyn ToSql>>) {
+        self.sql.push(String::from(sql));
+        self.values.extend(values);
+    }
+
+    fn clause(&self) -> String {
+        if self.sql.is_empty() {
+            String::new()
+        } else {
+            format!(" WHERE {}", self.sql.join(" AND "))
+        }
+    }
+}
+
+fn filter_conditions(filter: &HistoryFilter) -> Conditions {
+    let mut conditions = Conditions::default();
+    let texts = [
+        ("game_type = ?", &filter.game_type),
+        ("difficulty = ?", &filter.difficulty),
+    ];
+    for (sql, value) in texts.iter() {
+        if let Some(value) = value {
+            conditions.add(sql, vec![Box::new(value.clone())]);
+        }
+    }
+    let players = [
+        (&filter.player, &["player1", "player2"][..]),
+        (&filter.player1, &["player1"][..]),
+        (&filter.player2, &["player2"][..]),
+    ];
+    for (player, columns) in players.iter() {
+        if let Some(player) = player {
+            let (sql, values): (Vec<String>, Vec<Box<dyn ToSql>>) = columns.iter()
+                .map(|column| player_condition(player, column))
+                .unzip();
+            conditions.add(&format!("({})", sql.join(" OR ")), values);
+        }
+    }
+    match &filter.winner {
+        // the winner is stored by name, which is one of the players' unless it was a draw
+        Some(PlayerId::Account(account)) => conditions.add(
+            "((winner = player1 AND player1_account = ?) OR (winner = player2 AND player2_account = ?))",
+            vec![Box::new(stored_account_id(account)), Box::new(stored_account_id(account))],
+        ),
+        Some(PlayerId::Name(name)) => conditions.add(
+            "(winner = ? AND NOT (winner = player1 AND player1_account IS NOT NULL) AND NOT (winner = player2 AND player2_account IS NOT NULL))",
+            vec![Box::new(name.clone())],
+        ),
+        None => (),
+    }
+    if let Some(rows) = filter.board_rows {
+        conditions.add("board_rows = ?", vec![Box::new(rows as i64)]);
+    }
+    if let Some(columns) = filter.board_columns {
+        conditions.add("board_columns = ?", vec![Box::new(columns as i64)]);
+    }
+    if let Some(from) = filter.from {
+        conditions.add("game_date >= ?", vec![Box::new(from)]);
+    }
+    if let Some(to) = filter.to {
+        conditions.add("game_date < ?", vec![Box::new(to)]);
+    }
+    conditions
+}
+
+fn to_record(row: &Row) -> rusqlite::Result<HistoryRecord> {
+    let moves: String = row.get(11)?;
+    Ok(HistoryRecord {
+        id: row.get::<_, i64>(0)?.to_string(),
+        game_type: row.get(1)?,
+        player1: row.get(2)?,
+        player2: row.get(3)?,
+        winner: row.get(4)?,
+        difficulty: row.get(5)?,
+        player1_hints: row.get::<_, i64>(6)? as u32,
+        player2_hints: row.get::<_, i64>(7)? as u32,
+        game_date: row.get::<_, Option<DateTime<Utc>>>(8)?,
+        board_rows: row.get::<_, i64>(9)? as u32,
+        board_columns: row.get::<_, i64>(10)? as u32,
+        moves: moves.split_whitespace().map(String::from).collect(),
+        first_player: row.get(12)?,
+        duration_seconds: row.get::<_, i64>(13)? as u32,
+        ai_seed: row.get::<_, Option<i64>>(14)?.map(seed_from_i64),
+        player1_account: row.get::<_, Option<i64>>(15)?.map(|id| id.to_string()),
+        player2_account: row.get::<_, Option<i64>>(16)?.map(|id| id.to_string()),
+        ai_level: row.get(17)?,
+    })
+}
+
+fn to_account(row: &Row) -> rusqlite::Result<AccountRecord> {
+    Ok(AccountRecord {
+        id: row.get::<_, i64>(0)?.to_string(),
+        name: row.get(1)?,
+        password_hash: row.get(2)?,
+        created: row.get(3)?,
+    })
+}
+
+// account ids are numbers here, None for anything else
+fn account_id(id: &Option<String>) -> Option<i64> {
+    id.as_ref().and_then(|id| id.parse().ok())
+}
+
+// an id this storage never gave out matches nothing
+fn stored_account_id(id: &str) -> i64 {
+    id.parse().unwrap_or(-1)
+}
+
+// the player on one side of the game, `column` is player1 or player2
+fn player_condition(player: &PlayerId, column: &str) -> (String, Box<dyn ToSql>) {
+    match player {
+        PlayerId::Account(account) => (format!("{}_account = ?", column), Box::new(stored_account_id(account))),
+        PlayerId::Name(name) => (format!("({0} = ? AND {0}_account IS NULL)", column), Box::new(name.clone())),
+    }
+}
+
+fn to_rating(row: &Row) -> rusqlite::Result<RatingRecord> {
+    Ok(RatingRecord {
+        player: row.get(0)?,
+        account: row.get::<_, Option<i64>>(1)?.map(|id| id.to_string()),
+        rating: row.get(2)?,
+        deviation: row.get(3)?,
+        volatility: row.get(4)?,
+        games: row.get::<_, i64>(5)? as u32,
+        game_id: row.get(6)?,
+        date: row.get::<_, Option<DateTime<Utc>>>(7)?,
+    })
+}
+
+impl HistoryStorage for SqliteStorage {
+    fn histories(&self, query: &HistoryQuery) -> Result<HistoryPage, String> {
+        let connection = self.connection.lock().unwrap();
+        let mut conditions = filter_conditions(&query.filter);
+        let total: i64 = connection
+            .query_row(&format!("SELECT COUNT(*) FROM games{}", conditions.clause()), &conditions.values, |row| row.get(0))
+            .map_err(|err| format!("query failed: {}", err))?;
+
+        let column = sort_column(query.sort);
+        let (direction, after) = if query.descending { ("DESC", "<") } else { ("ASC", ">") };
+        let mut offset = query.offset as i64;
+        if let Some(cursor) = &query.after {
+            let id: i64 = cursor.id.parse().map_err(|_| String::from("invalid cursor"))?;
+            conditions.add(
+                &format!("({0} {1} ? OR ({0} = ? AND id {1} ?))", column, after),
+                vec![sort_value(&cursor.value), sort_value(&cursor.value), Box::new(id)],
+            );
+            offset = 0;
+        }
+        let sql = format!(
+            "SELECT {} FROM games{} ORDER BY {2} {3}, id {3} LIMIT ? OFFSET ?",
+            COLUMNS, conditions.clause(), column, direction
+        );
+        conditions.values.push(Box::new(query.limit as i64 + 1));
+        conditions.values.push(Box::new(offset));
+
+        let mut statement = connection.prepare(&sql).map_err(|err| format!("query failed: {}", err))?;
+        let rows = statement.query_map(&conditions.values, to_record).map_err(|err| format!("query failed: {}", err))?;
+        let records = rows.collect::<rusqlite::Result<Vec<HistoryRecord>>>().map_err(|err| format!("invalid row: {}", err))?;
+        Ok(query.page(records, total as u64))
+    }
+
+    fn results(&self, filter: &HistoryFilter) -> Result<Vec<PlayerResults>, String> {
+        // every game once for each player, the filter applies to both halves
+        let mut conditions = filter_conditions(filter);
+        let clause = conditions.clause();
+        conditions.values.extend(filter_conditions(filter).values);
+        let sql = format!(
+            "SELECT player, account, game_type, board_rows, board_columns, difficulty,
+                    COUNT(*), SUM(winner = player), SUM(winner = 'Draw')
+             FROM (SELECT player1 AS player, player1_account AS account, game_type, board_rows, board_columns, difficulty, winner FROM games{0}
+                   UNION ALL
+                   SELECT player2 AS player, player2_account AS account, game_type, board_rows, board_columns, difficulty, winner FROM games{0})
+             GROUP BY player, account, game_type, board_rows, board_columns, difficulty",
+            clause
+        );
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection.prepare(&sql).map_err(|err| format!("query failed: {}", err))?;
+        let rows = statement
+            .query_map(&conditions.values, |row| {
+                Ok(PlayerResults::from_counts(
+                    (row.get(0)?, row.get::<_, Option<i64>>(1)?.map(|id| id.to_string())),
+                    row.get(2)?,
+                    (row.get::<_, i64>(3)? as u32, row.get::<_, i64>(4)? as u32),
+                    row.get(5)?,
+                    (row.get::<_, i64>(6)? as u64, row.get::<_, i64>(7)? as u64, row.get::<_, i64>(8)? as u64),
+                ))
+            })
+            .map_err(|err| format!("query failed: {}", err))?;
+        rows.collect::<rusqlite::Result<Vec<PlayerResults>>>().map_err(|err| format!("invalid row: {}", err))
+    }
+
+    fn history(&self, id: &str) -> Result<Option<HistoryRecord>, String> {
+        // not an id this storage could have given out
+        let id: i64 = match id.parse() {
+            Ok(id) => id,
+            Err(_) => return Ok(None),
+        };
+        self.connection.lock().unwrap()
+            .query_row(&format!("SELECT {} FROM games WHERE id = ?1", COLUMNS), params![id], to_record)
+            .optional()
+            .map_err(|err| format!("query failed: {}", err))
+    }
+
+    fn insert(&self, record: HistoryRecord) -> Result<String, String> {
+        let connection = self.connection.lock().unwrap();
+        connection
+            .execute(
+                "INSERT INTO games (game_type, player1, player2, winner, difficulty, player1_hints, player2_hints, game_date,
+                                    board_rows, board_columns, moves, first_player, duration_seconds, ai_seed,
+                                    player1_account, player2_account, ai_level)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17)",
+                params![
+                    record.game_type,
+                    record.player1,
+                    record.player2,
+                    record.winner,
+                    record.difficulty,
+                    record.player1_hints as i64,
+                    record.player2_hints as i64,
+                    record.game_date.unwrap_or_else(Utc::now),
+                    record.board_rows as i64,
+                    record.board_columns as i64,
+                    record.moves.join(" "),
+                    record.first_player,
+                    record.duration_seconds as i64,
+                    record.ai_seed.map(seed_to_i64),
+                    account_id(&record.player1_account),
+                    account_id(&record.player2_account),
+                    record.ai_level,
+                ],
+            )
+            .map_err(|err| format!("insert failed: {}", err))?;
+        Ok(connection.last_insert_rowid().to_string())
+    }
+
+    fn clear(&self) -> Result<(), String> {
+        self.connection.lock().unwrap()
+            .execute_batch("DELETE FROM games; DELETE FROM ratings; DELETE FROM rating_history;")
+            .map_err(|err| format!("delete failed: {}", err))
+    }
+
+    fn ratings(&self) -> Result<Vec<RatingRecord>, String> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection
+            .prepare(&format!("SELECT {} FROM ratings", RATING_COLUMNS))
+            .map_err(|err| format!("query failed: {}", err))?;
+        let rows = statement.query_map(NO_PARAMS, to_rating).map_err(|err| format!("query failed: {}", err))?;
+        rows.collect::<rusqlite::Result<Vec<RatingRecord>>>().map_err(|err| format!("invalid row: {}", err))
+    }
+
+    fn rating(&self, player: &PlayerId) -> Result<Option<RatingRecord>, String> {
+        self.connection.lock().unwrap()
+            .query_row(&format!("SELECT {} FROM ratings WHERE player_key = ?1", RATING_COLUMNS), params![player.key()], to_rating)
+            .optional()
+            .map_err(|err| format!("query failed: {}", err))
+    }
+
+    fn rating_history(&self, player: &PlayerId) -> Result<Vec<RatingRecord>, String> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection
+            .prepare(&format!("SELECT {} FROM rating_history WHERE player_key = ?1 ORDER BY id", RATING_COLUMNS))
+            .map_err(|err| format!("query failed: {}", err))?;
+        let rows = statement.query_map(params![player.key()], to_rating).map_err(|err| format!("query failed: {}", err))?;
+        rows.collect::<rusqlite::Result<Vec<RatingRecord>>>().map_err(|err| format!("invalid row: {}", err))
+    }
+
+    fn add_rating(&self, rating: RatingRecord) -> Result<(), String> {
+        let connection = self.connection.lock().unwrap();
+        let values = params![
+            rating.player_id().key(),
+            rating.player,
+            account_id(&rating.account),
+            rating.rating,
+            rating.deviation,
+            rating.volatility,
+            rating.games as i64,
+            rating.game_id,
+            rating.date,
+        ];
+        // a player has one current rating, replaced every game
+        connection
+            .execute(&format!("INSERT OR REPLACE INTO ratings (player_key, {}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)", RATING_COLUMNS), values)
+            .map_err(|err| format!("insert failed: {}", err))?;
+        connection
+            .execute(&format!("INSERT INTO rating_history (player_key, {}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)", RATING_COLUMNS), values)
+            .map(|_| ())
+            .map_err(|err| format!("insert failed: {}", err))
+    }
+
+    fn add_account(&self, account: AccountRecord) -> Result<String, String> {
+        let connection = self.connection.lock().unwrap();
+        connection
+            .execute(
+                "INSERT INTO accounts (name, name_key, password_hash, created) VALUES (?1, ?2, ?3, ?4)",
+                params![account.name, name_key(&account.name), account.password_hash, account.created],
+            )
+            .map_err(|err| format!("insert failed: {}", err))?;
+        Ok(connection.last_insert_rowid().to_string())
+    }
+
+    fn account(&self, id: &str) -> Result<Option<AccountRecord>, String> {
+        let id: i64 = match id.parse() {
+            Ok(id) => id,
+            Err(_) => return Ok(None),
+        };
+        self.connection.lock().unwrap()
+            .query_row(&format!("SELECT {} FROM accounts WHERE id = ?1", ACCOUNT_COLUMNS), params![id], to_account)
+            .optional()
+            .map_err(|err| format!("query failed: {}", err))
+    }
+
+    fn account_by_name(&self, name: &str) -> Result<Option<AccountRecord>, String> {
+        self.connection.lock().unwrap()
+            .query_row(&format!("SELECT {} FROM accounts WHERE name_key = ?1", ACCOUNT_COLUMNS), params![name_key(name)], to_account)
+            .optional()
+            .map_err(|err| format!("query failed: {}", err))
+    }
+
+    fn add_session(&self, session: SessionRecord) -> Result<(), String> {
+        let account_id: i64 = session.account_id.parse().map_err(|_| format!("invalid account id {}", session.account_id))?;
+        self.connection.lock().unwrap()
+            .execute(
+                "INSERT INTO sessions (token, account_id, expires) VALUES (?1, ?2, ?3)",
+                params![session.token, account_id, session.expires],
+            )
+            .map(|_| ())
+            .map_err(|err| format!("insert failed: {}", err))
+    }
+
+    fn session(&self, token: &str) -> Result<Option<SessionRecord>, String> {
+        self.connection.lock().unwrap()
+            .query_row("SELECT token, account_id, expires FROM sessions WHERE token = ?1", params![token], |row| {
+                Ok(SessionRecord {
+                    token: row.get(0)?,
+                    account_id: row.get::<_, i64>(1)?.to_string(),
+                    expires: row.get(2)?,
+                })
+            })
+            .optional()
+            .map_err(|err| format!("query failed: {}", err))
+    }
+
+    fn remove_session(&self, token: &str) -> Result<(), String> {
+        self.connection.lock().unwrap()
+            .execute("DELETE FROM sessions WHERE token = ?1", params![token])
+            .map(|_| ())
+            .map_err(|err| format!("delete failed: {}", err))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::tests::check_filtering_and_paging;
+
+    #[test]
+    fn filtering_and_paging() {
+        check_filtering_and_paging(&SqliteStorage::open(":memory:").unwrap());
+    }
+}
@@ -0,0 +1,137 @@
+// Player statistics and leaderboards, added up from the results the storage counted in the
+// database (see HistoryStorage::results())
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use serde::Serialize;
+use crate::storage::{PlayerId, PlayerResults};
+
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct Record {
+    pub games: u64,
+    pub wins: u64,
+    pub losses: u64,
+    pub draws: u64,
+    pub win_rate: f64, // wins out of the games, 0 to 1
+}
+
+impl Record {
+    fn add(&mut self, results: &PlayerResults) {
+        self.wins += results.wins;
+        self.losses += results.losses;
+        self.draws += results.draws;
+        self.games = self.wins + self.losses + self.draws;
+        self.win_rate = if self.games == 0 { 0.0 } else { self.wins as f64 / self.games as f64 };
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct DifficultyRecord {
+    pub difficulty: String, // of the computer, N/A for games between two players
+    #[serde(flatten)]
+    pub record: Record,
+}
+
+#[derive(Serialize, Debug)]
+pub struct GameRecord {
+    pub game_type: String,
+    pub board_rows: u32, // 0 for games recorded before the board size was kept
+    pub board_columns: u32,
+    #[serde(flatten)]
+    pub record: Record,
+}
+
+#[derive(Serialize, Debug)]
+pub struct PlayerStats {
+    pub player: String,
+    pub account: Option<String>, // None for guests and the computer
+    #[serde(flatten)]
+    pub record: Record,
+    pub by_difficulty: Vec<DifficultyRecord>,
+    pub by_game: Vec<GameRecord>,
+}
+
+// every player's statistics, accounts apart from guests under the same name
+pub fn player_stats(results: &[PlayerResults]) -> Vec<PlayerStats> {
+    let mut players: BTreeMap<PlayerId, (&str, Record, BTreeMap<String, Record>, BTreeMap<(&str, u32, u32), Record>)> = BTreeMap::new();
+    for player_results in results {
+        let (player, record, by_difficulty, by_game) = players.entry(player_results.player_id()).or_default();
+        *player = &player_results.player;
+        record.add(player_results);
+        by_difficulty.entry(player_results.difficulty.clone()).or_default().add(player_results);
+        by_game.entry((player_results.game_type.as_str(), player_results.board_rows, player_results.board_columns))
+            .or_default()
+            .add(player_results);
+    }
+    players.into_iter()
+        .map(|(id, (player, record, by_difficulty, by_game))| PlayerStats {
+            player: String::from(player),
+            account: id.into_account(),
+            record,
+            by_difficulty: by_difficulty.into_iter()
+                .map(|(difficulty, record)| DifficultyRecord { difficulty, record })
+                .collect(),
+            by_game: by_game.into_iter()
+                .map(|((game_type, board_rows, board_columns), record)| GameRecord {
+                    game_type: String::from(game_type),
+                    board_rows,
+                    board_columns,
+                    record,
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Ranking {
+    Wins,
+    WinRate,
+    Games,
+}
+
+impl FromStr for Ranking {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "wins" => Ok(Ranking::Wins),
+            "win_rate" => Ok(Ranking::WinRate),
+            "games" => Ok(Ranking::Games),
+            _ => Err(format!("can't rank by {}, only wins, win_rate or games", text)),
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct LeaderboardEntry {
+    pub rank: usize, // from 1
+    pub player: String,
+    pub account: Option<String>,
+    #[serde(flatten)]
+    pub record: Record,
+}
+
+// the best `limit` players with at least `min_games` games, ties go to more wins, then more
+// games, then the name
+pub fn leaderboard(results: &[PlayerResults], ranking: Ranking, limit: usize, min_games: u64) -> Vec<LeaderboardEntry> {
+    let mut players: Vec<PlayerStats> = player_stats(results).into_iter()
+        .filter(|stats| stats.record.games >= min_games)
+        .collect();
+    players.sort_by(|a, b| {
+        let (a_record, b_record) = (&a.record, &b.record);
+        let order = match ranking {
+            Ranking::Wins => b_record.wins.cmp(&a_record.wins),
+            Ranking::WinRate => b_record.win_rate.partial_cmp(&a_record.win_rate).unwrap(),
+            Ranking::Games => b_record.games.cmp(&a_record.games),
+        };
+        order
+            .then(b_record.wins.cmp(&a_record.wins))
+            .then(b_record.games.cmp(&a_record.games))
+            .then(a.player.cmp(&b.player))
+    });
+    players.into_iter()
+        .take(limit)
+        .enumerate()
+        .map(|(index, stats)| LeaderboardEntry { rank: index + 1, player: stats.player, account: stats.account, record: stats.record })
+        .collect()
+}
@@ -0,0 +1,714 @@
+// Where the game history is kept, chosen when the server starts (see from_env())
+//
+// HISTORY_STORAGE=mongodb  the Connect4DB database on MONGODB_URI, mongodb://localhost:27017/ by default
+// HISTORY_STORAGE=sqlite   a SQLite file at SQLITE_PATH, connect4.db by default, created if missing
+// HISTORY_STORAGE=memory   nothing is written anywhere, the history is lost when the server stops
+//
+// MongoDB is the default, so existing setups keep working without any configuration.
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Mutex;
+use chrono::{DateTime, NaiveDate, SecondsFormat, TimeZone, Utc};
+use crate::mongo_storage::MongoStorage;
+use crate::sqlite_storage::SqliteStorage;
+
+const DEFAULT_MONGODB_URI: &str = "mongodb://localhost:27017/";
+const DEFAULT_SQLITE_PATH: &str = "connect4.db";
+
+pub const DEFAULT_PAGE_SIZE: usize = 50;
+pub const MAX_PAGE_SIZE: usize = 500;
+
+// a finished game as it is stored
+#[derive(Clone, Debug)]
+pub struct HistoryRecord {
+    pub id: String, // given by the storage, ignored by insert()
+    pub game_type: String,
+    pub player1: String,
+    pub player2: String,
+    pub winner: String,
+    pub difficulty: String,
+    pub player1_hints: u32,
+    pub player2_hints: u32,
+    pub game_date: Option<DateTime<Utc>>, // None for old records without a date
+    // the rest is 0 or empty for games recorded before they were stored
+    pub board_rows: u32,
+    pub board_columns: u32,
+    pub moves: Vec<String>, // in order, "3" in Connect 4 and "3T" in TOOT-OTTO (see model::notation)
+    pub first_player: String, // name of the player who moved first
+    pub duration_seconds: u32,
+    pub ai_seed: Option<u64>, // None without a computer player
+    pub ai_level: Option<f64>, // where an Adaptive computer started, see model::adaptive
+    // ids of the accounts the players were logged in with, None for guests and the computer
+    pub player1_account: Option<String>,
+    pub player2_account: Option<String>,
+}
+
+impl HistoryRecord {
+    pub fn player1_id(&self) -> PlayerId {
+        PlayerId::new(&self.player1, &self.player1_account)
+    }
+
+    pub fn player2_id(&self) -> PlayerId {
+        PlayerId::new(&self.player2, &self.player2_account)
+    }
+
+    // the player who won, or the name "Draw"
+    pub fn winner_id(&self) -> PlayerId {
+        if self.winner == self.player1 {
+            self.player1_id()
+        } else if self.winner == self.player2 {
+            self.player2_id()
+        } else {
+            PlayerId::Name(self.winner.clone())
+        }
+    }
+}
+
+// who played a game: the account of a logged in player, otherwise the name it was recorded
+// under, for guests and the computer. Statistics, ratings and filters go by this, names are
+// only shown.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PlayerId {
+    Account(String),
+    Name(String),
+}
+
+impl PlayerId {
+    pub fn new(name: &str, account: &Option<String>) -> Self {
+        match account {
+            Some(account) => PlayerId::Account(account.clone()),
+            None => PlayerId::Name(String::from(name)),
+        }
+    }
+
+    // None for guests and the computer
+    pub fn into_account(self) -> Option<String> {
+        match self {
+            PlayerId::Account(account) => Some(account),
+            PlayerId::Name(_) => None,
+        }
+    }
+
+    // one text for both kinds, for the storages to keep ratings under
+    pub fn key(&self) -> String {
+        match self {
+            PlayerId::Account(account) => format!("account:{}", account),
+            PlayerId::Name(name) => format!("name:{}", name),
+        }
+    }
+}
+
+// MongoDB and SQLite have no unsigned 64 bit integers, seeds are stored with the same bits in an i64
+pub fn seed_to_i64(seed: u64) -> i64 {
+    seed as i64
+}
+
+pub fn seed_from_i64(value: i64) -> u64 {
+    value as u64
+}
+
+// which games to list, a None field doesn't filter
+#[derive(Clone, Debug, Default)]
+pub struct HistoryFilter {
+    pub game_type: Option<String>,
+    pub player: Option<PlayerId>, // either player
+    pub player1: Option<PlayerId>,
+    pub player2: Option<PlayerId>,
+    pub difficulty: Option<String>,
+    pub winner: Option<PlayerId>, // the name "Draw" for draws
+    pub board_rows: Option<u32>,
+    pub board_columns: Option<u32>,
+    pub from: Option<DateTime<Utc>>, // played at or after
+    pub to: Option<DateTime<Utc>>,   // played before
+}
+
+impl HistoryFilter {
+    pub fn matches(&self, record: &HistoryRecord) -> bool {
+        fn same(wanted: &Option<String>, value: &str) -> bool {
+            wanted.as_ref().map(|wanted| wanted == value).unwrap_or(true)
+        }
+        fn same_player(wanted: &Option<PlayerId>, player: PlayerId) -> bool {
+            wanted.as_ref().map(|wanted| *wanted == player).unwrap_or(true)
+        }
+        same(&self.game_type, &record.game_type)
+            && (same_player(&self.player, record.player1_id()) || same_player(&self.player, record.player2_id()))
+            && same_player(&self.player1, record.player1_id())
+            && same_player(&self.player2, record.player2_id())
+            && same(&self.difficulty, &record.difficulty)
+            && same_player(&self.winner, record.winner_id())
+            && self.board_rows.map(|rows| rows == record.board_rows).unwrap_or(true)
+            && self.board_columns.map(|columns| columns == record.board_columns).unwrap_or(true)
+            && self.from.map(|from| matches!(record.game_date, Some(date) if date >= from)).unwrap_or(true)
+            && self.to.map(|to| matches!(record.game_date, Some(date) if date < to)).unwrap_or(true)
+    }
+}
+
+// a date as 2020-11-30, the start of that day or the end of it for the end of a range, or
+// a full RFC 3339 time
+pub fn parse_date(text: &str, end: bool) -> Result<DateTime<Utc>, String> {
+    if let Ok(date) = NaiveDate::parse_from_str(text, "%Y-%m-%d") {
+        let start = Utc.from_utc_datetime(&date.and_hms_opt(0, 0, 0).unwrap());
+        return Ok(if end { start + chrono::Duration::days(1) } else { start });
+    }
+    DateTime::parse_from_rfc3339(text)
+        .map(|date| date.with_timezone(&Utc))
+        .map_err(|_| format!("{} is not a date like 2020-11-30 or 2020-11-30T12:00:00Z", text))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SortKey {
+    Date,
+    Duration,
+    GameType,
+    Player1,
+    Player2,
+    Winner,
+    Difficulty,
+}
+
+impl SortKey {
+    pub fn value(self, record: &HistoryRecord) -> SortValue {
+        match self {
+            // old records without a date sort as the oldest
+            SortKey::Date => SortValue::Date(record.game_date.unwrap_or_else(|| Utc.timestamp_opt(0, 0).unwrap())),
+            SortKey::Duration => SortValue::Number(record.duration_seconds as i64),
+            SortKey::GameType => SortValue::Text(record.game_type.clone()),
+            SortKey::Player1 => SortValue::Text(record.player1.clone()),
+            SortKey::Player2 => SortValue::Text(record.player2.clone()),
+            SortKey::Winner => SortValue::Text(record.winner.clone()),
+            SortKey::Difficulty => SortValue::Text(record.difficulty.clone()),
+        }
+    }
+}
+
+impl FromStr for SortKey {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "date" => Ok(SortKey::Date),
+            "duration" => Ok(SortKey::Duration),
+            "game_type" => Ok(SortKey::GameType),
+            "player1" => Ok(SortKey::Player1),
+            "player2" => Ok(SortKey::Player2),
+            "winner" => Ok(SortKey::Winner),
+            "difficulty" => Ok(SortKey::Difficulty),
+            _ => Err(format!("can't sort by {}, only date, duration, game_type, player1, player2, winner or difficulty", text)),
+        }
+    }
+}
+
+impl fmt::Display for SortKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            SortKey::Date => "date",
+            SortKey::Duration => "duration",
+            SortKey::GameType => "game_type",
+            SortKey::Player1 => "player1",
+            SortKey::Player2 => "player2",
+            SortKey::Winner => "winner",
+            SortKey::Difficulty => "difficulty",
+        })
+    }
+}
+
+// the value of a game's sort key, only values of the same key are compared
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SortValue {
+    Date(DateTime<Utc>),
+    Number(i64),
+    Text(String),
+}
+
+// where a page ended: the sort key and id of its last game, the next page starts after it.
+// Games are ordered by the sort key, then by id, so the position is exact even when games
+// are added in between.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cursor {
+    pub sort: SortKey,
+    pub value: SortValue,
+    pub id: String,
+}
+
+impl Cursor {
+    pub fn after(sort: SortKey, record: &HistoryRecord) -> Self {
+        Cursor { sort, value: sort.value(record), id: record.id.clone() }
+    }
+
+    // hex, so it can go in a URL as it is
+    pub fn encode(&self) -> String {
+        let value = match &self.value {
+            SortValue::Date(date) => date.to_rfc3339_opts(SecondsFormat::Nanos, true),
+            SortValue::Number(number) => number.to_string(),
+            SortValue::Text(text) => text.clone(),
+        };
+        format!("{}\n{}\n{}", self.sort, self.id, value)
+            .bytes()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+
+    pub fn decode(text: &str) -> Result<Self, String> {
+        let invalid = || format!("invalid cursor {}", text);
+        let bytes = (0..text.len()).step_by(2)
+            .map(|index| text.get(index..index + 2).and_then(|byte| u8::from_str_radix(byte, 16).ok()))
+            .collect::<Option<Vec<u8>>>()
+            .ok_or_else(invalid)?;
+        let decoded = String::from_utf8(bytes).map_err(|_| invalid())?;
+        let mut parts = decoded.splitn(3, '\n');
+        let (sort, id, value) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(sort), Some(id), Some(value)) => (sort.parse::<SortKey>().map_err(|_| invalid())?, id, value),
+            _ => return Err(invalid()),
+        };
+        let value = match sort {
+            SortKey::Date => SortValue::Date(DateTime::parse_from_rfc3339(value).map_err(|_| invalid())?.with_timezone(&Utc)),
+            SortKey::Duration => SortValue::Number(value.parse().map_err(|_| invalid())?),
+            _ => SortValue::Text(String::from(value)),
+        };
+        Ok(Cursor { sort, value, id: String::from(id) })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct HistoryQuery {
+    pub filter: HistoryFilter,
+    pub sort: SortKey,
+    pub descending: bool,
+    pub limit: usize,
+    pub offset: usize,          // games to skip, for page numbers
+    pub after: Option<Cursor>, // start after this game instead, offset is then ignored
+}
+
+impl Default for HistoryQuery {
+    // every game oldest first, as the history was always listed
+    fn default() -> Self {
+        HistoryQuery {
+            filter: HistoryFilter::default(),
+            sort: SortKey::Date,
+            descending: false,
+            limit: DEFAULT_PAGE_SIZE,
+            offset: 0,
+            after: None,
+        }
+    }
+}
+
+impl HistoryQuery {
+    // the page from up to limit + 1 games fetched in order, the one past the limit only tells
+    // there is a next page
+    pub fn page(&self, mut records: Vec<HistoryRecord>, total: u64) -> HistoryPage {
+        let mut next = None;
+        if records.len() > self.limit {
+            records.truncate(self.limit);
+            next = records.last().map(|record| Cursor::after(self.sort, record));
+        }
+        HistoryPage { records, total, next }
+    }
+}
+
+pub struct HistoryPage {
+    pub records: Vec<HistoryRecord>,
+    pub total: u64, // games matching the filter, on every page
+    pub next: Option<Cursor>,
+}
+
+// one player's results in the games of one game type, board size and difficulty, what the
+// statistics are added up from (see stats.rs)
+#[derive(Clone, Debug, PartialEq)]
+pub struct PlayerResults {
+    pub player: String,
+    pub account: Option<String>, // None for guests and the computer
+    pub game_type: String,
+    pub board_rows: u32,
+    pub board_columns: u32,
+    pub difficulty: String,
+    pub wins: u64,
+    pub losses: u64,
+    pub draws: u64,
+}
+
+impl PlayerResults {
+    // from the number of games and how many were won and drawn, as databases count them
+    pub fn from_counts(
+        (player, account): (String, Option<String>),
+        game_type: String,
+        (board_rows, board_columns): (u32, u32),
+        difficulty: String,
+        (games, wins, draws): (u64, u64, u64),
+    ) -> Self {
+        PlayerResults { player, account, game_type, board_rows, board_columns, difficulty, wins, losses: games - wins - draws, draws }
+    }
+
+    pub fn player_id(&self) -> PlayerId {
+        PlayerId::new(&self.player, &self.account)
+    }
+}
+
+// a player's rating after a game, the latest one is their current rating (see ratings.rs)
+#[derive(Clone, Debug, PartialEq)]
+pub struct RatingRecord {
+    pub player: String, // the name of the latest game
+    pub account: Option<String>, // None for guests and the computer
+    pub rating: f64,
+    pub deviation: f64,
+    pub volatility: f64,
+    pub games: u32, // rated games so far
+    pub game_id: String, // the game it was rated after
+    pub date: Option<DateTime<Utc>>,
+}
+
+impl RatingRecord {
+    pub fn player_id(&self) -> PlayerId {
+        PlayerId::new(&self.player, &self.account)
+    }
+}
+
+// a registered player, the password is only kept hashed (see accounts.rs)
+#[derive(Clone, Debug, PartialEq)]
+pub struct AccountRecord {
+    pub id: String, // given by the storage, ignored by add_account()
+    pub name: String, // unique by name_key(), the account's games are recorded under it
+    pub password_hash: String,
+    pub created: DateTime<Utc>,
+}
+
+// what account names are compared by, so "alice" can't register next to "Alice"
+pub fn name_key(name: &str) -> String {
+    name.to_lowercase()
+}
+
+// a login, for as long as the token is kept and hasn't expired
+#[derive(Clone, Debug, PartialEq)]
+pub struct SessionRecord {
+    pub token: String,
+    pub account_id: String,
+    pub expires: DateTime<Utc>,
+}
+
+pub trait HistoryStorage: Send + Sync {
+    // a page of the games matching the query's filter
+    fn histories(&self, query: &HistoryQuery) -> Result<HistoryPage, String>;
+    // the results of both players of every game matching the filter, added up in the storage
+    fn results(&self, filter: &HistoryFilter) -> Result<Vec<PlayerResults>, String>;
+    // None if there is no game with this id
+    fn history(&self, id: &str) -> Result<Option<HistoryRecord>, String>;
+    // returns the id of the new record
+    fn insert(&self, record: HistoryRecord) -> Result<String, String>;
+    // the ratings too, the accounts are kept
+    fn clear(&self) -> Result<(), String>;
+
+    // every rated player's current rating
+    fn ratings(&self) -> Result<Vec<RatingRecord>, String>;
+    // None for a player without rated games
+    fn rating(&self, player: &PlayerId) -> Result<Option<RatingRecord>, String>;
+    // the player's rating after each of their games, oldest first
+    fn rating_history(&self, player: &PlayerId) -> Result<Vec<RatingRecord>, String>;
+    // becomes the player's current rating and the last of their history
+    fn add_rating(&self, rating: RatingRecord) -> Result<(), String>;
+
+    // returns the id of the new account, the name is checked to be free beforehand
+    fn add_account(&self, account: AccountRecord) -> Result<String, String>;
+    // None if there is no account with this id
+    fn account(&self, id: &str) -> Result<Option<AccountRecord>, String>;
+    // names are compared by name_key()
+    fn account_by_name(&self, name: &str) -> Result<Option<AccountRecord>, String>;
+    fn add_session(&self, session: SessionRecord) -> Result<(), String>;
+    // None for a token that was never given out or was removed, expired ones are returned
+    fn session(&self, token: &str) -> Result<Option<SessionRecord>, String>;
+    fn remove_session(&self, token: &str) -> Result<(), String>;
+}
+
+pub type Storage = Box<dyn HistoryStorage>;
+
+pub fn from_env() -> Result<Storage, String> {
+    let kind = env::var("HISTORY_STORAGE").unwrap_or_else(|_| String::from("mongodb"));
+    match kind.as_str() {
+        "mongodb" => {
+            let uri = env::var("MONGODB_URI").unwrap_or_else(|_| String::from(DEFAULT_MONGODB_URI));
+            Ok(Box::new(MongoStorage::connect(&uri)?))
+        },
+        "sqlite" => {
+            let path = env::var("SQLITE_PATH").unwrap_or_else(|_| String::from(DEFAULT_SQLITE_PATH));
+            Ok(Box::new(SqliteStorage::open(&path)?))
+        },
+        "memory" => Ok(Box::new(MemoryStorage::default())),
+        _ => Err(format!("HISTORY_STORAGE is mongodb, sqlite or memory, not {}", kind)),
+    }
+}
+
+// for development, demos and tests
+#[derive(Default)]
+pub struct MemoryStorage {
+    records: Mutex<Vec<HistoryRecord>>,
+    last_id: Mutex<u64>,
+    ratings: Mutex<Vec<RatingRecord>>, // every player's history, oldest first
+    accounts: Mutex<Vec<AccountRecord>>,
+    sessions: Mutex<Vec<SessionRecord>>,
+}
+
+// ids are numbers here, compared as such
+fn memory_id(id: &str) -> u64 {
+    id.parse().unwrap_or(0)
+}
+
+impl HistoryStorage for MemoryStorage {
+    fn histories(&self, query: &HistoryQuery) -> Result<HistoryPage, String> {
+        let mut records: Vec<HistoryRecord> = self.records.lock().unwrap().iter()
+            .filter(|record| query.filter.matches(record))
+            .cloned()
+            .collect();
+        let total = records.len() as u64;
+        let order = |a: &SortValue, a_id: &str, b: &SortValue, b_id: &str| {
+            let order = a.cmp(b).then(memory_id(a_id).cmp(&memory_id(b_id)));
+            if query.descending { order.reverse() } else { order }
+        };
+        records.sort_by(|a, b| order(&query.sort.value(a), &a.id, &query.sort.value(b), &b.id));
+        let start = match &query.after {
+            Some(cursor) => records.iter()
+                .position(|record| order(&query.sort.value(record), &record.id, &cursor.value, &cursor.id) == Ordering::Greater)
+                .unwrap_or_else(|| records.len()),
+            None => query.offset.min(records.len()),
+        };
+        let page = records.drain(start..).take(query.limit + 1).collect();
+        Ok(query.page(page, total))
+    }
+
+    fn results(&self, filter: &HistoryFilter) -> Result<Vec<PlayerResults>, String> {
+        // (games, wins, draws) by player, game type, board size and difficulty
+        let mut counts: BTreeMap<((String, Option<String>), String, (u32, u32), String), (u64, u64, u64)> = BTreeMap::new();
+        for record in self.records.lock().unwrap().iter().filter(|record| filter.matches(record)) {
+            for (player, account) in [(&record.player1, &record.player1_account), (&record.player2, &record.player2_account)].iter() {
+                let key = (
+                    ((*player).clone(), (*account).clone()),
+                    record.game_type.clone(),
+                    (record.board_rows, record.board_columns),
+                    record.difficulty.clone(),
+                );
+                let count = counts.entry(key).or_insert((0, 0, 0));
+                count.0 += 1;
+                if &record.winner == *player {
+                    count.1 += 1;
+                } else if record.winner == "Draw" {
+                    count.2 += 1;
+                }
+            }
+        }
+        Ok(counts.into_iter()
+            .map(|((player, game_type, size, difficulty), counts)| PlayerResults::from_counts(player, game_type, size, difficulty, counts))
+            .collect())
+    }
+
+    fn history(&self, id: &str) -> Result<Option<HistoryRecord>, String> {
+        Ok(self.records.lock().unwrap().iter().find(|record| record.id == id).cloned())
+    }
+
+    // ids count up from 1 and aren't reused after clear(), like SQLite's
+    fn insert(&self, mut record: HistoryRecord) -> Result<String, String> {
+        let mut last_id = self.last_id.lock().unwrap();
+        *last_id += 1;
+        record.id = last_id.to_string();
+        self.records.lock().unwrap().push(record);
+        Ok(last_id.to_string())
+    }
+
+    fn clear(&self) -> Result<(), String> {
+        self.records.lock().unwrap().clear();
+        self.ratings.lock().unwrap().clear();
+        Ok(())
+    }
+
+    fn ratings(&self) -> Result<Vec<RatingRecord>, String> {
+        let mut latest: BTreeMap<PlayerId, &RatingRecord> = BTreeMap::new();
+        let ratings = self.ratings.lock().unwrap();
+        for rating in ratings.iter() {
+            latest.insert(rating.player_id(), rating);
+        }
+        Ok(latest.values().map(|rating| (*rating).clone()).collect())
+    }
+
+    fn rating(&self, player: &PlayerId) -> Result<Option<RatingRecord>, String> {
+        Ok(self.ratings.lock().unwrap().iter().rev().find(|rating| rating.player_id() == *player).cloned())
+    }
+
+    fn rating_history(&self, player: &PlayerId) -> Result<Vec<RatingRecord>, String> {
+        Ok(self.ratings.lock().unwrap().iter().filter(|rating| rating.player_id() == *player).cloned().collect())
+    }
+
+    fn add_rating(&self, rating: RatingRecord) -> Result<(), String> {
+        self.ratings.lock().unwrap().push(rating);
+        Ok(())
+    }
+
+    // ids count up from 1 like the games'
+    fn add_account(&self, mut account: AccountRecord) -> Result<String, String> {
+        let mut accounts = self.accounts.lock().unwrap();
+        account.id = (accounts.len() + 1).to_string();
+        accounts.push(account);
+        Ok(accounts.len().to_string())
+    }
+
+    fn account(&self, id: &str) -> Result<Option<AccountRecord>, String> {
+        Ok(self.accounts.lock().unwrap().iter().find(|account| account.id == id).cloned())
+    }
+
+    fn account_by_name(&self, name: &str) -> Result<Option<AccountRecord>, String> {
+        let key = name_key(name);
+        Ok(self.accounts.lock().unwrap().iter().find(|account| name_key(&account.name) == key).cloned())
+    }
+
+    fn add_session(&self, session: SessionRecord) -> Result<(), String> {
+        self.sessions.lock().unwrap().push(session);
+        Ok(())
+    }
+
+    fn session(&self, token: &str) -> Result<Option<SessionRecord>, String> {
+        Ok(self.sessions.lock().unwrap().iter().find(|session| session.token == token).cloned())
+    }
+
+    fn remove_session(&self, token: &str) -> Result<(), String> {
+        self.sessions.lock().unwrap().retain(|session| session.token != token);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use chrono::Datelike;
+    use super::*;
+
+    // games on the days 2020-11-01 to 2020-11-05, alice and bob taking turns to go first. Bob
+    // plays the game on the 4th logged in to account 1.
+    fn games() -> Vec<HistoryRecord> {
+        let durations = [30, 10, 30, 20, 10];
+        (1..=5u32).map(|day| {
+            let (player1, player2) = if day % 2 == 1 { ("alice", "bob") } else { ("bob", "carol") };
+            HistoryRecord {
+                id: String::new(),
+                game_type: String::from(if day % 2 == 1 { "Connect-4" } else { "TOOT-OTTO" }),
+                player1: String::from(player1),
+                player2: String::from(player2),
+                winner: String::from(if day == 5 { "Draw" } else { player1 }),
+                difficulty: String::from("Hard"),
+                player1_hints: 0,
+                player2_hints: 0,
+                game_date: Some(Utc.with_ymd_and_hms(2020, 11, day, 12, 0, 0).unwrap()),
+                board_rows: 6,
+                board_columns: 7,
+                moves: vec![String::from("3")],
+                first_player: String::from(player1),
+                duration_seconds: durations[day as usize - 1],
+                ai_seed: None,
+                ai_level: None,
+                player1_account: if day == 4 { Some(String::from("1")) } else { None },
+                player2_account: None,
+            }
+        }).collect()
+    }
+
+    fn days(page: &HistoryPage) -> Vec<u32> {
+        page.records.iter().map(|record| record.game_date.unwrap().day()).collect()
+    }
+
+    // every page of the query, following the cursors through their text form as a client would
+    fn all_pages(storage: &dyn HistoryStorage, mut query: HistoryQuery) -> Vec<u32> {
+        let mut all = Vec::new();
+        loop {
+            let page = storage.histories(&query).unwrap();
+            assert_eq!(page.total, 5);
+            assert!(page.records.len() <= query.limit);
+            all.extend(days(&page));
+            match page.next {
+                Some(next) => query.after = Some(Cursor::decode(&next.encode()).unwrap()),
+                None => return all,
+            }
+        }
+    }
+
+    // the same checks for every storage, the SQLite one runs them too
+    pub(crate) fn check_filtering_and_paging(storage: &dyn HistoryStorage) {
+        for game in games() {
+            storage.insert(game).unwrap();
+        }
+        let filtered = |filter: HistoryFilter| days(&storage.histories(&HistoryQuery { filter, ..HistoryQuery::default() }).unwrap());
+
+        assert_eq!(filtered(HistoryFilter::default()), vec![1, 2, 3, 4, 5]);
+        let name = |name: &str| Some(PlayerId::Name(String::from(name)));
+        let account = Some(PlayerId::Account(String::from("1")));
+        assert_eq!(filtered(HistoryFilter { player: name("carol"), ..HistoryFilter::default() }), vec![2, 4]);
+        assert_eq!(filtered(HistoryFilter { player: name("bob"), ..HistoryFilter::default() }), vec![1, 2, 3, 5]);
+        assert_eq!(filtered(HistoryFilter { player: account.clone(), ..HistoryFilter::default() }), vec![4]);
+        assert_eq!(filtered(HistoryFilter { player1: name("bob"), ..HistoryFilter::default() }), vec![2]);
+        assert_eq!(filtered(HistoryFilter { player1: account.clone(), ..HistoryFilter::default() }), vec![4]);
+        assert_eq!(filtered(HistoryFilter { player2: account.clone(), ..HistoryFilter::default() }), Vec::<u32>::new());
+        assert_eq!(filtered(HistoryFilter { game_type: Some(String::from("Connect-4")), ..HistoryFilter::default() }), vec![1, 3, 5]);
+        assert_eq!(filtered(HistoryFilter { winner: name("bob"), ..HistoryFilter::default() }), vec![2]);
+        assert_eq!(filtered(HistoryFilter { winner: account, ..HistoryFilter::default() }), vec![4]);
+        assert_eq!(filtered(HistoryFilter { winner: name("Draw"), ..HistoryFilter::default() }), vec![5]);
+        assert_eq!(filtered(HistoryFilter { board_rows: Some(4), ..HistoryFilter::default() }), Vec::<u32>::new());
+        let dates = HistoryFilter {
+            from: Some(parse_date("2020-11-02", false).unwrap()),
+            to: Some(parse_date("2020-11-03", true).unwrap()),
+            ..HistoryFilter::default()
+        };
+        assert_eq!(filtered(dates), vec![2, 3]);
+
+        let page = storage.histories(&HistoryQuery { limit: 2, offset: 3, ..HistoryQuery::default() }).unwrap();
+        assert_eq!((days(&page), page.total), (vec![4, 5], 5));
+        assert!(page.next.is_none());
+
+        assert_eq!(all_pages(storage, HistoryQuery { limit: 2, ..HistoryQuery::default() }), vec![1, 2, 3, 4, 5]);
+        assert_eq!(all_pages(storage, HistoryQuery { limit: 2, descending: true, ..HistoryQuery::default() }), vec![5, 4, 3, 2, 1]);
+        // equal durations go by id, so a page can end between them
+        let by_duration = HistoryQuery { sort: SortKey::Duration, limit: 1, ..HistoryQuery::default() };
+        assert_eq!(all_pages(storage, by_duration), vec![2, 5, 4, 1, 3]);
+        let by_player = HistoryQuery { sort: SortKey::Player1, descending: true, limit: 3, ..HistoryQuery::default() };
+        assert_eq!(all_pages(storage, by_player), vec![4, 2, 5, 3, 1]);
+
+        // the account's games are counted apart from the guest's under the same name
+        let results = storage.results(&HistoryFilter { player: name("bob"), ..HistoryFilter::default() }).unwrap();
+        let mut bob: Vec<(Option<String>, &str, u64, u64, u64)> = results.iter()
+            .filter(|results| results.player == "bob")
+            .map(|results| (results.account.clone(), results.game_type.as_str(), results.wins, results.losses, results.draws))
+            .collect();
+        bob.sort();
+        assert_eq!(bob, vec![(None, "Connect-4", 0, 2, 1), (None, "TOOT-OTTO", 1, 0, 0)]);
+        let results = storage.results(&HistoryFilter { player1: Some(PlayerId::Account(String::from("1"))), ..HistoryFilter::default() }).unwrap();
+        let mut players: Vec<PlayerId> = results.iter().map(PlayerResults::player_id).collect();
+        players.sort();
+        assert_eq!(players, vec![PlayerId::Account(String::from("1")), PlayerId::Name(String::from("carol"))]);
+    }
+
+    #[test]
+    fn memory_filtering_and_paging() {
+        check_filtering_and_paging(&MemoryStorage::default());
+    }
+
+    #[test]
+    fn cursor_round_trip() {
+        let cursors = [
+            Cursor { sort: SortKey::Date, value: SortValue::Date(Utc.with_ymd_and_hms(2020, 11, 30, 12, 0, 0).unwrap()), id: String::from("12") },
+            Cursor { sort: SortKey::Duration, value: SortValue::Number(95), id: String::from("5fc4e6a1b2") },
+            // the value is last, so it may hold anything
+            Cursor { sort: SortKey::Player1, value: SortValue::Text(String::from("Ann\nLee ü")), id: String::from("3") },
+        ];
+        for cursor in cursors.iter() {
+            let text = cursor.encode();
+            assert!(text.chars().all(|c| c.is_ascii_hexdigit()));
+            assert_eq!(&Cursor::decode(&text).unwrap(), cursor);
+        }
+    }
+
+    #[test]
+    fn invalid_cursors() {
+        let hex = |text: &str| text.bytes().map(|byte| format!("{:02x}", byte)).collect::<String>();
+        assert!(Cursor::decode("zz").is_err());
+        assert!(Cursor::decode("abc").is_err());
+        assert!(Cursor::decode(&hex("date\n1")).is_err());
+        assert!(Cursor::decode(&hex("moves\n1\n3")).is_err());
+        assert!(Cursor::decode(&hex("duration\n1\nlong")).is_err());
+        assert!(Cursor::decode(&hex("date\n1\nyesterday")).is_err());
+    }
+}
@@ -0,0 +1,232 @@
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use yew::agent::{Agent, AgentLink, HandlerId, Public};
+
+use model::adaptive::AdaptiveAI;
+use model::ai::{Difficulty, Connect4AI, SearchAI, TootOttoAI};
+use model::game::*;
+use model::solution_table::has_table;
+use model::solver::{Solution, Solver, PLAYING_TABLE_SIZE};
+
+// Messages are serialized between the page and the worker, so positions and moves are
+// sent as plain text: game type and difficulty by name, discs by their letter.
+
+#[derive(Serialize, Deserialize, Debug)]
+pub enum AiRequest {
+    // (re)create the computer player, it keeps its random generator for the whole game
+    NewGame {
+        game_type: String,
+        board_rows: usize,
+        board_columns: usize,
+        difficulty: String,
+        level: f64, // the starting level when difficulty is Adaptive, see model::adaptive
+        seed: u64,
+    },
+    // ask the computer player for its move
+    FindMove {
+        search_id: u32,
+        board: Vec<String>,
+        current_player: usize,
+    },
+    // best move for the player to move, from a fresh Insane AI so the computer player's
+    // random numbers are not used up
+    Hint {
+        search_id: u32,
+        board: Vec<String>,
+        current_player: usize,
+    },
+    // exact result of the position, only for board sizes with a solution table
+    Solve {
+        solution_id: u32,
+        board: Vec<String>,
+        current_player: usize,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub enum AiResponse {
+    Move {
+        search_id: u32,
+        column: usize,
+        disc_type: String,
+    },
+    Hint {
+        search_id: u32,
+        column: usize,
+        disc_type: String,
+    },
+    // winner is None for a draw, moves is how many more moves the winner needs
+    Solution {
+        solution_id: u32,
+        winner: Option<usize>,
+        moves: usize,
+    },
+    // no move could be found, e.g. the request came before NewGame
+    Failed {
+        search_id: u32,
+        reason: String,
+    },
+}
+
+// one row per string from top to bottom, one disc letter per cell
+pub fn encode_board(game: &BoardGame) -> Vec<String> {
+    game.game_board.board.iter()
+        .map(|row| row.iter().map(|disc| disc.to_string()).collect())
+        .collect()
+}
+
+fn decode_board(game: &mut BoardGame, board: &[String]) -> Result<(), String> {
+    if board.len() != game.game_board.board_rows {
+        return Err(format!("expected {} rows, got {}", game.game_board.board_rows, board.len()));
+    }
+    for (row, line) in board.iter().enumerate() {
+        let discs = line.chars()
+            .map(|letter| letter.to_string().parse::<DiscType>())
+            .collect::<Result<Vec<DiscType>, String>>()?;
+        if discs.len() != game.game_board.board_columns {
+            return Err(format!("expected {} columns, got {}", game.game_board.board_columns, discs.len()));
+        }
+        game.game_board.board[row] = discs;
+    }
+    Ok(())
+}
+
+enum ComputerPlayer {
+    Connect4(Connect4AI),
+    TootOtto(TootOttoAI),
+    Adaptive(AdaptiveAI),
+}
+
+struct WorkerGame {
+    game_type: GameType,
+    board_rows: usize,
+    board_columns: usize,
+    seed: u64,
+    computer: ComputerPlayer,
+    solver: Option<Solver>, // made by the first solve or hint on a board size with a table
+}
+
+impl WorkerGame {
+    fn position(&self, board: &[String], current_player: usize) -> Result<BoardGame, String> {
+        let mut game = BoardGame::new_game(&self.game_type, self.board_rows, self.board_columns, true);
+        decode_board(&mut game, board)?;
+        game.current_player = current_player;
+        Ok(game)
+    }
+
+    fn find_move(&mut self, game: BoardGame) -> (usize, DiscType) {
+        match &mut self.computer {
+            ComputerPlayer::Connect4(ai) => {
+                let disc_type = game.get_current_disc_type();
+                (ai.find_best_move(game), disc_type)
+            },
+            ComputerPlayer::TootOtto(ai) => ai.find_best_move(game),
+            ComputerPlayer::Adaptive(ai) => ai.find_best_move(game),
+        }
+    }
+
+    fn solver(&mut self) -> Option<&mut Solver> {
+        if !has_table(&self.game_type, self.board_rows, self.board_columns) {
+            return None;
+        }
+        if self.solver.is_none() {
+            // the small table, an Insane computer player has a solver of its own
+            self.solver = Solver::with_table(&self.game_type, self.board_rows, self.board_columns, PLAYING_TABLE_SIZE);
+        }
+        self.solver.as_mut()
+    }
+
+    // the solver's best move where there is a solution table, otherwise Insane's
+    fn hint(&mut self, game: &BoardGame) -> Option<(usize, DiscType)> {
+        if let Some(solver) = self.solver() {
+            return solver.best_moves(game).first().copied();
+        }
+        let evaluations = match self.game_type {
+            GameType::Connect4 => Connect4AI::new(self.board_rows, self.board_columns, Difficulty::Insane, self.seed).analyze(game),
+            GameType::TOOTandOTTO => TootOttoAI::new(self.board_rows, self.board_columns, Difficulty::Insane, self.seed).analyze(game),
+        };
+        evaluations.first().map(|best| (best.column, best.disc_type))
+    }
+}
+
+// Runs the AI off the UI thread. A search can't be interrupted once it started, the page
+// cancels a search by moving on to a new worker (see CanvasModel::create_ai()) and ignores
+// any response whose search_id is out of date.
+pub struct AiWorker {
+    link: AgentLink<AiWorker>,
+    games: HashMap<HandlerId, WorkerGame>, // one game per connected page
+}
+
+impl AiWorker {
+    fn handle_request(&mut self, msg: AiRequest, who: HandlerId) -> Result<Option<AiResponse>, (u32, String)> {
+        match msg {
+            AiRequest::NewGame { game_type, board_rows, board_columns, difficulty, level, seed } => {
+                let game_type: GameType = game_type.parse().map_err(|err| (0, err))?;
+                let difficulty: Difficulty = difficulty.parse().map_err(|err| (0, err))?;
+                let computer = match (difficulty, &game_type) {
+                    (Difficulty::Adaptive, _) => ComputerPlayer::Adaptive(AdaptiveAI::new(&game_type, board_rows, board_columns, level, seed)),
+                    (_, GameType::Connect4) => ComputerPlayer::Connect4(Connect4AI::new(board_rows, board_columns, difficulty, seed)),
+                    (_, GameType::TOOTandOTTO) => ComputerPlayer::TootOtto(TootOttoAI::new(board_rows, board_columns, difficulty, seed)),
+                };
+                self.games.insert(who, WorkerGame { game_type, board_rows, board_columns, seed, computer, solver: None });
+                Ok(None)
+            },
+            AiRequest::FindMove { search_id, board, current_player } => {
+                let game = self.games.get_mut(&who).ok_or((search_id, String::from("no game started")))?;
+                let position = game.position(&board, current_player).map_err(|err| (search_id, err))?;
+                let (column, disc_type) = game.find_move(position);
+                Ok(Some(AiResponse::Move { search_id, column, disc_type: disc_type.to_string() }))
+            },
+            AiRequest::Hint { search_id, board, current_player } => {
+                let game = self.games.get_mut(&who).ok_or((search_id, String::from("no game started")))?;
+                let position = game.position(&board, current_player).map_err(|err| (search_id, err))?;
+                let (column, disc_type) = game.hint(&position).ok_or((search_id, String::from("no legal moves")))?;
+                Ok(Some(AiResponse::Hint { search_id, column, disc_type: disc_type.to_string() }))
+            },
+            AiRequest::Solve { solution_id, board, current_player } => {
+                // failures come back with search_id 0, which the page ignores
+                let game = self.games.get_mut(&who).ok_or((0, String::from("no game started")))?;
+                let position = game.position(&board, current_player).map_err(|err| (0, err))?;
+                let solver = game.solver().ok_or((0, String::from("no solution table for this board size")))?;
+                let (winner, moves) = match solver.solve(&position) {
+                    Solution::Win { player, moves } => (Some(player), moves),
+                    Solution::Draw => (None, 0),
+                };
+                Ok(Some(AiResponse::Solution { solution_id, winner, moves }))
+            },
+        }
+    }
+}
+
+impl Agent for AiWorker {
+    type Reach = Public<Self>;
+    type Message = ();
+    type Input = AiRequest;
+    type Output = AiResponse;
+
+    fn create(link: AgentLink<Self>) -> Self {
+        Self {
+            link,
+            games: HashMap::new(),
+        }
+    }
+
+    fn update(&mut self, _msg: Self::Message) {}
+
+    fn handle_input(&mut self, msg: Self::Input, who: HandlerId) {
+        match self.handle_request(msg, who) {
+            Ok(Some(response)) => self.link.respond(who, response),
+            Ok(None) => (),
+            Err((search_id, reason)) => self.link.respond(who, AiResponse::Failed { search_id, reason }),
+        }
+    }
+
+    fn disconnected(&mut self, id: HandlerId) {
+        self.games.remove(&id);
+    }
+
+    // built from src/bin/ai_worker.rs, see index.html
+    fn name_of_resource() -> &'static str {
+        "ai_worker.js"
+    }
+}
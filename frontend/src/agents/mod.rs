@@ -0,0 +1 @@
+pub mod ai_worker;
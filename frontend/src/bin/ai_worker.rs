@@ -0,0 +1,15 @@
+#![allow(dead_code)] // encode_board is only needed by the pages
+// entry point of the web worker that runs the AI, loaded by the game pages
+// through the AiWorker agent
+use yew::agent::Threaded;
+
+// shared with the main app, which only needs it to send requests
+#[path = "../agents/ai_worker.rs"]
+mod ai_worker;
+
+fn main() {
+    wasm_logger::init(wasm_logger::Config::default());
+    yew::initialize();
+    ai_worker::AiWorker::register();
+    yew::run_loop();
+}
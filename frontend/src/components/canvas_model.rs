@@ -1,6 +1,7 @@
 use anyhow::Error;
 use stdweb::{
     web::{
+        Date,
         FillRule, 
         document, 
         window, 
@@ -13,7 +14,7 @@ use stdweb::{
 };
 use yew::{
     prelude::*,
-    format::Json,
+    format::{Json, Nothing},
     services::fetch::{
         FetchService, 
         FetchTask, 
@@ -22,10 +23,15 @@ use yew::{
     },
 };
 
-use model::ai::{Difficulty, Connect4AI, TootOttoAI};
+use model::adaptive::{self, DEFAULT_LEVEL};
+use model::ai::{Difficulty, MoveOutcome, random_seed};
 use model::game::*;
+use model::notation::format_move;
+use model::solution_table::has_table;
 
-use crate::types::HistoryInfo;
+use crate::agents::ai_worker::{AiWorker, AiRequest, AiResponse, encode_board};
+use crate::session;
+use crate::types::{history_url, HistoryInfo, HistoryPage};
 
 pub struct CanvasModel {
     props: Props,
@@ -40,10 +46,23 @@ pub struct CanvasModel {
     cbk: Callback<ClickEvent>,
     animate_cbk: Callback<(usize, usize, usize, bool)>,
     game: BoardGame,
+    ai_seed: u64,
+    adaptive_level: f64, // where the next Adaptive game starts, from the player's game history
+    game_level: f64, // where the AI of this game started, sent with the result
+    ai_worker: Option<Box<dyn Bridge<AiWorker>>>, // None only while it is replaced, see create_ai()
+    searches: u32, // FindMove and Hint requests the worker hasn't answered yet
+    search_id: u32, // responses for any other search are out of date
+    thinking: bool,
+    solution_id: u32, // like search_id, for the solution shown under the buttons
+    solution: Option<String>,
     won: bool,
     paused: bool,
     reject_click: bool,
+    hints_used: [u32; 2], // for player 1 and player 2
+    moves: Vec<(usize, DiscType)>, // of the game so far, sent with the result
+    started: f64, // when the first move was made, in milliseconds
     fetch_task: Option<FetchTask>,
+    history_task: Option<FetchTask>,
     link: ComponentLink<CanvasModel>
 }
 
@@ -62,6 +81,11 @@ pub struct Props {
 pub enum Message {
     Click(ClickEvent),
     AnimateCallback((usize, usize, usize, bool)),
+    Hint,
+    Reset,
+    AiResponse(AiResponse),
+    PlayerHistory(Vec<HistoryInfo>),
+    HistoryFailed,
     PostSuccess(String),
     PostFailedWithErr(Error),
     PostFailed
@@ -79,10 +103,145 @@ impl CanvasModel {
         self.paused = false;
         self.won = false;
         self.reject_click = false;
+        self.hints_used = [0, 0];
+        self.moves.clear();
+        self.ai_seed = random_seed();
+        self.create_ai();
+        self.solve();
         self.clear();
         self.draw_mask();
     }
 
+    // asks the worker for the exact result of the position, board sizes with a solution
+    // table only. The worker answers requests in order, so this is sent after any move search.
+    fn solve(&mut self) {
+        self.solution_id += 1;
+        self.solution = None;
+        if self.won || !has_table(&self.game_type, self.board_rows, self.board_columns) {
+            return;
+        }
+        self.send_to_worker(AiRequest::Solve {
+            solution_id: self.solution_id,
+            board: encode_board(&self.game),
+            current_player: self.game.current_player,
+        });
+    }
+
+    fn player_name(&self, player: usize) -> String {
+        let name = if player == 1 { &self.props.player1 } else { &self.props.player2 };
+        match name {
+            Some(name) if !name.is_empty() => name.clone(),
+            _ => format!("Player {}", player),
+        }
+    }
+
+    pub fn show_solution(&mut self, winner: Option<usize>, moves: usize) {
+        self.solution = Some(match winner {
+            Some(player) if moves > 0 => {
+                let plural = if moves == 1 { "" } else { "s" };
+                format!("{} can force a win in {} move{}", self.player_name(player), moves, plural)
+            },
+            Some(player) => format!("{} has won", self.player_name(player)),
+            None => String::from("This position is a draw with perfect play"),
+        });
+    }
+
+    // the AI keeps its random generator for the whole game, so the seed replays every move
+    // also cancels the search in progress: a search can't be interrupted inside the worker, so
+    // the page moves on to a new worker and the old one closes as soon as its search returns
+    fn create_ai(&mut self) {
+        self.search_id += 1;
+        self.thinking = false;
+        if self.searches > 0 {
+            // the old bridge goes first, a worker is shared by the bridges of the page and only
+            // closes with the last one
+            self.ai_worker = None;
+            self.ai_worker = Some(AiWorker::bridge(self.link.callback(Message::AiResponse)));
+            self.searches = 0;
+        }
+        self.send_new_game();
+        if self.vs_ai {
+            log::info!("AI seed: {}", self.ai_seed);
+        }
+        if self.vs_ai && self.props.difficulty == Difficulty::Adaptive {
+            self.history_task = Some(self.fetch_history());
+        }
+    }
+
+    fn send_to_worker(&mut self, request: AiRequest) {
+        if let AiRequest::FindMove { .. } | AiRequest::Hint { .. } = request {
+            self.searches += 1;
+        }
+        if let Some(worker) = &mut self.ai_worker {
+            worker.send(request);
+        }
+    }
+
+    fn send_new_game(&mut self) {
+        self.game_level = self.adaptive_level;
+        self.send_to_worker(AiRequest::NewGame {
+            game_type: self.game_type.to_string(),
+            board_rows: self.board_rows,
+            board_columns: self.board_columns,
+            difficulty: self.props.difficulty.to_string(),
+            level: self.adaptive_level,
+            seed: self.ai_seed,
+        });
+    }
+
+    // the player's latest games against the computer in this game, to find the level an
+    // Adaptive game should start at
+    fn fetch_history(&self) -> FetchTask {
+        let callback = self.link.callback(
+            |response: Response<Json<Result<HistoryPage, Error>>>| {
+                let (parts, Json(body)) = response.into_parts();
+                match body {
+                    Ok(page) if parts.status.is_success() => Message::PlayerHistory(page.histories),
+                    _ => Message::HistoryFailed,
+                }
+            }
+        );
+        let game_type = self.game_type.to_string();
+        let player = self.props.player1.clone().unwrap_or_default();
+        let computer = self.props.player2.clone().unwrap_or_default();
+        let url = history_url(&[
+            ("game_type", game_type.as_str()),
+            ("player1", player.as_str()),
+            ("player2", computer.as_str()),
+            ("sort", "date"),
+            ("order", "desc"),
+            ("limit", "50"),
+        ]);
+        let request = Request::get(url).body(Nothing).unwrap();
+        FetchService::fetch(request, callback).unwrap()
+    }
+
+    // the player's games against the computer in this game, newest first as they are fetched
+    pub fn set_adaptive_level(&mut self, histories: Vec<HistoryInfo>) {
+        let player = self.props.player1.clone().unwrap_or_default();
+        let games: Vec<(f64, MoveOutcome)> = histories.iter()
+            .rev()
+            .filter_map(|history| {
+                let level = adaptive::played_level(&history.difficulty, history.ai_level)?;
+                let outcome = if history.winner == player {
+                    MoveOutcome::Win
+                } else if history.winner == "Draw" {
+                    MoveOutcome::Draw
+                } else {
+                    MoveOutcome::Loss
+                };
+                Some((level, outcome))
+            })
+            .collect();
+        self.adaptive_level = adaptive::starting_level(&games);
+        log::info!("adaptive level for {}: {:.2} from {} games", player, self.adaptive_level, games.len());
+        // a new AI would start over in the middle of the game, once a move is made the level
+        // waits for the next game
+        if self.moves.is_empty() {
+            self.send_new_game();
+        }
+    }
+
     // Same for both Connect 4 and TOOT-and-OTTO
     pub fn draw_circle(&self, x: u32, y: u32, fill: &str, stroke: &str, text: &str) {
         let context = self.canvas_render_context.as_ref().unwrap();
@@ -180,6 +339,48 @@ impl CanvasModel {
         return (coord - x) * (coord - x) <= radius * radius;
     }
 
+    // Same for both Connect 4 and TOOT-and-OTTO
+    pub fn can_hint(&self) -> bool {
+        let computer_turn = self.vs_ai && self.game.current_player == 2;
+        !(self.won || self.paused || self.reject_click || self.thinking || computer_turn)
+    }
+
+    // Same for both Connect 4 and TOOT-and-OTTO
+    pub fn hint(&mut self) {
+        if !self.can_hint() {
+            return;
+        }
+
+        self.search_id += 1;
+        self.send_to_worker(AiRequest::Hint {
+            search_id: self.search_id,
+            board: encode_board(&self.game),
+            current_player: self.game.current_player,
+        });
+    }
+
+    // Mostly the same for both games, TOOT-and-OTTO also shows the suggested letter
+    pub fn show_hint(&mut self, column: usize, disc_type: DiscType) {
+        self.hints_used[self.game.current_player - 1] += 1;
+        log::info!("Hint for player {}: column {} ({})", self.game.current_player, column, disc_type);
+
+        // highlight the cell the suggested disc would land in
+        let row = (0..self.board_rows).rev()
+            .find(|&row| self.game.game_board.board[row][column] == DiscType::Empty)
+            .unwrap_or(0);
+        let text = if self.game_type == GameType::TOOTandOTTO { disc_type.to_string() } else { String::from("") };
+        self.clear();
+        self.draw();
+        self.draw_circle(
+            (75 * column + 100) as u32,
+            (75 * row + 50) as u32,
+            "rgba(46, 204, 64, 0.6)",
+            "black",
+            &text,
+        );
+        self.draw_mask();
+    }
+
     // Same for both Connect 4 and TOOT-and-OTTO
     pub fn check(&mut self) {
         match self.game.check() {
@@ -227,25 +428,66 @@ impl CanvasModel {
         } else {
             self.draw();
             self.check();
-            if self.vs_ai && self.game_type == GameType::Connect4 && self.game.current_player == 2 {
-                let mut connect4_ai = Connect4AI::new(self.board_rows, self.board_columns, self.props.difficulty);
-                let best_move = connect4_ai.find_best_move(self.game.clone());
-                log::info!("Computer Choose to place at column {}", best_move);
-                self.paused = false;
-                self.action(best_move, true);
-            } else if self.vs_ai && self.game_type == GameType::TOOTandOTTO && self.game.current_player == 2 {
-                let mut toototto_ai = TootOttoAI::new(self.board_rows, self.board_columns, self.props.difficulty);
-                let (best_move, disc_type) = toototto_ai.find_best_move(self.game.clone());
-
-                let current_disc_type = self.game.get_current_disc_type(); // record the current disc type
-                self.game.change_disc_type(disc_type); // set disc type to whatever the AI return
-                log::info!("Computer Choose to place at column {}", best_move);
-                self.paused = false;
-                self.action(best_move, true); // place the piece
-                self.game.change_disc_type(current_disc_type); // reset the disc type back to original
+            if self.vs_ai && !self.won && self.game.current_player == 2 {
+                // the move comes back as Message::AiResponse, clicks stay rejected until then
+                self.search_id += 1;
+                self.thinking = true;
+                self.send_to_worker(AiRequest::FindMove {
+                    search_id: self.search_id,
+                    board: encode_board(&self.game),
+                    current_player: self.game.current_player,
+                });
             } else {
                 self.reject_click = false;
             }
+            self.solve();
+        }
+    }
+
+    // Mostly the same for both games, TOOT-and-OTTO also uses the letter the AI chose
+    pub fn computer_move(&mut self, column: usize, disc_type: DiscType) {
+        log::info!("Computer Choose to place at column {}", column);
+        self.paused = false;
+        if self.game_type == GameType::TOOTandOTTO {
+            let current_disc_type = self.game.get_current_disc_type(); // record the current disc type
+            self.game.change_disc_type(disc_type); // set disc type to whatever the AI return
+            self.action(column, true); // place the piece
+            self.game.change_disc_type(current_disc_type); // reset the disc type back to original
+        } else {
+            self.action(column, true);
+        }
+    }
+
+    pub fn handle_ai_response(&mut self, response: AiResponse) {
+        match response {
+            // failures of NewGame and Solve have search_id 0
+            AiResponse::Move { .. } | AiResponse::Hint { .. } => self.searches = self.searches.saturating_sub(1),
+            AiResponse::Failed { search_id, .. } if search_id != 0 => self.searches = self.searches.saturating_sub(1),
+            _ => (),
+        }
+        match response {
+            AiResponse::Move { search_id, column, disc_type } if search_id == self.search_id => {
+                self.thinking = false;
+                match disc_type.parse() {
+                    Ok(disc_type) => self.computer_move(column, disc_type),
+                    Err(err) => log::info!("computer returned an invalid move: {}", err),
+                }
+            },
+            AiResponse::Hint { search_id, column, disc_type } if search_id == self.search_id => {
+                match disc_type.parse() {
+                    Ok(disc_type) => self.show_hint(column, disc_type),
+                    Err(err) => log::info!("hint returned an invalid move: {}", err),
+                }
+            },
+            AiResponse::Solution { solution_id, winner, moves } if solution_id == self.solution_id => {
+                self.show_solution(winner, moves);
+            },
+            AiResponse::Failed { search_id, reason } if search_id == self.search_id => {
+                self.thinking = false;
+                self.reject_click = false;
+                log::info!("AI failed to find a move: {}", reason);
+            },
+            _ => log::info!("ignoring out of date AI response"),
         }
     }
 
@@ -254,8 +496,15 @@ impl CanvasModel {
             return 0;
         }
 
+        let disc_type = self.game.get_current_disc_type();
         match self.game.place_disc(column) {
             GameEvent::PlaceSuccess(row) => {
+                if self.moves.is_empty() {
+                    self.started = Date::now();
+                }
+                self.moves.push((column, disc_type));
+                // a hint still on its way was for the position before this move
+                self.search_id += 1;
                 self.animate(column, row, 0, mode);
                 self.paused = true;
                 return 1
@@ -285,7 +534,8 @@ impl CanvasModel {
         context.set_fill_style_color("#111");
         context.fill_text(&to_print, 21.0 * self.board_columns as f64, 20.0, None); 
 
-        let difficulty = if self.vs_ai {self.props.difficulty.to_string()} else {"N/A".to_string()};
+        let difficulty = if self.vs_ai { self.props.difficulty.to_string() } else { "N/A".to_string() };
+        let vs_adaptive = self.vs_ai && self.props.difficulty == Difficulty::Adaptive;
 
         let history = HistoryInfo {
             game_type: self.game_type.to_string(),
@@ -300,6 +550,16 @@ impl CanvasModel {
             },
             difficulty: difficulty,
             time_played: "".to_string(), // doesn't matter here, backend will use the current time
+            player1_hints: self.hints_used[0],
+            player2_hints: self.hints_used[1],
+            board_rows: self.board_rows as u32,
+            board_columns: self.board_columns as u32,
+            moves: self.moves.iter().map(|game_move| format_move(&self.game_type, *game_move)).collect(),
+            first_player: self.props.player1.as_ref().unwrap().clone(), // player 1 always starts
+            duration_seconds: ((Date::now() - self.started) / 1000.0) as u32,
+            ai_seed: if self.vs_ai { Some(self.ai_seed) } else { None },
+            ai_level: if vs_adaptive { Some(self.game_level) } else { None },
+            id: String::new(), // given by the backend
         };
 
         // create callback for POST request to backend
@@ -315,11 +575,13 @@ impl CanvasModel {
             }
         });
 
-        // create the POST request
-        let request = Request::post("http://127.0.0.1:8000/history")
-            .header("Content-Type", "application/json")
-            .body(Json(&history))
-            .unwrap();
+        // create the POST request, a logged in player's game is linked to their account
+        let mut request = Request::post("http://127.0.0.1:8000/history")
+            .header("Content-Type", "application/json");
+        if let Some(authorization) = session::authorization() {
+            request = request.header("Authorization", authorization);
+        }
+        let request = request.body(Json(&history)).unwrap();
 
         // send the POST request
         self.fetch_task = FetchService::fetch(request, callback).ok();
@@ -362,6 +624,9 @@ impl Component for CanvasModel {
             }
         };
 
+        let ai_seed = random_seed();
+        let ai_worker = AiWorker::bridge(link.callback(Message::AiResponse));
+
         Self {
             props,
             canvas_id,
@@ -375,10 +640,23 @@ impl Component for CanvasModel {
             cbk: link.callback(|e: ClickEvent| Message::Click(e)),
             animate_cbk: link.callback(|e: (usize, usize, usize, bool)| Message::AnimateCallback(e)),
             game,
+            ai_seed,
+            adaptive_level: DEFAULT_LEVEL,
+            game_level: DEFAULT_LEVEL,
+            ai_worker: Some(ai_worker),
+            searches: 0,
+            search_id: 0,
+            thinking: false,
+            solution_id: 0,
+            solution: None,
             paused: false,
             won: false,
             reject_click: false,
+            hints_used: [0, 0],
+            moves: Vec::new(),
+            started: 0.0,
             fetch_task: None,
+            history_task: None,
             link,
         }
     }
@@ -414,6 +692,11 @@ impl Component for CanvasModel {
             Message::AnimateCallback((column, row, cur_pos, mode)) => {
                 self.animate(column, row, cur_pos, mode);
             }
+            Message::Hint => self.hint(),
+            Message::Reset => self.reset(),
+            Message::AiResponse(response) => self.handle_ai_response(response),
+            Message::PlayerHistory(histories) => self.set_adaptive_level(histories),
+            Message::HistoryFailed => log::info!("failed to fetch the game history, adaptive level stays at {:.2}", self.adaptive_level),
             Message::PostSuccess(msg) => log::info!("game history successfully recorded: {}", msg),
             Message::PostFailedWithErr(err) => log::info!("failed to record game history with err: {}", err),
             Message::PostFailed => log::info!("failed to record game history")
@@ -423,6 +706,7 @@ impl Component for CanvasModel {
     }
 
     fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        let difficulty_changed = self.props.difficulty != props.difficulty;
         self.props = props;
         let board_columns = self.props.board_columns.unwrap();
         let board_rows = self.props.board_rows.unwrap();
@@ -437,8 +721,13 @@ impl Component for CanvasModel {
             };
             self.board_rows = board_rows;
             self.board_columns = board_columns;
+            self.moves.clear();
+            self.create_ai();
+            self.solve();
             self.clear();
             self.draw_mask();
+        } else if difficulty_changed {
+            self.create_ai();
         }
 
         // Only TOOT-and-OTTO is allowed to change disc-type / text
@@ -457,7 +746,26 @@ impl Component for CanvasModel {
 
     fn view(&self) -> Html {
         html! {
-            <canvas id={&self.canvas_id} height="640" width="1000"></canvas>
+            <>
+                <button
+                    onclick = self.link.callback(|_| Message::Hint)
+                    disabled = {!self.can_hint()}
+                >
+                    {"Hint"}
+                </button>
+                {"\u{00a0}"}
+                <button onclick = self.link.callback(|_| Message::Reset)>
+                    {"Reset"}
+                </button>
+                {format!("\u{00a0}\u{00a0}Hints used: {}", self.hints_used[0] + self.hints_used[1])}
+                {if self.thinking { "\u{00a0}\u{00a0}Computer is thinking..." } else { "" }}
+                {match &self.solution {
+                    Some(solution) => format!("\u{00a0}\u{00a0}{}", solution),
+                    None => String::new(),
+                }}
+                <br/>
+                <canvas id={&self.canvas_id} height="640" width="1000"></canvas>
+            </>
         }
     }
 
@@ -0,0 +1,19 @@
+use yew::prelude::*;
+use crate::session;
+use crate::switch::{AppAnchor, AppRoute};
+
+// under the name fields, whether the games will be recorded under an account
+pub fn guest_notice() -> Html {
+    match session::current() {
+        Some(session) => html! {
+            <p><small>{ format!("Logged in as {}, your games are recorded under your account.", session.account.name) }</small></p>
+        },
+        None => html! {
+            <p><small>
+                {"Playing as a guest. "}
+                <AppAnchor route=AppRoute::Login>{"Log in"}</AppAnchor>
+                {" to record your games under your account."}
+            </small></p>
+        },
+    }
+}
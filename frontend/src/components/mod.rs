@@ -1,3 +1,4 @@
 pub mod canvas_model;
 pub mod alert;
 pub mod text_input;
+pub mod guest_notice;
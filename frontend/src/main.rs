@@ -4,8 +4,11 @@
 #[global_allocator]
 static ALLOC: wee_alloc::WeeAlloc = wee_alloc::WeeAlloc::INIT;
 
+use anyhow::Error;
 use yew::prelude::*;
-use yew_router::{route::Route, switch::Permissive};
+use yew::format::Nothing;
+use yew::services::fetch::{FetchService, FetchTask, Request, Response};
+use yew_router::{agent::RouteAgentBridge, route::Route, switch::Permissive};
 
 // mod components;
 mod pages;
@@ -19,20 +22,28 @@ use pages::{
     how_to_toot::HowToToot, 
     game_history::GameHistory,
     score_board::ScoreBoard,
+    login::Login,
+    logout::Logout,
     page_not_found::PageNotFound,
 };
 mod switch;
 use switch::{AppAnchor, AppRoute, AppRouter, PublicUrlSwitch};
+mod agents;
 mod components;
+mod session;
 mod types;
 
 pub enum Msg {
     ToggleNavbar,
+    RouteChanged, // the menu shows whether someone is logged in, which changes on the login pages
+    LoginChecked(bool), // false if the stored login has expired
 }
 
 pub struct Model {
     link: ComponentLink<Self>,
     navbar_active: bool,
+    _route_bridge: RouteAgentBridge,
+    fetch_task: Option<FetchTask>,
 }
 
 impl Component for Model {
@@ -40,9 +51,23 @@ impl Component for Model {
     type Properties = ();
 
     fn create(_props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        let route_bridge = RouteAgentBridge::new(link.callback(|_: Route| Msg::RouteChanged));
+        // a login kept from an earlier visit may have expired or been logged out elsewhere
+        let fetch_task = session::authorization().and_then(|authorization| {
+            let request = Request::get("http://127.0.0.1:8000/account")
+                .header("Authorization", authorization)
+                .body(Nothing)
+                .unwrap();
+            let callback = link.callback(|response: Response<Result<String, Error>>| {
+                Msg::LoginChecked(response.status().as_u16() != 401)
+            });
+            FetchService::fetch(request, callback).ok()
+        });
         Self {
             link,
             navbar_active: false,
+            _route_bridge: route_bridge,
+            fetch_task,
         }
     }
 
@@ -52,6 +77,14 @@ impl Component for Model {
                 self.navbar_active = !self.navbar_active;
                 true
             }
+            Msg::RouteChanged => true,
+            Msg::LoginChecked(valid) => {
+                self.fetch_task = None;
+                if !valid {
+                    session::clear();
+                }
+                !valid
+            }
         }
     }
 
@@ -120,6 +153,8 @@ impl Model {
                     <AppAnchor classes="w3-padding w3-hover-white" route=AppRoute::ScoreBoard>
                         {"Score Board"}
                     </AppAnchor>
+                    <br/>
+                    { self.view_account() }
                 </nav>
 
                 // <!-- Top menu on small screens -->
@@ -133,6 +168,21 @@ impl Model {
         }
     }
 
+    fn view_account(&self) -> Html {
+        match session::current() {
+            Some(session) => html! {
+                <AppAnchor classes="w3-padding w3-hover-white" route=AppRoute::Logout>
+                    { format!("Log Out ({})", session.account.name) }
+                </AppAnchor>
+            },
+            None => html! {
+                <AppAnchor classes="w3-padding w3-hover-white" route=AppRoute::Login>
+                    {"Log In / Register"}
+                </AppAnchor>
+            },
+        }
+    }
+
     fn switch(switch: PublicUrlSwitch) -> Html {
         match switch.route() {
             AppRoute::Home => {
@@ -162,6 +212,12 @@ impl Model {
             AppRoute::ScoreBoard => {
                 html! { <ScoreBoard /> }
             }
+            AppRoute::Login => {
+                html! { <Login /> }
+            }
+            AppRoute::Logout => {
+                html! { <Logout /> }
+            }
             AppRoute::PageNotFound(Permissive(route)) => {
                 html! { <PageNotFound route=route /> }
             }
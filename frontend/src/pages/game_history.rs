@@ -1,29 +1,31 @@
 // code skeleton from yew documentation: https://yew.rs/docs/en/next/concepts/services/fetch
-use anyhow::Error;
 use yew::prelude::*;
 use yew::services::fetch::{FetchService, FetchTask, Request, Response};
-use yew::format::{Json, Nothing};
-use crate::types::HistoryInfo;
+use yew::format::Json;
+use crate::types::{history_url, HistoryInfo, HistoryPage};
+
+const PAGE_SIZE: &str = "20";
 
 pub struct GameHistory {
     link: ComponentLink<Self>,
     history: Vec<HistoryInfo>,
+    total: u64,
+    next: Option<String>, // cursor for the next page
     fetch_task: Option<FetchTask>,
 }
 
 pub enum Msg {
-    FetchSuccess(Vec<HistoryInfo>),
+    FetchSuccess(HistoryPage),
+    FetchMore,
     FetchDataError,
     FetchFailed,
-    PostDrop,
-    PostSuccess,
-    PostFailed,
 }
 
 impl GameHistory {
-    fn fetch(&mut self) -> FetchTask {
+    // newest games first, the page after `cursor` or the first one
+    fn fetch(&mut self, cursor: Option<String>) -> FetchTask {
         let callback = self.link.callback(
-            |response: Response<Json<Result<Vec<HistoryInfo>, anyhow::Error>>>| {
+            |response: Response<Json<Result<HistoryPage, anyhow::Error>>>| {
                 let (parts, Json(body)) = response.into_parts();
                 if parts.status.is_success() {
                     if let Ok(data) = body {
@@ -39,10 +41,26 @@ impl GameHistory {
             }
         );
 
-        let request = Request::get("http://127.0.0.1:8000/history").body(yew::format::Nothing).unwrap();
+        let mut params = vec![("sort", "date"), ("order", "desc"), ("limit", PAGE_SIZE)];
+        if let Some(cursor) = &cursor {
+            params.push(("cursor", cursor.as_str()));
+        }
+        let request = Request::get(history_url(&params)).body(yew::format::Nothing).unwrap();
         FetchService::fetch(request, callback).unwrap()
     }
 
+    fn view_more(&self) -> Html {
+        if self.next.is_some() {
+            html! {
+                <button onclick=self.link.callback(|_| Msg::FetchMore) style="margin-top: 1.5em">
+                    { format!("Show More ({} of {} games shown)", self.history.len(), self.total) }
+                </button>
+            }
+        } else {
+            html! {}
+        }
+    }
+
     fn view_history(&self) -> Html {
         if self.history.len() > 0 {
             html! {
@@ -55,6 +73,7 @@ impl GameHistory {
                             <td> { history.player2.clone() } </td>
                             <td> { history.difficulty.clone() } </td>
                             <td> { history.winner.clone()} </td>
+                            <td> { format!("{} / {}", history.player1_hints, history.player2_hints) } </td>
                             <td> { history.time_played.clone() } </td>
                         </tr>
                     }
@@ -63,7 +82,7 @@ impl GameHistory {
         } else {
             html! {
                 <tr>
-                    <td colspan="7"> {"Failed to get history or no game history exist"} </td>
+                    <td colspan="8"> {"Failed to get history or no game history exist"} </td>
                 </tr>
             }
         }
@@ -78,44 +97,29 @@ impl Component for GameHistory {
         let mut this = Self {
             link,
             history: Vec::new(),
+            total: 0,
+            next: None,
             fetch_task: None
         };
         // store the task so it isn't cancelled immediately
-        this.fetch_task = Some(this.fetch());
+        this.fetch_task = Some(this.fetch(None));
         this
     }
 
     fn update(&mut self, msg: Self::Message) -> ShouldRender {
         match msg {
-            Msg::FetchSuccess(data) => {
-                self.history = data;
+            Msg::FetchSuccess(page) => {
+                self.history.extend(page.histories);
+                self.total = page.total;
+                self.next = page.next;
                 self.fetch_task = None;
             }
+            Msg::FetchMore => {
+                let cursor = self.next.take();
+                self.fetch_task = Some(self.fetch(cursor));
+            }
             Msg::FetchFailed => log::info!("fetching history failed"),
             Msg::FetchDataError => log::info!("fetched data contains error"),
-            Msg::PostDrop => {
-                // create callback for POST request to backend
-                let callback = self.link.callback(move |response: Response<Result<String, Error>>| {
-                    if response.status().is_success() {
-                        Msg::PostSuccess
-                    } else {
-                        Msg::PostFailed
-                    }
-                });
-
-                // create the POST request
-                let request = Request::delete("http://127.0.0.1:8000/history")
-                    .body(Nothing)
-                    .unwrap();
-
-                // send the POST request
-                self.fetch_task = FetchService::fetch(request, callback).ok();
-            },
-            Msg::PostSuccess => {
-                log::info!("drop collection successful");
-                self.fetch_task = Some(self.fetch());
-            },
-            Msg::PostFailed => log::info!("failed to drop collection"),
         }
         true
     }
@@ -129,7 +133,6 @@ impl Component for GameHistory {
             <div class="w3-container" id="services" style="margin-top:75px">
                 <h5 class="w3-xxxlarge w3-text-red"><b>{"Game History"}</b></h5>
                 <hr style="width:50px;border:5px solid red" class="w3-round" />
-                <button onclick=self.link.callback(|_| Msg::PostDrop) style="margin-bottom: 1.5em">{"Clear Game History"}</button>
                 <table>
                     <tr>
                         <th>{"Game ID"}</th>
@@ -138,12 +141,14 @@ impl Component for GameHistory {
                         <th>{"Player2"}</th>
                         <th>{"Difficulty"}</th>
                         <th>{"Winner"}</th>
+                        <th>{"Hints Used (Player1 / Player2)"}</th>
                         <th>{"When Played (UTC Time)"}</th>
                     </tr>
 
                     { self.view_history() }
                 
                 </table>
+                { self.view_more() }
             </div>
         }
     }
@@ -0,0 +1,144 @@
+use anyhow::Error;
+use yew::prelude::*;
+use yew::format::Json;
+use yew::services::fetch::{FetchService, FetchTask, Request, Response};
+use yew_router::agent::{RouteAgentDispatcher, RouteRequest};
+use crate::components::text_input::TextInput;
+use crate::session;
+use crate::switch::{AppAnchor, AppRoute};
+use crate::types::{Credentials, SessionInfo};
+
+pub enum Msg {
+    SetName(String),
+    SetPassword(String),
+    Login,
+    Register,
+    LoggedIn(SessionInfo),
+    Failed(String),
+}
+
+// logs in to an account, or registers a new one and logs in to it
+pub struct Login {
+    link: ComponentLink<Self>,
+    name: String,
+    password: String,
+    error: String,
+    fetch_task: Option<FetchTask>,
+}
+
+impl Login {
+    // POST /login or POST /accounts, which answer the same way
+    fn send(&mut self, url: &str) {
+        if self.name.is_empty() || self.password.is_empty() {
+            self.error = String::from("Name and password cannot be empty");
+            return;
+        }
+        let credentials = Credentials { name: self.name.clone(), password: self.password.clone() };
+        let callback = self.link.callback(|response: Response<Result<String, Error>>| {
+            let (parts, body) = response.into_parts();
+            match body {
+                Ok(body) if parts.status.is_success() => {
+                    let Json(session) = Json::<Result<SessionInfo, Error>>::from(Ok(body));
+                    match session {
+                        Ok(session) => Msg::LoggedIn(session),
+                        Err(_) => Msg::Failed(String::from("Unexpected answer from the server")),
+                    }
+                }
+                // the backend tells why, such as a taken name or a short password
+                Ok(reason) => Msg::Failed(reason),
+                Err(_) => Msg::Failed(String::from("Failed to reach the server")),
+            }
+        });
+        let request = Request::post(url)
+            .header("Content-Type", "application/json")
+            .body(Json(&credentials))
+            .unwrap();
+        self.error = String::new();
+        self.fetch_task = FetchService::fetch(request, callback).ok();
+    }
+}
+
+impl Component for Login {
+    type Message = Msg;
+    type Properties = ();
+
+    fn create(_props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        Self {
+            link,
+            name: String::new(),
+            password: String::new(),
+            error: String::new(),
+            fetch_task: None,
+        }
+    }
+
+    fn update(&mut self, msg: Self::Message) -> ShouldRender {
+        match msg {
+            Msg::SetName(name) => self.name = name,
+            Msg::SetPassword(password) => self.password = password,
+            Msg::Login => self.send("http://127.0.0.1:8000/login"),
+            Msg::Register => self.send("http://127.0.0.1:8000/accounts"),
+            Msg::LoggedIn(session) => {
+                self.fetch_task = None;
+                self.password = String::new();
+                session::save(&session);
+                // to the home page, which also shows the account in the menu
+                RouteAgentDispatcher::<()>::new().send(RouteRequest::ChangeRoute(AppRoute::Home.into_route()));
+            }
+            Msg::Failed(reason) => {
+                self.fetch_task = None;
+                self.error = reason;
+            }
+        }
+        true
+    }
+
+    fn change(&mut self, _props: Self::Properties) -> ShouldRender {
+        false
+    }
+
+    fn view(&self) -> Html {
+        if let Some(session) = session::current() {
+            return html! {
+                <div class="w3-container" id="services" style="margin-top:75px">
+                    <h5 class="w3-xxxlarge w3-text-red"><b>{"Account"}</b></h5>
+                    <hr style="width:50px;border:5px solid red" class="w3-round"/>
+                    <p>{ format!("You are logged in as {}.", session.account.name) }</p>
+                    <AppAnchor route=AppRoute::Logout>{"Log Out"}</AppAnchor>
+                </div>
+            };
+        }
+
+        let sending = self.fetch_task.is_some();
+        html! {
+            <div class="w3-container" id="services" style="margin-top:75px">
+                <h5 class="w3-xxxlarge w3-text-red"><b>{"Log In or Register"}</b></h5>
+                <hr style="width:50px;border:5px solid red" class="w3-round"/>
+                <p>{"Games you play while logged in are recorded under your account, nobody else can play under your name. You can also play as a guest without an account."}</p>
+
+                <div class="col-md-offset-3 col-md-8">
+                    <TextInput
+                        value = self.name.clone()
+                        placeholder = "Player's Name"
+                        oninput = self.link.callback(Msg::SetName)
+                        disabled = {sending}
+                    />
+                    {"\u{00a0}\u{00a0}\u{00a0}\u{00a0}"} // add some spaces between the elements
+                    <input
+                        type="password"
+                        value=&self.password
+                        placeholder="Password"
+                        oninput=self.link.callback(|e: InputData| Msg::SetPassword(e.value))
+                        disabled=sending
+                    />
+                    {"\u{00a0}\u{00a0}\u{00a0}\u{00a0}"} // add some spaces between the elements
+                    <button onclick=self.link.callback(|_| Msg::Login) disabled=sending>{"Log In"}</button>
+                    {"\u{00a0}\u{00a0}"}
+                    <button onclick=self.link.callback(|_| Msg::Register) disabled=sending>{"Register"}</button>
+                </div>
+                <p class="w3-text-red">{ &self.error }</p>
+                <small>{"Names have up to 20 letters, digits, spaces, - and _. Passwords have at least 8 characters."}</small>
+            </div>
+        }
+    }
+}
@@ -0,0 +1,64 @@
+use anyhow::Error;
+use yew::prelude::*;
+use yew::format::Nothing;
+use yew::services::fetch::{FetchService, FetchTask, Request, Response};
+use yew_router::agent::{RouteAgentDispatcher, RouteRequest};
+use crate::session;
+use crate::switch::AppRoute;
+
+pub enum Msg {
+    LoggedOut,
+}
+
+// ends the login on the backend and forgets it here, then goes to the home page
+pub struct Logout {
+    fetch_task: Option<FetchTask>,
+}
+
+impl Component for Logout {
+    type Message = Msg;
+    type Properties = ();
+
+    fn create(_props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        let fetch_task = session::authorization().and_then(|authorization| {
+            // logged out here whether the backend answers or not
+            session::clear();
+            let request = Request::post("http://127.0.0.1:8000/logout")
+                .header("Authorization", authorization)
+                .body(Nothing)
+                .unwrap();
+            let callback = link.callback(|_: Response<Result<String, Error>>| Msg::LoggedOut);
+            FetchService::fetch(request, callback).ok()
+        });
+        Self { fetch_task }
+    }
+
+    fn update(&mut self, msg: Self::Message) -> ShouldRender {
+        match msg {
+            Msg::LoggedOut => {
+                self.fetch_task = None;
+                RouteAgentDispatcher::<()>::new().send(RouteRequest::ChangeRoute(AppRoute::Home.into_route()));
+            }
+        }
+        true
+    }
+
+    fn change(&mut self, _props: Self::Properties) -> ShouldRender {
+        false
+    }
+
+    fn view(&self) -> Html {
+        let text = if self.fetch_task.is_some() {
+            "Logging out..."
+        } else {
+            "You are logged out, games are played as a guest until you log in again."
+        };
+        html! {
+            <div class="w3-container" id="services" style="margin-top:75px">
+                <h5 class="w3-xxxlarge w3-text-red"><b>{"Log Out"}</b></h5>
+                <hr style="width:50px;border:5px solid red" class="w3-round"/>
+                <p>{ text }</p>
+            </div>
+        }
+    }
+}
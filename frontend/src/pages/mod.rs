@@ -7,4 +7,6 @@ pub mod play_toototto_with_computer;
 pub mod play_toototto_with_human;
 pub mod game_history;
 pub mod score_board;
+pub mod login;
+pub mod logout;
 pub mod page_not_found;
@@ -4,7 +4,9 @@ use model::ai::Difficulty;
 use model::board_size::BoardSize;
 use crate::components::canvas_model::CanvasModel;
 use crate::components::text_input::TextInput;
+use crate::components::guest_notice::guest_notice;
 use crate::components::alert::alert;
+use crate::session;
 
 pub enum Msg {
     StartGame,
@@ -31,7 +33,7 @@ impl Component for PlayConnect4WithComputer {
 
     fn create(_props: Self::Properties, link: ComponentLink<Self>) -> Self {
         Self {
-            player1_name: String::from(""),
+            player1_name: session::player_name(), // a guest types their name
             player2_name: String::from("Computer"),
             game_running: false,
             disable_button: false,
@@ -112,6 +114,7 @@ impl Component for PlayConnect4WithComputer {
                     {"Start Game"}
                     </button>
                 </div>
+                { guest_notice() }
                 <div style=format!("display: {}", self.display_board)>
                     <br/>
                     <h4>{format!("New Game: {} Vs {}", self.player1_name, self.player2_name)}</h4>
@@ -1,7 +1,9 @@
 use yew::prelude::*;
 use crate::components::text_input::TextInput;
+use crate::components::guest_notice::guest_notice;
 use crate::components::canvas_model::CanvasModel;
 use crate::components::alert::alert;
+use crate::session;
 use yew_components::Select;
 use model::ai::Difficulty;
 use model::board_size::BoardSize;
@@ -30,7 +32,7 @@ impl Component for PlayConnect4WithHuman {
 
     fn create(_props: Self::Properties, link: ComponentLink<Self>) -> Self {
         Self {
-            player1_name: String::from(""),
+            player1_name: session::player_name(), // a guest types their name
             player2_name: String::from(""),
             game_running: false,
             disable_button: false,
@@ -110,6 +112,7 @@ impl Component for PlayConnect4WithHuman {
                         {"Start Game"}
                     </button>
                 </div>
+                { guest_notice() }
                 <div style=format!("display: {}", self.display_board)>
                     <br/>
                     <h4>{format!("New Game: {} Vs {}", self.player1_name, self.player2_name)}</h4>
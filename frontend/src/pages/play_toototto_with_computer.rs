@@ -1,8 +1,10 @@
 use yew::prelude::*;
 use yew_components::Select;
 use crate::components::text_input::TextInput;
+use crate::components::guest_notice::guest_notice;
 use crate::components::canvas_model::CanvasModel;
 use crate::components::alert::alert;
+use crate::session;
 use model::ai::Difficulty;
 use model::board_size::BoardSize;
 
@@ -34,7 +36,7 @@ impl Component for PlayTOOTOTTOWithComputer {
 
     fn create(_props: Self::Properties, link: ComponentLink<Self>) -> Self {
         Self {
-            player1_name: String::from(""),
+            player1_name: session::player_name(), // a guest types their name
             player2_name: String::from("Computer"),
             game_running: false,
             disable_button: false,
@@ -119,6 +121,7 @@ impl Component for PlayTOOTOTTOWithComputer {
                     {"Start Game"}
                     </button>
                 </div>
+                { guest_notice() }
                 <div style=format!("display: {}", self.display_board)>
                     <br/>
                     <h4>{format!("New Game: {} Vs {}", self.player1_name, self.player2_name)}</h4>
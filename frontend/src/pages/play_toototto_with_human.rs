@@ -2,8 +2,10 @@ use yew::prelude::*;
 use yew::html::InputData;
 use yew_components::Select;
 use crate::components::text_input::TextInput;
+use crate::components::guest_notice::guest_notice;
 use crate::components::canvas_model::CanvasModel;
 use crate::components::alert::alert;
+use crate::session;
 use model::ai::Difficulty;
 use model::board_size::BoardSize;
 
@@ -34,7 +36,7 @@ impl Component for PlayTOOTOTTOWithHuman {
 
     fn create(_props: Self::Properties, link: ComponentLink<Self>) -> Self {
         Self {
-            player1_name: String::from(""),
+            player1_name: session::player_name(), // a guest types their name
             player2_name: String::from(""),
             game_running: false,
             disable_button: false,
@@ -118,6 +120,7 @@ impl Component for PlayTOOTOTTOWithHuman {
                     {"Start Game"}
                     </button>
                 </div>
+                { guest_notice() }
                 
                 <div style=format!("display: {}", self.display_board)>
                     <br/>
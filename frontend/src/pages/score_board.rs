@@ -1,31 +1,60 @@
 use yew::prelude::*;
 use yew::services::fetch::{FetchService, FetchTask, Request, Response};
 use yew::format::Json;
-use std::collections::HashMap;
-use crate::types::HistoryInfo;
+use serde::de::DeserializeOwned;
+use crate::types::{
+    backend_url, history_url, player_ratings_url, HistoryInfo, HistoryPage, LeaderboardEntry, PlayerRatings, RatedPlayer,
+};
+
+// the counts only need the totals, no games
+const TOTALS: [&[(&str, &str)]; 3] = [
+    &[("limit", "0")],                         // all games
+    &[("player2", "Computer"), ("limit", "0")], // games against the computer
+    &[("winner", "Computer"), ("limit", "0")],  // games the computer won
+];
+
+// the game type of each champion table, every game for the last one
+const CHAMPION_GAMES: [&str; 3] = ["Connect-4", "TOOT-OTTO", ""];
+
+// the rating table shows the best rated players and the chart how the ratings of the best few
+// went, game after game
+const RATING_TABLE_SIZE: usize = 10;
+const CHART_COLOURS: [&str; 5] = ["red", "blue", "green", "orange", "purple"];
+const CHART_WIDTH: f64 = 600.0;
+const CHART_HEIGHT: f64 = 300.0;
 
 // this Struct and its impelementation is very similar to GameHistory in game_history.rs
 // only the stats functions and htmls are different 
 pub struct ScoreBoard {
     link: ComponentLink<Self>,
-    history: Vec<HistoryInfo>,
-    fetch_task: Option<FetchTask>,
+    totals: [Option<u64>; 3], // in the order of TOTALS
+    computer_wins: Vec<HistoryInfo>,
+    computer_wins_next: Option<String>, // cursor for the next page of them
+    champions: [Vec<LeaderboardEntry>; 3], // in the order of CHAMPION_GAMES
+    ratings: Vec<RatedPlayer>, // with the difficulties of the computer, best first
+    rating_histories: Vec<PlayerRatings>, // for the chart
+    fetch_tasks: Vec<FetchTask>,
 }
 
 pub enum Msg {
-    FetchSuccess(Vec<HistoryInfo>),
+    Total(usize, u64),
+    ComputerWins(HistoryPage),
+    MoreComputerWins,
+    Champions(usize, Vec<LeaderboardEntry>),
+    Ratings(Vec<RatedPlayer>),
+    PlayerRatings(PlayerRatings),
     FetchDataError,
     FetchFailed,
 }
 
 impl ScoreBoard {
-    fn fetch(&mut self) -> FetchTask {
+    fn fetch<T: DeserializeOwned + 'static>(&self, url: String, on_data: impl Fn(T) -> Msg + 'static) -> FetchTask {
         let callback = self.link.callback(
-            |response: Response<Json<Result<Vec<HistoryInfo>, anyhow::Error>>>| {
+            move |response: Response<Json<Result<T, anyhow::Error>>>| {
                 let (parts, Json(body)) = response.into_parts();
                 if parts.status.is_success() {
                     if let Ok(data) = body {
-                        Msg::FetchSuccess(data)
+                        on_data(data)
                     } else {
                         // fetched data contains error
                         Msg::FetchDataError
@@ -37,17 +66,34 @@ impl ScoreBoard {
             }
         );
 
-        let request = Request::get("http://127.0.0.1:8000/history").body(yew::format::Nothing).unwrap();
+        let request = Request::get(url).body(yew::format::Nothing).unwrap();
         FetchService::fetch(request, callback).unwrap()
     }
 
+    fn fetch_computer_wins(&self, cursor: Option<String>) -> FetchTask {
+        let mut params = vec![("winner", "Computer"), ("limit", "20")];
+        if let Some(cursor) = &cursor {
+            params.push(("cursor", cursor.as_str()));
+        }
+        self.fetch(history_url(&params), Msg::ComputerWins)
+    }
+
+    // the players with the most wins, counted by the backend
+    fn fetch_champions(&self, index: usize) -> FetchTask {
+        let mut params = vec![("by", "wins"), ("limit", "10")];
+        if !CHAMPION_GAMES[index].is_empty() {
+            params.push(("game_type", CHAMPION_GAMES[index]));
+        }
+        self.fetch(backend_url("leaderboard", &params), move |entries| Msg::Champions(index, entries))
+    }
+
     fn games_won_by_computer_stat(&self) -> Html {
-        if self.history.len() > 0 {
+        if let [Some(total), Some(against_computer), Some(computer_won)] = self.totals {
             html! {
                 <tr>
-                    <td> { self.history.len() } </td> // Total Games Played
-                    <td> { self.history.iter().filter(|history| history.player2.eq("Computer")).count() } </td> // Games Againest Computer
-                    <td> { self.history.iter().filter(|history| history.winner.eq("Computer")).count() }</td> // Games Computer Won
+                    <td> { total } </td> // Total Games Played
+                    <td> { against_computer } </td> // Games Againest Computer
+                    <td> { computer_won }</td> // Games Computer Won
 
                 </tr>
             }
@@ -61,9 +107,9 @@ impl ScoreBoard {
     }
 
     fn detail_won_by_computer_stat(&self) -> Html {
-        if self.history.len() > 0 {
+        if self.computer_wins.len() > 0 {
             html! {
-                { self.history.iter().filter(|history| history.winner.eq("Computer")).enumerate().map(|(i, history)| {
+                { self.computer_wins.iter().enumerate().map(|(i, history)| {
                     html! {
                         <tr>
                             <td> { i + 1 } </td> // Sl. No.
@@ -85,30 +131,28 @@ impl ScoreBoard {
         }
     }
 
-    fn champion_games_stat(&self, filter_cond: String) -> Html {
-        if self.history.len() > 0 {
-            let mut map = HashMap::new();
-            self.history.iter().filter(|history| 
-                if filter_cond.len() == 0 {
-                    return true
-                } else {
-                    return history.game_type.eq(&filter_cond)
-                }
-            ).for_each(|history| {
-                *map.entry(history.winner.clone()).or_insert(0) += 1;
-            });
-            
-            // sorting from https://stackoverflow.com/a/63966206
-            let mut hash_vec: Vec<(&String, &u32)> = map.iter().collect();
-            hash_vec.sort_by(|a, b| b.1.cmp(a.1));
+    fn view_more_computer_wins(&self) -> Html {
+        if self.computer_wins_next.is_some() {
+            html! {
+                <button onclick=self.link.callback(|_| Msg::MoreComputerWins) style="margin-top: 1em">{"Show More"}</button>
+            }
+        } else {
+            html! {}
+        }
+    }
 
+    fn champion_games_stat(&self, index: usize) -> Html {
+        if self.champions[index].len() > 0 {
             html! {
-                { hash_vec.iter().enumerate().map(|(i, (winner, num_wins))| {
+                { self.champions[index].iter().map(|entry| {
                     html! {
                         <tr>
-                            <td> { i + 1 } </td> // Sl. No.
-                            <td> { winner } </td> // Winner or draw
-                            <td> { num_wins } </td> // No. of Wins
+                            <td> { entry.rank } </td> // Sl. No.
+                            <td> { entry.player.clone() } </td> // Player Name
+                            <td> { entry.wins } </td> // No. of Wins
+                            <td> { entry.games } </td> // Games Played
+                            <td> { format!("{:.0}%", entry.win_rate * 100.0) } </td> // Win Rate
+                            <td> { self.rating_text(&entry.player) } </td> // Rating
                         </tr>
                     }
                 }).collect::<Html>() }
@@ -116,11 +160,80 @@ impl ScoreBoard {
         } else {
             html! {
                 <tr>
-                    <td colspan="3"> {"Failed to get history or no game history exist"} </td>
+                    <td colspan="5"> {"Failed to get history or no game history exist"} </td>
+                </tr>
+            }
+        }
+    }
+
+    // the champion tables count the computer's wins at every difficulty, it has no rating of its own
+    fn rating_text(&self, player: &str) -> String {
+        match self.ratings.iter().find(|rated| rated.player == player) {
+            Some(rated) => format!("{:.0}", rated.rating),
+            None => String::from("-"),
+        }
+    }
+
+    fn rating_stat(&self) -> Html {
+        if self.ratings.len() > 0 {
+            html! {
+                { self.ratings.iter().take(RATING_TABLE_SIZE).map(|rated| {
+                    html! {
+                        <tr>
+                            <td> { rated.rank } </td> // Rank
+                            <td> { rated.player.clone() } </td> // Player Name
+                            <td> { format!("{:.0} \u{b1} {:.0}", rated.rating, 2.0 * rated.deviation) } </td> // Rating
+                            <td> { rated.games } </td> // Games Rated
+                        </tr>
+                    }
+                }).collect::<Html>() }
+            }
+        } else {
+            html! {
+                <tr>
+                    <td colspan="4"> {"Failed to get ratings or no game history exist"} </td>
                 </tr>
             }
         }
     }
+
+    // the rating after each game of the best rated players, one line each
+    fn rating_chart(&self) -> Html {
+        let histories: Vec<&PlayerRatings> = self.rating_histories.iter().filter(|ratings| ratings.history.len() > 0).collect();
+        if histories.is_empty() {
+            return html! {};
+        }
+        let ratings = histories.iter().flat_map(|ratings| ratings.history.iter().map(|point| point.rating));
+        let low = ratings.clone().fold(f64::INFINITY, f64::min) - 50.0;
+        let high = ratings.fold(f64::NEG_INFINITY, f64::max) + 50.0;
+        let games = histories.iter().map(|ratings| ratings.history.len()).max().unwrap_or(0).max(2);
+        let x = |game: usize| game as f64 * CHART_WIDTH / (games - 1) as f64;
+        let y = |rating: f64| CHART_HEIGHT - (rating - low) * CHART_HEIGHT / (high - low);
+
+        html! {
+            <div>
+                <svg width=CHART_WIDTH.to_string() height=CHART_HEIGHT.to_string() style="border: 1px solid #ccc">
+                    <text x="4" y="14"> { format!("{:.0}", high) } </text>
+                    <text x="4" y=(CHART_HEIGHT - 4.0).to_string()> { format!("{:.0}", low) } </text>
+                    { histories.iter().zip(CHART_COLOURS.iter()).map(|(ratings, colour)| {
+                        let points: Vec<String> = ratings.history.iter().enumerate()
+                            .map(|(game, point)| format!("{:.1},{:.1}", x(game), y(point.rating)))
+                            .collect();
+                        html! {
+                            <polyline points=points.join(" ") fill="none" stroke=colour.to_string() stroke-width="2" />
+                        }
+                    }).collect::<Html>() }
+                </svg>
+                <div>
+                    { histories.iter().zip(CHART_COLOURS.iter()).map(|(ratings, colour)| {
+                        html! {
+                            <span style=format!("color: {}; margin-right: 1em", colour)> { ratings.player.clone() } </span>
+                        }
+                    }).collect::<Html>() }
+                </div>
+            </div>
+        }
+    }
 }
 
 impl Component for ScoreBoard {
@@ -130,19 +243,58 @@ impl Component for ScoreBoard {
     fn create(_props: Self::Properties, link: ComponentLink<Self>) -> Self {
         let mut this = Self {
             link,
-            history: Vec::new(),
-            fetch_task: None
+            totals: [None; 3],
+            computer_wins: Vec::new(),
+            computer_wins_next: None,
+            champions: [Vec::new(), Vec::new(), Vec::new()],
+            ratings: Vec::new(),
+            rating_histories: Vec::new(),
+            fetch_tasks: Vec::new(),
         };
-        // store the task so it isn't cancelled immediately
-        this.fetch_task = Some(this.fetch());
+        // store the tasks so they aren't cancelled immediately
+        for (index, params) in TOTALS.iter().enumerate() {
+            let task = this.fetch(history_url(params), move |page: HistoryPage| Msg::Total(index, page.total));
+            this.fetch_tasks.push(task);
+        }
+        this.fetch_tasks.push(this.fetch_computer_wins(None));
+        for index in 0..CHAMPION_GAMES.len() {
+            let task = this.fetch_champions(index);
+            this.fetch_tasks.push(task);
+        }
+        let task = this.fetch(backend_url("ratings", &[("limit", "500"), ("computer", "true")]), Msg::Ratings);
+        this.fetch_tasks.push(task);
         this
     }
 
     fn update(&mut self, msg: Self::Message) -> ShouldRender {
         match msg {
-            Msg::FetchSuccess(data) => {
-                self.history = data;
-                self.fetch_task = None;
+            Msg::Total(index, total) => self.totals[index] = Some(total),
+            Msg::ComputerWins(page) => {
+                self.computer_wins.extend(page.histories);
+                self.computer_wins_next = page.next;
+            }
+            Msg::MoreComputerWins => {
+                let cursor = self.computer_wins_next.take();
+                let task = self.fetch_computer_wins(cursor);
+                self.fetch_tasks.push(task);
+            }
+            Msg::Champions(index, entries) => self.champions[index] = entries,
+            Msg::Ratings(ratings) => {
+                self.ratings = ratings;
+                let players: Vec<String> = self.ratings.iter()
+                    .filter(|rated| !rated.computer)
+                    .take(CHART_COLOURS.len())
+                    .map(|rated| rated.player.clone())
+                    .collect();
+                for player in players {
+                    let task = self.fetch(player_ratings_url(&player), Msg::PlayerRatings);
+                    self.fetch_tasks.push(task);
+                }
+            }
+            Msg::PlayerRatings(ratings) => {
+                // they arrive in any order, the chart lists them best first
+                self.rating_histories.push(ratings);
+                self.rating_histories.sort_by(|a, b| b.rating.partial_cmp(&a.rating).unwrap());
             }
             Msg::FetchFailed => log::info!("fetching history failed"),
             Msg::FetchDataError => log::info!("fetched data contains error"),
@@ -190,6 +342,7 @@ impl Component for ScoreBoard {
                     
                     { self.detail_won_by_computer_stat() }
                 </table>
+                { self.view_more_computer_wins() }
         
                 <br />
 
@@ -202,9 +355,12 @@ impl Component for ScoreBoard {
                         <th> {"Sl. No."} </th>
                         <th> {"Player Name"} </th>
                         <th> {"No. of Wins"} </th>
+                        <th> {"Games Played"} </th>
+                        <th> {"Win Rate"} </th>
+                        <th> {"Rating"} </th>
                     </tr>
                     
-                    { self.champion_games_stat(String::from("Connect-4")) }
+                    { self.champion_games_stat(0) }
                 </table>
 
                 <br />
@@ -218,9 +374,12 @@ impl Component for ScoreBoard {
                         <th> {"Sl. No."} </th>
                         <th> {"Player Name"} </th>
                         <th> {"No. of Wins"} </th>
+                        <th> {"Games Played"} </th>
+                        <th> {"Win Rate"} </th>
+                        <th> {"Rating"} </th>
                     </tr>
                     
-                    { self.champion_games_stat(String::from("TOOT-OTTO")) }
+                    { self.champion_games_stat(1) }
                 </table>
 
                 <br />
@@ -234,11 +393,34 @@ impl Component for ScoreBoard {
                         <th> {"Sl. No."} </th>
                         <th> {"Player Name"} </th>
                         <th> {"No. of Wins"} </th>
+                        <th> {"Games Played"} </th>
+                        <th> {"Win Rate"} </th>
+                        <th> {"Rating"} </th>
                     </tr>
                     
-                    { self.champion_games_stat(String::from("")) }
+                    { self.champion_games_stat(2) }
+                </table>
+
+                <br />
+
+                <div>
+                    <h4> {"Player Ratings"} </h4>
+                </div>
+
+                <table>
+                    <tr>
+                        <th> {"Rank"} </th>
+                        <th> {"Player Name"} </th>
+                        <th> {"Rating"} </th>
+                        <th> {"Games Rated"} </th>
+                    </tr>
+
+                    { self.rating_stat() }
                 </table>
 
+                <br />
+                { self.rating_chart() }
+
             </div>
         }
     }
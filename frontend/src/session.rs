@@ -0,0 +1,42 @@
+// The logged in account, kept in the browser's local storage so it lasts across visits
+//
+// Without one the player is a guest: they can still play under any free name, their games are
+// just not linked to an account.
+use anyhow::Error;
+use yew::format::Json;
+use yew::services::storage::{Area, StorageService};
+use crate::types::SessionInfo;
+
+const KEY: &str = "connect4.session";
+
+fn storage() -> Option<StorageService> {
+    StorageService::new(Area::Local).ok()
+}
+
+// None for a guest
+pub fn current() -> Option<SessionInfo> {
+    let Json(session) = storage()?.restore::<Json<Result<SessionInfo, Error>>>(KEY);
+    session.ok()
+}
+
+pub fn save(session: &SessionInfo) {
+    if let Some(mut storage) = storage() {
+        storage.store(KEY, Json(session));
+    }
+}
+
+pub fn clear() {
+    if let Some(mut storage) = storage() {
+        storage.remove(KEY);
+    }
+}
+
+// the name to fill in for the player, empty for a guest
+pub fn player_name() -> String {
+    current().map(|session| session.account.name).unwrap_or_default()
+}
+
+// the Authorization header of requests made for the account
+pub fn authorization() -> Option<String> {
+    current().map(|session| format!("Bearer {}", session.token))
+}
@@ -22,6 +22,10 @@ pub enum AppRoute {
     GameHistory,
     #[to = "/score-board"]
     ScoreBoard,
+    #[to = "/login"]
+    Login,
+    #[to = "/logout"]
+    Logout,
     #[to = "/page-not-found"]
     PageNotFound(Permissive<String>),
     #[to = "/!"]
@@ -2,10 +2,129 @@ use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct HistoryInfo {
+    #[serde(default)]
+    pub id: String, // given by the backend
     pub game_type: String,
     pub player1: String,
     pub player2: String,
     pub winner: String,
     pub difficulty: String,
     pub time_played: String,
+    #[serde(default)]
+    pub player1_hints: u32,
+    #[serde(default)]
+    pub player2_hints: u32,
+    // 0 or empty for games recorded before these were kept
+    #[serde(default)]
+    pub board_rows: u32,
+    #[serde(default)]
+    pub board_columns: u32,
+    #[serde(default)]
+    pub moves: Vec<String>, // in order, "3" in Connect 4 and "3T" in TOOT-OTTO (see model::notation)
+    #[serde(default)]
+    pub first_player: String,
+    #[serde(default)]
+    pub duration_seconds: u32,
+    #[serde(default)]
+    pub ai_seed: Option<u64>,
+    #[serde(default)]
+    pub ai_level: Option<f64>, // where an Adaptive computer started, see model::adaptive
+}
+
+// one page of GET /history
+#[derive(Serialize, Deserialize, Debug)]
+pub struct HistoryPage {
+    pub histories: Vec<HistoryInfo>,
+    pub total: u64, // games matching the filters, on all pages
+    pub next: Option<String>, // cursor for the next page, None on the last one
+}
+
+// one player of GET /leaderboard, from the best
+#[derive(Serialize, Deserialize, Debug)]
+pub struct LeaderboardEntry {
+    pub rank: usize,
+    pub player: String,
+    pub games: u64,
+    pub wins: u64,
+    pub losses: u64,
+    pub draws: u64,
+    pub win_rate: f64, // 0 to 1
+}
+
+// one player of GET /ratings, from the best rated
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RatedPlayer {
+    pub rank: usize,
+    pub player: String,
+    pub rating: f64,
+    pub deviation: f64, // the rating is within twice this 95% of the time
+    pub games: u32,
+    pub computer: bool, // a difficulty of the computer, "Computer (Hard)" and so on
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RatingPoint {
+    pub game_id: String,
+    pub date: String,
+    pub rating: f64,
+    pub deviation: f64,
+}
+
+// GET /ratings/<player>
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PlayerRatings {
+    pub player: String,
+    pub rating: f64,
+    pub deviation: f64,
+    pub games: u32,
+    pub history: Vec<RatingPoint>, // after each game, oldest first
+}
+
+// the body of POST /accounts and POST /login
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Credentials {
+    pub name: String,
+    pub password: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AccountInfo {
+    pub id: String,
+    pub name: String,
+    pub created: String,
+}
+
+// answer of POST /accounts and POST /login, kept while logged in (see session.rs)
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SessionInfo {
+    pub token: String, // sent as "Authorization: Bearer <token>"
+    pub expires: String,
+    pub account: AccountInfo,
+}
+
+// GET /history with the given filters, sort and paging (see the backend's HistoryParams)
+pub fn history_url(params: &[(&str, &str)]) -> String {
+    backend_url("history", params)
+}
+
+pub fn backend_url(path: &str, params: &[(&str, &str)]) -> String {
+    let query: Vec<String> = params.iter()
+        .map(|(name, value)| format!("{}={}", name, encode_component(value)))
+        .collect();
+    format!("http://127.0.0.1:8000/{}?{}", path, query.join("&"))
+}
+
+// GET /ratings/<player>
+pub fn player_ratings_url(player: &str) -> String {
+    format!("http://127.0.0.1:8000/ratings/{}", encode_component(player))
+}
+
+// player names can have any character, everything but letters, digits and -_.~ is escaped
+fn encode_component(text: &str) -> String {
+    text.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (byte as char).to_string(),
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
 }
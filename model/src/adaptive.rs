@@ -0,0 +1,151 @@
+// Adaptive difficulty, aiming for the player to win about half of their games
+//
+// The strength is a level between 0 (Easy) and 3 (Insane), a level in between plays at a
+// strength between the two difficulties (see Difficulty::strength()). The starting level
+// comes from the player's recent games against the computer, and every move the AI looks at
+// the position with a Hard search first: when it is far ahead it sometimes plays a slightly
+// worse move, when it is behind it plays a level up.
+use rand::prelude::*;
+use rand::rngs::StdRng;
+use crate::ai::{Connect4AI, Difficulty, MoveEvaluation, MoveOutcome, SearchAI, TootOttoAI};
+use crate::disc::DiscType;
+use crate::eval_params::EvalParams;
+use crate::game::{BoardGame, GameType};
+
+// Medium, for players without any games yet
+pub const DEFAULT_LEVEL: f64 = 1.0;
+const MAX_LEVEL: f64 = 3.0;
+// only the latest games count, players get better
+const RECENT_GAMES: usize = 10;
+// how far a win or a loss moves the estimate of the player's level
+const LEVEL_STEP: f64 = 0.5;
+// how often a move is given away when far ahead
+const INACCURACY_CHANCE: f64 = 0.5;
+
+// the level a game was played at, from the difficulty and the AI level stored in the game
+// history, None for games without a computer player
+pub fn played_level(difficulty: &str, level: Option<f64>) -> Option<f64> {
+    match difficulty.parse::<Difficulty>().ok()? {
+        Difficulty::Easy => Some(0.0),
+        Difficulty::Medium => Some(1.0),
+        Difficulty::Hard => Some(2.0),
+        Difficulty::Insane => Some(3.0),
+        Difficulty::Adaptive => level,
+    }
+}
+
+// level to start the next game at, from the player's games against the computer oldest first:
+// the level each was played at and how it went for the player
+pub fn starting_level(games: &[(f64, MoveOutcome)]) -> f64 {
+    let recent = &games[games.len().saturating_sub(RECENT_GAMES)..];
+    if recent.is_empty() {
+        return DEFAULT_LEVEL;
+    }
+    // a win says the player is a bit stronger than the level and a loss a bit weaker, the
+    // average is where they'd win as often as they lose
+    let total: f64 = recent.iter()
+        .map(|(level, outcome)| match outcome {
+            MoveOutcome::Win => level + LEVEL_STEP,
+            MoveOutcome::Draw => *level,
+            MoveOutcome::Loss => level - LEVEL_STEP,
+        })
+        .sum();
+    (total / recent.len() as f64).max(0.0).min(MAX_LEVEL)
+}
+
+enum Engine {
+    Connect4(Connect4AI),
+    TootOtto(TootOttoAI),
+}
+
+impl Engine {
+    fn search_ai(&mut self) -> &mut dyn SearchAI {
+        match self {
+            Engine::Connect4(ai) => ai,
+            Engine::TootOtto(ai) => ai,
+        }
+    }
+}
+
+// Easy to Insane on the strength scale
+fn level_strength(level: f64) -> u8 {
+    let strengths: Vec<f64> = Difficulty::fixed().iter().map(|difficulty| difficulty.strength() as f64).collect();
+    let lower = (level.floor() as usize).min(strengths.len() - 1);
+    let upper = (lower + 1).min(strengths.len() - 1);
+    let fraction = level - lower as f64;
+    (strengths[lower] + (strengths[upper] - strengths[lower]) * fraction).round() as u8
+}
+
+pub struct AdaptiveAI {
+    level: f64,
+    seed: u64,
+    rng: StdRng,
+    engine: Engine,
+    ahead_margin: i64, // a Hard search score this far from 0 counts as far ahead or behind
+}
+
+impl AdaptiveAI {
+    // the same seed, level and moves from the opponent always give the same game
+    pub fn new(game_type: &GameType, board_rows: usize, board_columns: usize, level: f64, seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let engine = match game_type {
+            GameType::Connect4 => Engine::Connect4(Connect4AI::new(board_rows, board_columns, Difficulty::Hard, rng.gen())),
+            GameType::TOOTandOTTO => Engine::TootOtto(TootOttoAI::new(board_rows, board_columns, Difficulty::Hard, rng.gen())),
+        };
+        // about two open lines of 3
+        let ahead_margin = 2 * EvalParams::tuned(game_type).chain_weights[3];
+        Self {
+            level: level.max(0.0).min(MAX_LEVEL),
+            seed,
+            rng,
+            engine,
+            ahead_margin,
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn level(&self) -> f64 {
+        self.level
+    }
+
+    fn analyze(&mut self, game: &BoardGame) -> Vec<MoveEvaluation> {
+        let ai = self.engine.search_ai();
+        ai.set_strength(Difficulty::Hard.strength());
+        ai.analyze(game)
+    }
+
+    fn engine_move(&mut self, level: f64, game: &BoardGame) -> (usize, DiscType) {
+        self.engine.search_ai().set_strength(level_strength(level));
+        match &mut self.engine {
+            Engine::Connect4(ai) => (ai.find_best_move(game.clone()), game.get_current_disc_type()),
+            Engine::TootOtto(ai) => ai.find_best_move(game.clone()),
+        }
+    }
+
+    pub fn find_best_move(&mut self, game: BoardGame) -> (usize, DiscType) {
+        let evaluations = self.analyze(&game);
+        if let Some(best) = evaluations.first() {
+            let ahead = best.outcome == Some(MoveOutcome::Win) || best.score >= self.ahead_margin;
+            let behind = best.outcome == Some(MoveOutcome::Loss) || best.score <= -self.ahead_margin;
+            if behind {
+                return self.engine_move((self.level + 1.0).min(MAX_LEVEL), &game);
+            }
+            // taking a win right away is the one thing anyone would do
+            let wins_now = best.outcome == Some(MoveOutcome::Win) && best.principal_variation.len() == 1;
+            if ahead && !wins_now && self.rng.gen::<f64>() < INACCURACY_CHANCE {
+                // the next best move that doesn't lose, it still looks like a reasonable move
+                let inaccuracy = evaluations.iter()
+                    .skip(1)
+                    .find(|evaluation| evaluation.outcome != Some(MoveOutcome::Loss));
+                if let Some(evaluation) = inaccuracy {
+                    return (evaluation.column, evaluation.disc_type);
+                }
+            }
+        }
+
+        self.engine_move(self.level, &game)
+    }
+}
@@ -1,9 +1,19 @@
 #![allow(dead_code)]
 
+use std::cell::RefCell;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::str::FromStr;
 use rand::prelude::*;
-use crate::game::{BoardGame, GameEvent};
+use rand::rngs::StdRng;
+use crate::game::{BoardGame, GameType};
 use crate::board::{Board};
 use crate::disc::{DiscType};
+use crate::eval_params::EvalParams;
+use crate::parallel;
+use crate::solution_table;
+use crate::solver::{Solver, PLAYING_TABLE_SIZE};
+use crate::trace::SearchTrace;
 
 #[derive(PartialEq, Copy, Clone, Debug)]
 pub enum Difficulty {
@@ -11,6 +21,7 @@ pub enum Difficulty {
     Medium,
     Hard,
     Insane,
+    Adaptive, // see adaptive.rs, Connect4AI and TootOttoAI on their own play it like Hard
 }
 
 // needed to <Select> component display
@@ -20,19 +31,129 @@ impl ToString for Difficulty {
             Difficulty::Easy => String::from("Easy"),
             Difficulty::Medium => String::from("Medium"),
             Difficulty::Hard => String::from("Hard"),
-            Difficulty::Insane => String::from("Insane")
+            Difficulty::Insane => String::from("Insane"),
+            Difficulty::Adaptive => String::from("Adaptive"),
         }
     }
 }
 
+impl FromStr for Difficulty {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Difficulty::to_vec().into_iter()
+            .find(|difficulty| difficulty.to_string().eq_ignore_ascii_case(s))
+            .ok_or(format!("unknown difficulty: {}", s))
+    }
+}
+
 impl Difficulty {
     pub fn to_vec() -> Vec<Difficulty> {
+        vec![Difficulty::Easy, Difficulty::Medium, Difficulty::Hard, Difficulty::Insane, Difficulty::Adaptive]
+    }
+
+    // the levels that always play the same way
+    pub fn fixed() -> Vec<Difficulty> {
         vec![Difficulty::Easy, Difficulty::Medium, Difficulty::Hard, Difficulty::Insane]
     }
+
+    // where the level sits on the continuous strength scale, see set_strength()
+    pub fn strength(&self) -> u8 {
+        match self {
+            Difficulty::Easy => 10,
+            Difficulty::Medium => 40,
+            Difficulty::Hard | Difficulty::Adaptive => 80,
+            Difficulty::Insane => MAX_STRENGTH,
+        }
+    }
+}
+
+// Strength goes from 0 to 100. Every strength searches as deep as the others, then picks among
+// the moves close to the best with a softmax, the weaker the more randomly. Only MAX_STRENGTH
+// always plays the best move it finds, perfectly on the boards with a solution table. Wins on
+// the spot are always taken and moves the search sees losing are avoided, so even 0 doesn't
+// play absurd moves, it just misses more of the deeper tactics.
+pub const MAX_STRENGTH: u8 = 100;
+// moves scoring more than this many temperatures below the best are never picked
+const NEAR_BEST: f64 = 3.0;
+
+// how deep max_state/min_state go, the search looks at SEARCH_DEPTH + 1 moves
+// depth is a bit lower as the AI is slow
+const SEARCH_DEPTH: i64 = 3;
+
+// how far apart in score two moves are that get picked about e (2.7) times as often as each
+// other, from a three in a row at strength 0 down to 0 at MAX_STRENGTH. Measured in lines of 3,
+// the unit of the evaluation.
+fn temperature(strength: u8, params: &EvalParams) -> f64 {
+    params.chain_weights[3] as f64 * (MAX_STRENGTH - strength) as f64 / MAX_STRENGTH as f64
+}
+
+// one of the evaluations (best first) for a strength below MAX_STRENGTH
+fn choose_near_best<'a>(evaluations: &'a [MoveEvaluation], temperature: f64, rng: &mut StdRng) -> Option<&'a MoveEvaluation> {
+    let best = evaluations.first()?;
+    if best.outcome == Some(MoveOutcome::Win) && best.principal_variation.len() == 1 {
+        return Some(best);
+    }
+    let candidates: Vec<&MoveEvaluation> = evaluations.iter()
+        .filter(|evaluation| evaluation.outcome != Some(MoveOutcome::Loss) || best.outcome == Some(MoveOutcome::Loss))
+        .filter(|evaluation| ((best.score - evaluation.score) as f64) <= NEAR_BEST * temperature)
+        .collect();
+    let weights: Vec<f64> = candidates.iter()
+        .map(|evaluation| (-((best.score - evaluation.score) as f64) / temperature).exp())
+        .collect();
+    let mut pick = rng.gen::<f64>() * weights.iter().sum::<f64>();
+    for (evaluation, weight) in candidates.iter().zip(&weights) {
+        if pick < *weight {
+            return Some(evaluation);
+        }
+        pick -= weight;
+    }
+    candidates.last().copied()
+}
+
+// result of a move for the player making it, only set when the search proved it
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum MoveOutcome {
+    Win,
+    Draw,
+    Loss,
+}
+
+#[derive(Clone, Debug)]
+pub struct MoveEvaluation {
+    pub column: usize,
+    pub disc_type: DiscType,
+    pub score: i64, // from the point of view of the player to move, higher is better
+    pub depth: usize, // moves the search looked ahead, this one included, see principal_variation for the line
+    pub principal_variation: Vec<(usize, DiscType)>, // starts with this move
+    pub outcome: Option<MoveOutcome>,
 }
 
 const ERR_CODE: i64 = 421;
-const REWARD: i64 = 999999;
+const INFINITY: i64 = 100000000007;
+
+// any score this far from zero comes from a win or a loss, the chain values are much smaller.
+// Anything else is a draw only when every line of the search reached the end of the game.
+fn score_outcome(score: i64, searched_to_the_end: bool, params: &EvalParams) -> Option<MoveOutcome> {
+    if score > params.reward / 2 {
+        Some(MoveOutcome::Win)
+    } else if score < -params.reward / 2 {
+        Some(MoveOutcome::Loss)
+    } else if searched_to_the_end {
+        Some(MoveOutcome::Draw)
+    } else {
+        None
+    }
+}
+
+fn is_state_full(state: &[Vec<i64>]) -> bool {
+    state[0].iter().all(|&cell| cell != 0)
+}
+
+// a search `depth` moves deep from `state` fills the board on every line
+fn fills_board(state: &[Vec<i64>], depth: i64) -> bool {
+    state.iter().flatten().filter(|&&cell| cell == 0).count() as i64 <= depth
+}
 
 fn fill_map(
         new_state: &Vec<Vec<i64>>, 
@@ -65,14 +186,31 @@ fn fill_map(
     return temp_map;
 }
 
-fn get_random_index(len: usize) -> usize {
-    let mut rng = rand::thread_rng();
+// a fresh seed for a new AI, record it to be able to replay the game later
+pub fn random_seed() -> u64 {
+    thread_rng().gen()
+}
+
+// goes through f64 instead of gen_range::<usize>() so the same seed
+// picks the same index on both wasm32 and 64-bit native builds
+pub(crate) fn get_random_index(len: usize, rng: &mut StdRng) -> usize {
     let random: f64 = rng.gen();
 
     return (random * len as f64).floor() as usize;
 }
 
-pub fn check_state(state: &Vec<Vec<i64>>, board_rows: usize, board_columns: usize, is_sign: bool) -> (i64, i64) {
+// the search hooks for a trace, they do nothing when the AI isn't recording one
+fn trace_enter(trace: &RefCell<Option<SearchTrace>>, game_move: (usize, DiscType), maximizing: bool, alpha: i64, beta: i64) -> Option<usize> {
+    trace.borrow_mut().as_mut().and_then(|trace| trace.enter(game_move, maximizing, alpha, beta))
+}
+
+fn trace_exit(trace: &RefCell<Option<SearchTrace>>, node: Option<usize>, score: i64, cutoff: bool) {
+    if let Some(trace) = trace.borrow_mut().as_mut() {
+        trace.exit(node, score, cutoff);
+    }
+}
+
+pub fn check_state(state: &Vec<Vec<i64>>, board_rows: usize, board_columns: usize, is_sign: bool, params: &EvalParams) -> (i64, i64) {
     let mut win_val = 0;
     let mut chain_val = 0;
     let (mut temp_r, mut temp_b, mut temp_br, mut temp_tr);
@@ -103,10 +241,10 @@ pub fn check_state(state: &Vec<Vec<i64>>, board_rows: usize, board_columns: usiz
                     temp_tr += sign * state[i - k][j + k];
                 }
             }
-            chain_val += temp_r * temp_r * temp_r;
-            chain_val += temp_b * temp_b * temp_b;
-            chain_val += temp_br * temp_br * temp_br;
-            chain_val += temp_tr * temp_tr * temp_tr;
+            chain_val += params.chain_value(temp_r);
+            chain_val += params.chain_value(temp_b);
+            chain_val += params.chain_value(temp_br);
+            chain_val += params.chain_value(temp_tr);
 
             if temp_r.abs() == 4 {
                 win_val = temp_r;
@@ -123,30 +261,192 @@ pub fn check_state(state: &Vec<Vec<i64>>, board_rows: usize, board_columns: usiz
     return (win_val, chain_val);
 }
 
+// What Connect4AI and TootOttoAI have in common: the level, the random generator and the
+// search options. SearchAI sets them.
+pub struct Search {
+    strength: u8,
+    seed: u64,
+    rng: RefCell<StdRng>,
+    params: EvalParams,
+    threads: usize,
+    depth: Option<i64>, // overrides SEARCH_DEPTH, see set_depth()
+    stop: Option<Arc<AtomicBool>>, // see set_stop()
+    solver: Option<Solver>, // Insane on small boards, see perfect_move()
+    trace: RefCell<Option<SearchTrace>>, // see start_trace()
+}
+
+impl Search {
+    fn new(difficulty: Difficulty, seed: u64, params: EvalParams) -> Self {
+        Self {
+            strength: difficulty.strength(),
+            seed,
+            rng: RefCell::new(StdRng::seed_from_u64(seed)),
+            params,
+            threads: 1,
+            depth: None,
+            stop: None,
+            solver: None,
+            trace: RefCell::new(None),
+        }
+    }
+
+    fn max_depth(&self) -> i64 {
+        self.depth.unwrap_or(SEARCH_DEPTH)
+    }
+
+    fn stopped(&self) -> bool {
+        match &self.stop {
+            Some(stop) => stop.load(Ordering::Relaxed),
+            None => false,
+        }
+    }
+
+    // every search starts a new tree
+    fn clear_trace(&mut self) {
+        if let Some(trace) = self.trace.get_mut() {
+            *trace = SearchTrace::new(trace.max_depth, trace.max_nodes);
+        }
+    }
+
+    fn mark_trace(&mut self, chosen: (usize, DiscType)) {
+        if let Some(trace) = self.trace.get_mut() {
+            trace.mark_principal_variation(chosen);
+        }
+    }
+
+    // Insane plays perfectly on the board sizes with a solution table, picking one of the best
+    // moves at random. The solver is only made the first time, with a small transposition table.
+    fn perfect_move(&mut self, game: &BoardGame) -> Option<(usize, DiscType)> {
+        let rows = game.game_board.board_rows;
+        let columns = game.game_board.board_columns;
+        if !solution_table::has_table(&game.game_type, rows, columns) {
+            return None;
+        }
+        if self.solver.is_none() {
+            self.solver = Solver::with_table(&game.game_type, rows, columns, PLAYING_TABLE_SIZE);
+        }
+        let moves = self.solver.as_mut()?.best_moves(game);
+        if moves.is_empty() {
+            return None;
+        }
+        Some(moves[get_random_index(moves.len(), self.rng.get_mut())])
+    }
+
+    // a copy with its own random generator, to search a root move on another thread
+    fn worker(&self, seed: u64) -> Self {
+        Self {
+            strength: self.strength,
+            seed,
+            rng: RefCell::new(StdRng::seed_from_u64(seed)),
+            params: self.params,
+            threads: 1,
+            depth: self.depth,
+            stop: self.stop.clone(),
+            solver: None,
+            trace: RefCell::new(None),
+        }
+    }
+
+    // a worker AI for every move, made by `make` around a worker search. The seeds come from
+    // this generator, so the game doesn't depend on the thread count.
+    fn workers<T, A>(&self, moves: Vec<T>, make: impl Fn(Search) -> A) -> Vec<(T, A)> {
+        moves.into_iter()
+            .map(|game_move| {
+                let seed = self.rng.borrow_mut().gen();
+                (game_move, make(self.worker(seed)))
+            })
+            .collect()
+    }
+}
+
+// The settings and the analysis of Connect4AI and TootOttoAI
+pub trait SearchAI {
+    fn search(&self) -> &Search;
+
+    fn search_mut(&mut self) -> &mut Search;
+
+    // evaluate every legal move for the player to move, best move first
+    fn analyze(&mut self, game: &BoardGame) -> Vec<MoveEvaluation>;
+
+    fn seed(&self) -> u64 {
+        self.search().seed
+    }
+
+    fn set_eval_params(&mut self, params: EvalParams) {
+        self.search_mut().params = params;
+    }
+
+    // any strength from 0 to MAX_STRENGTH, instead of the difficulty's
+    fn set_strength(&mut self, strength: u8) {
+        self.search_mut().strength = std::cmp::min(strength, MAX_STRENGTH);
+    }
+
+    // more than 1 thread splits Hard and Insane searches by root move, which only runs
+    // in parallel with the `parallel` feature. Any thread count above 1 plays the same
+    // moves for a seed, but not the same ones as a single thread.
+    fn set_threads(&mut self, threads: usize) {
+        self.search_mut().threads = std::cmp::max(threads, 1);
+    }
+
+    // search exactly `depth` moves ahead (at least 1), None to go back to the usual depth
+    fn set_depth(&mut self, depth: Option<usize>) {
+        self.search_mut().depth = depth.map(|depth| std::cmp::max(depth, 1) as i64 - 1);
+    }
+
+    // once `stop` is set the search winds down quickly, and what it returns is meaningless
+    fn set_stop(&mut self, stop: Arc<AtomicBool>) {
+        self.search_mut().stop = Some(stop);
+    }
+
+    // records the tree of the next searches (find_best_move() or analyze()) until take_trace(),
+    // down to `max_depth` moves and up to `max_nodes` nodes. Only single threaded searches are
+    // recorded, see model::trace.
+    fn start_trace(&mut self, max_depth: usize, max_nodes: usize) {
+        *self.search_mut().trace.get_mut() = Some(SearchTrace::new(max_depth, max_nodes));
+    }
+
+    fn take_trace(&mut self) -> Option<SearchTrace> {
+        self.search_mut().trace.get_mut().take()
+    }
+}
+
 pub struct Connect4AI {
     board_rows: usize,
     board_columns: usize,
-    difficulty: Difficulty,
-    score_board: Vec<Vec<i64>>
+    score_board: Vec<Vec<i64>>,
+    search: Search,
 }
 
 impl Connect4AI {
-    pub fn new(board_rows: usize, board_columns: usize, difficulty: Difficulty) -> Self {
+    // the same seed and the same moves from the opponent always give the same game
+    pub fn new(board_rows: usize, board_columns: usize, difficulty: Difficulty, seed: u64) -> Self {
         let map: Vec<Vec<i64>> = vec![vec![0; board_columns]; board_rows];
         Self {
             board_rows,
             board_columns,
-            difficulty,
             score_board: map,
+            search: Search::new(difficulty, seed, EvalParams::tuned(&GameType::Connect4)),
         }
     }
 
+    // a copy around a worker search, see Search::workers()
+    fn worker(&self, search: Search) -> Self {
+        Self {
+            board_rows: self.board_rows,
+            board_columns: self.board_columns,
+            score_board: self.score_board.clone(),
+            search,
+        }
+    }
+
+
     fn random_gen(&self, game_board: Board) -> usize {
         let valid_columns = game_board.get_valid_columns();
-        match valid_columns.choose(&mut thread_rng()) {
-            Some(column) => return *column,
-            None => return 0 // not gonna be used
+        if valid_columns.is_empty() {
+            return 0; // not gonna be used
         }
+        let index = get_random_index(valid_columns.len(), &mut self.search.rng.borrow_mut());
+        valid_columns[index]
     }
 
     fn convert_board(&mut self, board: Board) {
@@ -163,64 +463,101 @@ impl Connect4AI {
         }
     }
 
+    // the AI plays whoever's turn it is: Red counts as 1 and Yellow as -1 in the score board
+    fn ai_move_value(game: &BoardGame) -> i64 {
+        if game.get_current_disc_type() == DiscType::Red { 1 } else { -1 }
+    }
+
+    fn disc_type_of(move_value: i64) -> DiscType {
+        if move_value == 1 { DiscType::Red } else { DiscType::Yellow }
+    }
+
     pub fn find_best_move(&mut self, game: BoardGame) -> usize {
         self.convert_board(game.game_board.clone());
-        match self.difficulty {
-            Difficulty::Easy => {
-                // pure random
-                return self.random_gen(game.game_board.clone())
-            },
-            Difficulty::Medium => {
-                // find if there's a winning move for AI
-                let winning_move = self.find_winning_move(game.game_board.clone());
-                if winning_move >= 0 {
-                    return winning_move as usize
-                } else { // find if there's a blocking move for player
-                    let blocking_move = self.find_blocking_move(game.game_board.clone());
-                    if blocking_move >= 0 {
-                        return blocking_move as usize
-                    } else {
-                        return self.random_gen(game.game_board.clone())
-                    }
-                }
-            }
-            _ => { // Hard or Insane, use minmax algorithm with alpha-beta pruning
-                let choice_val = self.max_state(-1, &self.score_board, 0, -100000000007, 100000000007);
-                let choice = choice_val.1;
-                if choice < 0 || choice as usize > self.board_columns || game.game_board.is_column_full(choice as usize) {
-                    return self.random_gen(game.game_board.clone());
-                }
-                return choice as usize;
+        self.search.clear_trace();
+        let ai_move_value = Self::ai_move_value(&game);
+        if self.search.strength == MAX_STRENGTH {
+            if let Some((column, _)) = self.search.perfect_move(&game) {
+                return column;
             }
         }
-    }
-
-    fn find_winning_move(&self, game_board: Board) -> isize {
-        // find if there's a move that causes AI to win
-        let valid_columns = game_board.get_valid_columns();
-        for col in valid_columns.clone() {
-            let mut clone_board = game_board.clone();
-            clone_board.place_disc(col, DiscType::Yellow);
-            if clone_board.is_connect4(DiscType::Yellow) {
-                return col as isize
+        let temperature = temperature(self.search.strength, &self.search.params);
+        if temperature > 0.0 {
+            let evaluations = self.analyze(&game);
+            let choice = choose_near_best(&evaluations, temperature, &mut self.search.rng.borrow_mut()).map(|evaluation| evaluation.column);
+            if let Some(column) = choice {
+                self.search.mark_trace((column, Self::disc_type_of(ai_move_value)));
             }
+            return choice.unwrap_or_else(|| self.random_gen(game.game_board.clone()));
         }
 
-        -1
+        // full strength, use minmax algorithm with alpha-beta pruning
+        let choice_val = if self.search.threads > 1 {
+            self.split_root(ai_move_value)
+        } else {
+            self.max_state(ai_move_value, &self.score_board, 0, -INFINITY, INFINITY)
+        };
+        let choice = choice_val.1;
+        if choice < 0 || choice as usize > self.board_columns || game.game_board.is_column_full(choice as usize) {
+            return self.random_gen(game.game_board.clone());
+        }
+        self.search.mark_trace((choice as usize, Self::disc_type_of(ai_move_value)));
+        return choice as usize;
     }
 
-    fn find_blocking_move(&self, game_board: Board) -> isize {
-        // find if there's a move that can prevent player to win
-        let valid_columns = game_board.get_valid_columns();
-        for col in valid_columns {
-            let mut clone_board = game_board.clone();
-            clone_board.place_disc(col, DiscType::Red);
-            if clone_board.is_connect4(DiscType::Red) {
-                return col as isize
-            }
+    fn evaluate_move(&self, column: usize, ai_move_value: i64) -> MoveEvaluation {
+        let max_depth = self.search.max_depth();
+        let mut state = fill_map(&self.score_board, column, ai_move_value, self.board_rows, self.board_columns);
+        let node = trace_enter(&self.search.trace, (column, Self::disc_type_of(ai_move_value)), true, -INFINITY, INFINITY);
+        let (score, _) = self.value(ai_move_value, &state, 0, -INFINITY, INFINITY);
+        trace_exit(&self.search.trace, node, score, false);
+        let searched_to_the_end = fills_board(&state, max_depth);
+
+        // follow the best replies to get the line the score comes from, these searches
+        // are already in the trace
+        let trace = self.search.trace.borrow_mut().take();
+        let mut principal_variation = vec![(column, Self::disc_type_of(ai_move_value))];
+        let mut depth = 1;
+        while depth <= max_depth
+            && check_state(&state, self.board_rows, self.board_columns, false, &self.search.params).0.abs() != 4
+            && !is_state_full(&state) {
+            let move_value = if depth % 2 == 1 { -ai_move_value } else { ai_move_value };
+            let (_, reply) = if depth % 2 == 1 {
+                self.min_state(ai_move_value, &state, depth, -INFINITY, INFINITY)
+            } else {
+                self.max_state(ai_move_value, &state, depth, -INFINITY, INFINITY)
+            };
+            state = fill_map(&state, reply as usize, move_value, self.board_rows, self.board_columns);
+            principal_variation.push((reply as usize, Self::disc_type_of(move_value)));
+            depth += 1;
+        }
+        *self.search.trace.borrow_mut() = trace;
+
+        MoveEvaluation {
+            column,
+            disc_type: Self::disc_type_of(ai_move_value),
+            score,
+            depth: max_depth as usize + 1,
+            principal_variation,
+            outcome: score_outcome(score, searched_to_the_end, &self.search.params),
         }
+    }
 
-        -1
+    // root splitting: every root move gets a full window search of its own, the same
+    // values max_state() finds at the root
+    fn split_root(&self, ai_move_value: i64) -> (i64, i64) {
+        let columns = (0..self.board_columns).filter(|&column| self.score_board[0][column] == 0).collect();
+        let scores = parallel::map(self.search.workers(columns, |search| self.worker(search)), self.search.threads, move |(column, worker)| {
+            let state = fill_map(&worker.score_board, column, ai_move_value, worker.board_rows, worker.board_columns);
+            (column as i64, worker.value(ai_move_value, &state, 0, -INFINITY, INFINITY).0)
+        });
+
+        let best = match scores.iter().map(|(_, score)| *score).max() {
+            Some(best) => best,
+            None => return (0, -1), // the board is full, it's a draw
+        };
+        let move_queue: Vec<i64> = scores.iter().filter(|(_, score)| *score == best).map(|(column, _)| *column).collect();
+        (best, self.choose(&move_queue))
     }
 
     fn choose(&self, choice: &Vec<i64>) -> i64 {
@@ -228,7 +565,7 @@ impl Connect4AI {
             return 0;
         }
 
-        let index = get_random_index(choice.len());
+        let index = get_random_index(choice.len(), &mut self.search.rng.borrow_mut());
         return choice[index];
     }
 
@@ -240,13 +577,9 @@ impl Connect4AI {
         alpha: i64,
         beta: i64,
     ) -> (i64, i64) {
-        let val = check_state(state, self.board_rows, self.board_columns, false);
-        let max_depth = match self.difficulty {
-            Difficulty::Hard => 2,
-            Difficulty::Insane => 3,
-            _ => 1,
-        };
-        if depth >= max_depth { // if slow (or memory consumption is high), lower the value
+        let val = check_state(state, self.board_rows, self.board_columns, false, &self.search.params);
+        let max_depth = self.search.max_depth();
+        if depth >= max_depth || self.search.stopped() { // if slow (or memory consumption is high), lower the value
             // if win, value = +inf
             let win_val = val.0;
             let chain_val = val.1 * ai_move_value;
@@ -254,11 +587,11 @@ impl Connect4AI {
 
             // If it lead to winning, then do it
             if win_val == 4 * ai_move_value { // AI win, AI wants to win of course
-                ret_value = REWARD;
+                ret_value = self.search.params.reward;
             } else if win_val == 4 * ai_move_value * -1 { // AI lose, AI hates losing
-                ret_value = REWARD * -1;
+                ret_value = self.search.params.reward * -1;
             }
-            ret_value -= depth * depth;
+            ret_value -= self.search.params.depth_penalty * depth * depth;
 
             return (ret_value, -1);
         }
@@ -266,10 +599,10 @@ impl Connect4AI {
         let win = val.0;
         // if already won, then return the value right away
         if win == 4 * ai_move_value { // AI win, AI wants to win of course
-            return (REWARD - depth * depth, -1);
+            return (self.search.params.reward - self.search.params.depth_penalty * depth * depth, -1);
         }
         if win == 4 * ai_move_value * -1 { // AI lose, AI hates losing
-            return (REWARD * -1 - depth * depth, -1);
+            return (self.search.params.reward * -1 - self.search.params.depth_penalty * depth * depth, -1);
         }
 
         if depth % 2 == 0 {
@@ -286,13 +619,14 @@ impl Connect4AI {
         mut alpha: i64,
         beta: i64,
     ) -> (i64, i64) {
-        let mut v = -100000000007;
+        let mut v = -INFINITY;
         let move_val: i64;
         let mut move_queue = Vec::new();
 
         for j in 0..self.board_columns {
             let temp_state = fill_map(state, j, ai_move_value, self.board_rows, self.board_columns);
             if temp_state[0][0] != ERR_CODE {
+                let node = trace_enter(&self.search.trace, (j, Self::disc_type_of(ai_move_value)), true, alpha, beta);
                 let temp_val = self.value(ai_move_value, &temp_state, depth, alpha, beta);
                 if temp_val.0 > v {
                     v = temp_val.0;
@@ -303,6 +637,7 @@ impl Connect4AI {
                 }
 
                 // alpha-beta pruning
+                trace_exit(&self.search.trace, node, temp_val.0, v > beta);
                 if v > beta {
                     move_val = self.choose(&move_queue);
                     return (v, move_val);
@@ -311,6 +646,9 @@ impl Connect4AI {
             }
         }
 
+        if move_queue.is_empty() { // the board is full, it's a draw
+            return (0, -1);
+        }
         move_val = self.choose(&move_queue);
         return (v, move_val);
     }
@@ -323,13 +661,14 @@ impl Connect4AI {
         alpha: i64, 
         mut beta: i64
     ) -> (i64, i64) {
-        let mut v = 100000000007;
+        let mut v = INFINITY;
         let move_val: i64;
         let mut move_queue = Vec::new();
 
         for j in 0..self.board_columns {
             let temp_state = fill_map(state, j, ai_move_value * -1, self.board_rows, self.board_columns);
             if temp_state[0][0] != ERR_CODE {
+                let node = trace_enter(&self.search.trace, (j, Self::disc_type_of(-ai_move_value)), false, alpha, beta);
                 let temp_val = self.value(ai_move_value, &temp_state, depth, alpha, beta);
                 if temp_val.0 < v {
                     v = temp_val.0;
@@ -340,6 +679,7 @@ impl Connect4AI {
                 }
 
                 // alpha-beta pruning
+                trace_exit(&self.search.trace, node, temp_val.0, v < alpha);
                 if v < alpha {
                     move_val = self.choose(&move_queue);
                     return (v, move_val);
@@ -348,55 +688,121 @@ impl Connect4AI {
             }
         }
 
+        if move_queue.is_empty() { // the board is full, it's a draw
+            return (0, -1);
+        }
         move_val = self.choose(&move_queue);
         return (v, move_val);
     }
 }
 
+impl SearchAI for Connect4AI {
+    fn search(&self) -> &Search {
+        &self.search
+    }
+
+    fn search_mut(&mut self) -> &mut Search {
+        &mut self.search
+    }
+
+    fn analyze(&mut self, game: &BoardGame) -> Vec<MoveEvaluation> {
+        self.convert_board(game.game_board.clone());
+        self.search.clear_trace();
+        // the search draws from a copy of the random generator, so analysing a position
+        // doesn't change the moves the seed plays
+        let rng = self.search.rng.borrow().clone();
+        let ai_move_value = Self::ai_move_value(game);
+        let columns = game.game_board.get_valid_columns();
+
+        let mut evaluations: Vec<MoveEvaluation> = if self.search.threads > 1 {
+            parallel::map(self.search.workers(columns, |search| self.worker(search)), self.search.threads, move |(column, worker)| worker.evaluate_move(column, ai_move_value))
+        } else {
+            columns.into_iter().map(|column| self.evaluate_move(column, ai_move_value)).collect()
+        };
+
+        *self.search.rng.get_mut() = rng;
+        evaluations.sort_by_key(|evaluation| std::cmp::Reverse(evaluation.score));
+        if let Some(best) = evaluations.first() {
+            self.search.mark_trace((best.column, best.disc_type));
+        }
+        evaluations
+    }
+}
+
 pub struct TootOttoAI {
     board_rows: usize,
     board_columns: usize,
-    difficulty: Difficulty,
-    score_board: Vec<Vec<i64>>
+    score_board: Vec<Vec<i64>>,
+    perspective: i64, // 1 when the AI spells OTTO (player 2), -1 when it spells TOOT (player 1)
+    search: Search,
 }
 
 impl TootOttoAI {
-    pub fn new(board_rows: usize, board_columns: usize, difficulty: Difficulty) -> Self {
+    // the same seed and the same moves from the opponent always give the same game
+    pub fn new(board_rows: usize, board_columns: usize, difficulty: Difficulty, seed: u64) -> Self {
         let map: Vec<Vec<i64>> = vec![vec![0; board_columns]; board_rows];
 
         Self {
             board_rows,
             board_columns,
-            difficulty,
             score_board: map,
+            perspective: 1,
+            search: Search::new(difficulty, seed, EvalParams::tuned(&GameType::TOOTandOTTO)),
+        }
+    }
+
+    // a copy around a worker search, see Search::workers()
+    fn worker(&self, search: Search) -> Self {
+        Self {
+            board_rows: self.board_rows,
+            board_columns: self.board_columns,
+            score_board: self.score_board.clone(),
+            perspective: self.perspective,
+            search,
+        }
+    }
+
+
+    // every (column, letter) pair that fits in the score board
+    fn root_moves(&self) -> Vec<(usize, char)> {
+        let mut moves = Vec::new();
+        for letter in &['T', 'O'] {
+            for column in 0..self.board_columns {
+                if self.score_board[0][column] == 0 {
+                    moves.push((column, *letter));
+                }
+            }
         }
+        moves
     }
 
     fn random_gen(&self, game_board: Board) -> (usize, DiscType) {
-        let mut rng = thread_rng();
+        let mut rng = self.search.rng.borrow_mut();
         let valid_columns = game_board.get_valid_columns();
-        match valid_columns.choose(&mut rng) {
-            Some(column) => {
-                let disc_type = {
-                    if rng.gen_range(0, 2) == 0 {
-                        DiscType::T
-                    } else {
-                        DiscType::O
-                    }
-                };
-                (*column, disc_type)
-            },
-            None => return (0, DiscType::Empty), // not gonna be used
+        if valid_columns.is_empty() {
+            return (0, DiscType::Empty); // not gonna be used
         }
+        let column = valid_columns[get_random_index(valid_columns.len(), &mut rng)];
+        let disc_type = {
+            if rng.gen_range(0, 2) == 0 {
+                DiscType::T
+            } else {
+                DiscType::O
+            }
+        };
+        (column, disc_type)
     }
 
-    fn convert_board(&mut self, board: Board) {
+    // T and O swap values when the AI spells TOOT, so evaluate() always sees the AI's word as +4
+    fn convert_board(&mut self, game: &BoardGame) {
+        self.perspective = if game.current_player == 1 { -1 } else { 1 };
+        let board = &game.game_board;
         for y in 0..self.board_rows {
             for x in 0..self.board_columns {
                 self.score_board[y][x] = if board.board[y][x] == DiscType::T {
-                    1
+                    self.perspective
                 } else if board.board[y][x] == DiscType::O {
-                    -1
+                    -self.perspective
                 } else {
                     0
                 }
@@ -404,89 +810,185 @@ impl TootOttoAI {
         }
     }
 
-    pub fn find_best_move(&mut self, game: BoardGame) -> (usize, DiscType) {
-        self.convert_board(game.game_board.clone());
-        match self.difficulty {
-            Difficulty::Easy => {
-                // pure random
-                return self.random_gen(game.game_board.clone())
-            },
-            Difficulty::Medium => {
-                // find if there's a winning move for AI
-                let (winning_move, disc_type) = self.find_winning_move(game.game_board.clone());
-                if winning_move >= 0 {
-                    return (winning_move as usize, disc_type)
-                } else { // find if there's a blocking move for player
-                    let (blocking_move, disc_type) = self.find_blocking_move(game.game_board.clone());
-                    if blocking_move >= 0 {
-                        return (blocking_move as usize, disc_type)
-                    } else {
-                        return self.random_gen(game.game_board.clone())
-                    }
-                }
-            },
-            _ => { // Hard or Insane
-                let choice_val = self.max_state(&self.score_board, 0, -100000000007, 100000000007);
-                let (column, letter) = choice_val.1;
-                if column < 0 || column as usize > self.board_columns || game.game_board.is_column_full(column as usize) {
-                    return self.random_gen(game.game_board.clone());
+    fn letter_value(&self, letter: char) -> i64 {
+        if letter == 'T' { self.perspective } else { -self.perspective }
+    }
+
+    fn disc_type_of(letter: char) -> DiscType {
+        if letter == 'T' { DiscType::T } else { DiscType::O }
+    }
+
+    // The game doesn't hand out letters, so the evaluation assumes the usual rule: both players
+    // together have half the cells worth of T's and half of O's. Only a guess at which windows
+    // can still be filled, the search plays any letter the game allows.
+    // returns the T's and O's left
+    fn letters_left(&self, state: &[Vec<i64>]) -> (i64, i64) {
+        let limit = (self.board_rows * self.board_columns / 2) as i64;
+        let t_value = self.letter_value('T');
+        let (mut t_used, mut o_used) = (0, 0);
+        for row in state {
+            for &cell in row {
+                if cell == t_value {
+                    t_used += 1;
+                } else if cell != 0 {
+                    o_used += 1;
                 }
-                let ret_let = if letter == 'T' { DiscType::T } else { DiscType::O };
-                return (column as usize, ret_let);
             }
         }
+        (std::cmp::max(limit - t_used, 0), std::cmp::max(limit - o_used, 0))
     }
 
-    fn find_winning_move(&self, game_board: Board) -> (isize, DiscType) {
-        // find if there's a move that causes AI(OTTO) to win
-        let valid_columns = game_board.get_valid_columns();
-        // first check for 'T'
-        for col in valid_columns.clone() {
-            let mut clone_board = game_board.clone();
-            clone_board.place_disc(col, DiscType::T);
-            match clone_board.is_toot_or_otto() {
-                GameEvent::IsOTTO => return (col as isize, DiscType::T),
-                _ => ()
-            }
-        }
+    // TOOT-OTTO evaluation, from the point of view of the AI's word (+4 in the score board).
+    // Every 4 cell window is checked against both words: a window with letters of both is
+    // dead, a window that needs more of a letter than is left is dead, otherwise it scores
+    // chain_weights[letters in place] for its word, e.g. T_OT or O_TO score chain_weights[3].
+    // A window missing one letter in a cell that can be played right now counts twice.
+    // Returns (4 / -4 when the AI's / opponent's word is on the board, score, both words on the board)
+    fn evaluate(&self, state: &[Vec<i64>]) -> (i64, i64, bool) {
+        let (t_left, o_left) = self.letters_left(state);
+        let t_value = self.letter_value('T');
+        let (mut ai_word, mut player_word) = (false, false);
+        let mut score = 0;
+
+        for i in 0..self.board_rows as isize {
+            for j in 0..self.board_columns as isize {
+                for (di, dj) in &[(0, 1), (1, 0), (1, 1), (-1, 1)] {
+                    let (end_i, end_j) = (i + 3 * di, j + 3 * dj);
+                    if end_i < 0 || end_i >= self.board_rows as isize || end_j >= self.board_columns as isize {
+                        continue;
+                    }
+
+                    let (mut ai_letters, mut player_letters) = (0, 0);
+                    let (mut ai_needs_t, mut ai_needs_o, mut player_needs_t, mut player_needs_o) = (0, 0, 0, 0);
+                    let mut playable_gap = false;
+                    for k in 0..4 {
+                        let (row, column) = ((i + k * di) as usize, (j + k * dj) as usize);
+                        // the AI's word has value 1 * sign in each cell, the opponent's word -1 * sign
+                        let sign = if k == 0 || k == 3 { -1 } else { 1 };
+                        let cell = state[row][column];
+                        if cell == 0 {
+                            if sign == t_value { ai_needs_t += 1; player_needs_o += 1; } else { ai_needs_o += 1; player_needs_t += 1; }
+                            playable_gap = row + 1 == self.board_rows || state[row + 1][column] != 0;
+                        } else if cell * sign == 1 {
+                            ai_letters += 1;
+                        } else {
+                            player_letters += 1;
+                        }
+                    }
 
-        // then check for 'O'
-        for col in valid_columns.clone() {
-            let mut clone_board = game_board.clone();
-            clone_board.place_disc(col, DiscType::O);
-            match clone_board.is_toot_or_otto() {
-                GameEvent::IsOTTO => return (col as isize, DiscType::O),
-                _ => ()
+                    if ai_letters == 4 {
+                        ai_word = true;
+                    } else if player_letters == 4 {
+                        player_word = true;
+                    } else if player_letters == 0 && ai_letters > 0 && ai_needs_t <= t_left && ai_needs_o <= o_left {
+                        score += self.search.params.chain_weights[ai_letters];
+                        if ai_letters == 3 && playable_gap {
+                            score += self.search.params.chain_weights[3];
+                        }
+                    } else if ai_letters == 0 && player_letters > 0 && player_needs_t <= t_left && player_needs_o <= o_left {
+                        score -= self.search.params.chain_weights[player_letters];
+                        if player_letters == 3 && playable_gap {
+                            score -= self.search.params.chain_weights[3];
+                        }
+                    }
+                }
             }
         }
 
-        (-1, DiscType::Empty)
+        if ai_word && player_word {
+            (0, score, true)
+        } else if ai_word {
+            (4, score, false)
+        } else if player_word {
+            (-4, score, false)
+        } else {
+            (0, score, false)
+        }
     }
 
-    fn find_blocking_move(&self, game_board: Board) -> (isize, DiscType) {
-        // find if there's a move that can prevent player(TOOT) to win
-        let valid_columns = game_board.get_valid_columns();
-        // first check for 'T'
-        for col in valid_columns.clone() {
-            let mut clone_board = game_board.clone();
-            clone_board.place_disc(col, DiscType::T);
-            match clone_board.is_toot_or_otto() {
-                GameEvent::IsTOOT => return (col as isize, DiscType::O), // place the opposite disc to prevent winning
-                _ => ()
+    pub fn find_best_move(&mut self, game: BoardGame) -> (usize, DiscType) {
+        self.convert_board(&game);
+        self.search.clear_trace();
+        if self.search.strength == MAX_STRENGTH {
+            if let Some(game_move) = self.search.perfect_move(&game) {
+                return game_move;
             }
         }
-
-        // then check for 'O'
-        for col in valid_columns.clone() {
-            let mut clone_board = game_board.clone();
-            clone_board.place_disc(col, DiscType::O);
-            match clone_board.is_toot_or_otto() {
-                GameEvent::IsTOOT => return (col as isize, DiscType::T),
-                _ => ()
+        let temperature = temperature(self.search.strength, &self.search.params);
+        if temperature > 0.0 {
+            let evaluations = self.analyze(&game);
+            let choice = choose_near_best(&evaluations, temperature, &mut self.search.rng.borrow_mut())
+                .map(|evaluation| (evaluation.column, evaluation.disc_type));
+            if let Some(game_move) = choice {
+                self.search.mark_trace(game_move);
             }
+            return choice.unwrap_or_else(|| self.random_gen(game.game_board.clone()));
         }
 
-        (-1, DiscType::Empty)
+        // full strength
+        let choice_val = if self.search.threads > 1 {
+            self.split_root()
+        } else {
+            self.max_state(&self.score_board, 0, -INFINITY, INFINITY)
+        };
+        let (column, letter) = choice_val.1;
+        if column < 0 || column as usize > self.board_columns || game.game_board.is_column_full(column as usize) {
+            return self.random_gen(game.game_board.clone());
+        }
+        self.search.mark_trace((column as usize, Self::disc_type_of(letter)));
+        return (column as usize, Self::disc_type_of(letter));
+    }
+
+    fn evaluate_move(&self, column: usize, letter: char) -> MoveEvaluation {
+        let max_depth = self.search.max_depth();
+        let mut state = fill_map(&self.score_board, column, self.letter_value(letter), self.board_rows, self.board_columns);
+        let node = trace_enter(&self.search.trace, (column, Self::disc_type_of(letter)), true, -INFINITY, INFINITY);
+        let score = self.value(&state, 0, -INFINITY, INFINITY);
+        trace_exit(&self.search.trace, node, score, false);
+        // spelling both words ends the game as well
+        let searched_to_the_end = self.is_game_over(&state) || fills_board(&state, max_depth);
+
+        // follow the best replies to get the line the score comes from, these searches
+        // are already in the trace
+        let trace = self.search.trace.borrow_mut().take();
+        let mut principal_variation = vec![(column, Self::disc_type_of(letter))];
+        let mut depth = 1;
+        while depth <= max_depth && !self.is_game_over(&state) {
+            let (_, (reply, reply_letter)) = if depth % 2 == 1 {
+                self.min_state(&state, depth, -INFINITY, INFINITY)
+            } else {
+                self.max_state(&state, depth, -INFINITY, INFINITY)
+            };
+            state = fill_map(&state, reply as usize, self.letter_value(reply_letter), self.board_rows, self.board_columns);
+            principal_variation.push((reply as usize, Self::disc_type_of(reply_letter)));
+            depth += 1;
+        }
+        *self.search.trace.borrow_mut() = trace;
+
+        MoveEvaluation {
+            column,
+            disc_type: Self::disc_type_of(letter),
+            score,
+            depth: max_depth as usize + 1,
+            principal_variation,
+            outcome: score_outcome(score, searched_to_the_end, &self.search.params),
+        }
+    }
+
+    // root splitting: every root move gets a full window search of its own, the same
+    // values max_state() finds at the root
+    fn split_root(&self) -> (i64, (i64, char)) {
+        let scores = parallel::map(self.search.workers(self.root_moves(), |search| self.worker(search)), self.search.threads, move |((column, letter), worker)| {
+            let state = fill_map(&worker.score_board, column, worker.letter_value(letter), worker.board_rows, worker.board_columns);
+            ((column as i64, letter), worker.value(&state, 0, -INFINITY, INFINITY))
+        });
+
+        let best = match scores.iter().map(|(_, score)| *score).max() {
+            Some(best) => best,
+            None => return (0, (-1, 'T')), // the board is full, it's a draw
+        };
+        let move_queue: Vec<(i64, char)> = scores.iter().filter(|(_, score)| *score == best).map(|(game_move, _)| *game_move).collect();
+        (best, self.choose(&move_queue))
     }
 
     fn choose(&self, choice: &Vec<(i64, char)>) -> (i64, char) {
@@ -494,10 +996,15 @@ impl TootOttoAI {
             return (0, 'T'); // default, needed for intermediate returns
         }
 
-        let index = get_random_index(choice.len());
+        let index = get_random_index(choice.len(), &mut self.search.rng.borrow_mut());
         return choice[index];
     }
 
+    fn is_game_over(&self, state: &[Vec<i64>]) -> bool {
+        let (win_val, _, both_words) = self.evaluate(state);
+        win_val != 0 || both_words || is_state_full(state)
+    }
+
     pub fn value(
         &self, 
         state: &Vec<Vec<i64>>, 
@@ -505,37 +1012,34 @@ impl TootOttoAI {
         alpha: i64, 
         beta: i64
     ) -> i64 {
-        let val = check_state(state, self.board_rows, self.board_columns, true);
-        // depth is a big lower as the AI is slow
-        let max_depth = match self.difficulty {
-            Difficulty::Hard => 2,
-            Difficulty::Insane => 3,
-            _ => 1,
-        };
-        if depth >= max_depth { // if slow (or memory consumption is high), lower the value
+        let (win_val, chain_val, both_words) = self.evaluate(state);
+        if both_words { // both words spelled with the same disc, it's a draw
+            return 0;
+        }
+        let max_depth = self.search.max_depth();
+        if depth >= max_depth || self.search.stopped() { // if slow (or memory consumption is high), lower the value
             // if win, value = +inf
-            let (win_val, chain_val) = val; 
             let mut ret_val = chain_val;
 
             // If it lead to winning, then do it
             if win_val == 4 { // AI win, AI wants to win of course
-                ret_val = REWARD;
-            } else if win_val == 4 { // AI lose, AI hates losing
-                ret_val = REWARD * -1;
+                ret_val = self.search.params.reward;
+            } else if win_val == -4 { // AI lose, AI hates losing
+                ret_val = self.search.params.reward * -1;
             }
-            ret_val -= depth * depth;
+            ret_val -= self.search.params.depth_penalty * depth * depth;
 
             return ret_val;
         }
 
-        let win = val.0;
+        let win = win_val;
         // if already won, then return the value right away
         if win == 4 { // AI win, AI wants to win of course
-            return REWARD - depth * depth;
+            return self.search.params.reward - self.search.params.depth_penalty * depth * depth;
         }
         if win == -4 {
             // AI lose, AI hates losing
-            return REWARD * -1 - depth * depth;
+            return self.search.params.reward * -1 - self.search.params.depth_penalty * depth * depth;
         }
 
         if depth % 2 == 0 {
@@ -552,15 +1056,16 @@ impl TootOttoAI {
         mut alpha: i64,
         beta: i64,
     ) -> (i64, (i64, char)) {
-        let mut v = -100000000007;
+        let mut v = -INFINITY;
         let new_move: (i64, char);
         let mut move_queue = Vec::new();
 
         for letter in &['T', 'O'] {
             for j in 0..self.board_columns {
-                let move_value = if *letter == 'T' { 1 } else { -1 };
+                let move_value = self.letter_value(*letter);
                 let temp_state = fill_map(state, j, move_value, self.board_rows, self.board_columns);
                 if temp_state[0][0] != ERR_CODE {
+                    let node = trace_enter(&self.search.trace, (j, Self::disc_type_of(*letter)), true, alpha, beta);
                     let temp_val = self.value(&temp_state, depth, alpha, beta);
                     if temp_val > v {
                         v = temp_val;
@@ -570,6 +1075,7 @@ impl TootOttoAI {
                         move_queue.push((j as i64, *letter));
                     }
                     // alpha-beta pruning
+                    trace_exit(&self.search.trace, node, temp_val, v > beta);
                     if v > beta {
                         new_move = self.choose(&move_queue);
                         return (v, new_move);
@@ -578,6 +1084,9 @@ impl TootOttoAI {
                 }
             }
         }
+        if move_queue.is_empty() { // the board is full, it's a draw
+            return (0, (-1, 'T'));
+        }
         new_move = self.choose(&move_queue);
 
         return (v, new_move);
@@ -590,15 +1099,16 @@ impl TootOttoAI {
         alpha: i64,
         mut beta: i64,
     ) -> (i64, (i64, char)) {
-        let mut v = 100000000007;
+        let mut v = INFINITY;
         let new_move: (i64, char);
         let mut move_queue = Vec::new();
 
         for letter in &['T', 'O'] {
             for j in 0..self.board_columns {
-                let move_value = if *letter == 'T' { 1 } else { -1 };
+                let move_value = self.letter_value(*letter);
                 let temp_state = fill_map(state, j, move_value, self.board_rows, self.board_columns);
                 if temp_state[0][0] != ERR_CODE {
+                    let node = trace_enter(&self.search.trace, (j, Self::disc_type_of(*letter)), false, alpha, beta);
                     let temp_val = self.value(&temp_state, depth, alpha, beta);
                     if temp_val < v {
                         v = temp_val;
@@ -609,6 +1119,7 @@ impl TootOttoAI {
                     }
 
                     // alpha-beta pruning
+                    trace_exit(&self.search.trace, node, temp_val, v < alpha);
                     if v < alpha {
                         new_move = self.choose(&move_queue);
                         return (v, new_move);
@@ -617,9 +1128,233 @@ impl TootOttoAI {
                 }
             }
         }
+        if move_queue.is_empty() { // the board is full, it's a draw
+            return (0, (-1, 'T'));
+        }
         new_move = self.choose(&move_queue);
 
         return (v, new_move);
     }
 
 }
+
+impl SearchAI for TootOttoAI {
+    fn search(&self) -> &Search {
+        &self.search
+    }
+
+    fn search_mut(&mut self) -> &mut Search {
+        &mut self.search
+    }
+
+    fn analyze(&mut self, game: &BoardGame) -> Vec<MoveEvaluation> {
+        self.convert_board(game);
+        self.search.clear_trace();
+        // the search draws from a copy of the random generator, so analysing a position
+        // doesn't change the moves the seed plays
+        let rng = self.search.rng.borrow().clone();
+        let moves = self.root_moves();
+
+        let mut evaluations: Vec<MoveEvaluation> = if self.search.threads > 1 {
+            parallel::map(self.search.workers(moves, |search| self.worker(search)), self.search.threads, move |((column, letter), worker)| worker.evaluate_move(column, letter))
+        } else {
+            moves.into_iter().map(|(column, letter)| self.evaluate_move(column, letter)).collect()
+        };
+
+        *self.search.rng.get_mut() = rng;
+        evaluations.sort_by_key(|evaluation| std::cmp::Reverse(evaluation.score));
+        if let Some(best) = evaluations.first() {
+            self.search.mark_trace((best.column, best.disc_type));
+        }
+        evaluations
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notation::parse_moves;
+
+    fn game_after(game_type: &GameType, rows: usize, columns: usize, moves: &str) -> BoardGame {
+        let mut game = BoardGame::new_game(game_type, rows, columns, false);
+        for game_move in parse_moves(&game, moves).unwrap() {
+            game.play_move(game_move);
+        }
+        game
+    }
+
+    // two AIs with the same settings play each other from the empty board
+    fn connect4_moves(difficulty: Difficulty, seed: u64) -> Vec<usize> {
+        let mut players = (Connect4AI::new(6, 7, difficulty, seed), Connect4AI::new(6, 7, difficulty, seed + 1));
+        let mut game = BoardGame::new_game(&GameType::Connect4, 6, 7, false);
+        let mut moves = Vec::new();
+        for turn in 0..6 {
+            let ai = if turn % 2 == 0 { &mut players.0 } else { &mut players.1 };
+            let column = ai.find_best_move(game.clone());
+            moves.push(column);
+            if game.play_move((column, game.get_current_disc_type())).is_some() {
+                break;
+            }
+        }
+        moves
+    }
+
+    fn toot_otto_moves(difficulty: Difficulty, seed: u64) -> Vec<(usize, DiscType)> {
+        let mut players = (TootOttoAI::new(4, 6, difficulty, seed), TootOttoAI::new(4, 6, difficulty, seed + 1));
+        let mut game = BoardGame::new_game(&GameType::TOOTandOTTO, 4, 6, false);
+        let mut moves = Vec::new();
+        for turn in 0..6 {
+            let ai = if turn % 2 == 0 { &mut players.0 } else { &mut players.1 };
+            let game_move = ai.find_best_move(game.clone());
+            moves.push(game_move);
+            if game.play_move(game_move).is_some() {
+                break;
+            }
+        }
+        moves
+    }
+
+    #[test]
+    fn same_seed_same_game() {
+        for difficulty in &[Difficulty::Easy, Difficulty::Hard] {
+            assert_eq!(connect4_moves(*difficulty, 3), connect4_moves(*difficulty, 3));
+            assert_eq!(toot_otto_moves(*difficulty, 3), toot_otto_moves(*difficulty, 3));
+        }
+    }
+
+    #[test]
+    fn analyze_keeps_the_seed() {
+        let game = game_after(&GameType::Connect4, 6, 7, "3 3 2");
+        let mut analysed = Connect4AI::new(6, 7, Difficulty::Easy, 11);
+        let mut fresh = Connect4AI::new(6, 7, Difficulty::Easy, 11);
+        analysed.analyze(&game);
+        for _ in 0..5 {
+            assert_eq!(analysed.find_best_move(game.clone()), fresh.find_best_move(game.clone()));
+        }
+    }
+
+    #[test]
+    fn analyze_every_legal_move() {
+        let game = game_after(&GameType::Connect4, 6, 7, "0 0 0 0 0 0 3");
+        let evaluations = Connect4AI::new(6, 7, Difficulty::Hard, 1).analyze(&game);
+        let mut columns: Vec<usize> = evaluations.iter().map(|evaluation| evaluation.column).collect();
+        columns.sort();
+        assert_eq!(columns, vec![1, 2, 3, 4, 5, 6]);
+        assert!(evaluations.windows(2).all(|pair| pair[0].score >= pair[1].score));
+        assert!(evaluations.iter().all(|evaluation| evaluation.disc_type == DiscType::Yellow));
+
+        let game = game_after(&GameType::TOOTandOTTO, 4, 6, "2T");
+        let evaluations = TootOttoAI::new(4, 6, Difficulty::Hard, 1).analyze(&game);
+        let mut moves: Vec<(usize, DiscType)> = evaluations.iter().map(|evaluation| (evaluation.column, evaluation.disc_type)).collect();
+        moves.sort_by_key(|(column, disc_type)| (*column, *disc_type == DiscType::O));
+        assert_eq!(moves, game.legal_moves());
+    }
+
+    #[test]
+    fn analyze_wins_and_losses() {
+        // red wins in column 0
+        let game = game_after(&GameType::Connect4, 6, 7, "0 6 0 6 0 5");
+        let evaluations = Connect4AI::new(6, 7, Difficulty::Hard, 1).analyze(&game);
+        assert_eq!((evaluations[0].column, evaluations[0].outcome), (0, Some(MoveOutcome::Win)));
+        assert_eq!(evaluations[0].principal_variation, vec![(0, DiscType::Red)]);
+        assert_eq!(evaluations[0].depth, SEARCH_DEPTH as usize + 1);
+
+        // and yellow has to stop it
+        let game = game_after(&GameType::Connect4, 6, 7, "0 6 0 6 0");
+        for evaluation in Connect4AI::new(6, 7, Difficulty::Hard, 1).analyze(&game) {
+            assert_eq!(evaluation.outcome == Some(MoveOutcome::Loss), evaluation.column != 0, "{:?}", evaluation);
+        }
+
+        // T O O _ for TOOT, player 1
+        let game = game_after(&GameType::TOOTandOTTO, 4, 6, "0T 1O 2O 5T");
+        let best = &TootOttoAI::new(4, 6, Difficulty::Hard, 1).analyze(&game)[0];
+        assert_eq!((best.column, best.disc_type, best.outcome), (3, DiscType::T, Some(MoveOutcome::Win)));
+    }
+
+    #[test]
+    fn draws_only_when_searched_to_the_end() {
+        // 4 x 4 with only the top row left
+        let game = game_after(&GameType::Connect4, 4, 4, "0 1 2 3 1 0 3 2 0 1 2 3");
+        let mut ai = Connect4AI::new(4, 4, Difficulty::Hard, 1);
+        ai.set_depth(Some(4));
+        let evaluations = ai.analyze(&game);
+        for evaluation in &evaluations {
+            assert_eq!(evaluation.depth, 4);
+            assert!(evaluation.outcome.is_some(), "{:?}", evaluation);
+        }
+        assert!(evaluations.iter().any(|evaluation| evaluation.outcome == Some(MoveOutcome::Draw)));
+        ai.set_depth(Some(2));
+        for evaluation in ai.analyze(&game) {
+            assert_ne!(evaluation.outcome, Some(MoveOutcome::Draw), "{:?}", evaluation);
+        }
+    }
+
+    #[test]
+    fn strengths_and_temperatures() {
+        let strengths: Vec<u8> = Difficulty::fixed().iter().map(|difficulty| difficulty.strength()).collect();
+        assert!(strengths.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(Difficulty::Insane.strength(), MAX_STRENGTH);
+        assert_eq!(Difficulty::Adaptive.strength(), Difficulty::Hard.strength());
+
+        let mut ai = Connect4AI::new(6, 7, Difficulty::Easy, 1);
+        ai.set_strength(200);
+        assert_eq!(ai.search.strength, MAX_STRENGTH);
+
+        let params = EvalParams::tuned(&GameType::Connect4);
+        assert_eq!(temperature(0, &params), params.chain_weights[3] as f64);
+        assert_eq!(temperature(MAX_STRENGTH, &params), 0.0);
+        assert!((0..MAX_STRENGTH).all(|strength| temperature(strength, &params) > temperature(strength + 1, &params)));
+    }
+
+    #[test]
+    fn only_near_best_moves() {
+        let evaluation = |column, score, outcome| MoveEvaluation {
+            column,
+            disc_type: DiscType::Red,
+            score,
+            depth: 4,
+            principal_variation: vec![(column, DiscType::Red)],
+            outcome,
+        };
+        let evaluations = vec![
+            evaluation(0, 50, None),
+            evaluation(1, 40, None),
+            evaluation(2, 0, None),
+            evaluation(3, -1000000, Some(MoveOutcome::Loss)),
+        ];
+        let mut rng = StdRng::seed_from_u64(1);
+        for _ in 0..200 {
+            let column = choose_near_best(&evaluations, 10.0, &mut rng).unwrap().column;
+            assert!(column == 0 || column == 1, "{}", column);
+        }
+    }
+
+    #[test]
+    fn wins_at_every_strength() {
+        let connect4 = game_after(&GameType::Connect4, 6, 7, "0 6 0 6 0 5");
+        let toot_otto = game_after(&GameType::TOOTandOTTO, 4, 6, "0T 1O 2O 5T");
+        for strength in &[0, 10, 40, 80, MAX_STRENGTH] {
+            for seed in 0..3 {
+                let mut ai = Connect4AI::new(6, 7, Difficulty::Easy, seed);
+                ai.set_strength(*strength);
+                assert_eq!(ai.find_best_move(connect4.clone()), 0);
+                let mut ai = TootOttoAI::new(4, 6, Difficulty::Easy, seed);
+                ai.set_strength(*strength);
+                assert_eq!(ai.find_best_move(toot_otto.clone()), (3, DiscType::T));
+            }
+        }
+    }
+
+    #[test]
+    fn no_proven_losses_at_any_strength() {
+        // yellow loses unless it stops column 0
+        let game = game_after(&GameType::Connect4, 6, 7, "0 6 0 6 0");
+        for strength in &[0, 40, MAX_STRENGTH] {
+            for seed in 0..3 {
+                let mut ai = Connect4AI::new(6, 7, Difficulty::Easy, seed);
+                ai.set_strength(*strength);
+                assert_eq!(ai.find_best_move(game.clone()), 0);
+            }
+        }
+    }
+}
@@ -0,0 +1,232 @@
+// Plays self-play games and writes every position with the final result and the AI's
+// evaluation, as JSON lines and in the binary form, see model::dataset for both formats
+//
+// Games go round the selected game types and board sizes in turn. Each game starts with
+// random moves so deterministic players don't repeat themselves, and the positions of the
+// whole game, opening included, are written.
+//
+// usage (from the model folder):
+//     cargo run --release --bin dataset -- [--count 100] [--player1 hard] [--player2 strength:60]
+//         [--evaluator insane] [--games connect4,toot-otto] [--sizes "7x6,6x4"|all]
+//         [--opening 4] [--seed N] [--output dataset]   (writes dataset.jsonl and dataset.bin)
+//         [--threads N]   (needs --features parallel to use more than 1 core)
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use rand::prelude::*;
+use rand::rngs::StdRng;
+use model::ai::{Connect4AI, Difficulty, SearchAI, TootOttoAI, random_seed};
+use model::board_size::BoardSize;
+use model::dataset::{game_positions, write_header};
+use model::game::{BoardGame, GameType};
+use model::parallel::available_threads;
+use model::selfplay::{GameAI, play_game, random_opening};
+
+// a minimax player, a difficulty or any strength from 0 to 100 (see SearchAI::set_strength())
+#[derive(Clone, Copy, Debug)]
+enum Player {
+    Difficulty(Difficulty),
+    Strength(u8),
+}
+
+impl Player {
+    fn parse(spec: &str) -> Result<Self, String> {
+        match spec.strip_prefix("strength:") {
+            Some(strength) => Ok(Player::Strength(strength.parse().map_err(|_| format!("invalid strength: {}", strength))?)),
+            None => Ok(Player::Difficulty(spec.parse()?)),
+        }
+    }
+
+    fn name(&self) -> String {
+        match self {
+            Player::Difficulty(difficulty) => difficulty.to_string(),
+            Player::Strength(strength) => format!("Strength-{}", strength),
+        }
+    }
+
+    fn new_ai(&self, game_type: &GameType, board_size: BoardSize, seed: u64, threads: usize) -> Box<dyn GameAI> {
+        let (difficulty, strength) = match *self {
+            Player::Difficulty(difficulty) => (difficulty, None),
+            Player::Strength(strength) => (Difficulty::Hard, Some(strength)),
+        };
+        let rows = board_size.get_row();
+        let columns = board_size.get_column();
+        match game_type {
+            GameType::Connect4 => {
+                let mut ai = Connect4AI::new(rows, columns, difficulty, seed);
+                if let Some(strength) = strength {
+                    ai.set_strength(strength);
+                }
+                ai.set_threads(threads);
+                Box::new(ai)
+            },
+            GameType::TOOTandOTTO => {
+                let mut ai = TootOttoAI::new(rows, columns, difficulty, seed);
+                if let Some(strength) = strength {
+                    ai.set_strength(strength);
+                }
+                ai.set_threads(threads);
+                Box::new(ai)
+            },
+        }
+    }
+}
+
+struct Options {
+    count: usize,
+    player1: Player,
+    player2: Player,
+    evaluator: Difficulty,
+    game_types: Vec<GameType>,
+    board_sizes: Vec<BoardSize>,
+    opening_plies: usize,
+    seed: u64,
+    output: String,
+    threads: usize,
+}
+
+fn parse_args() -> Result<Options, String> {
+    let mut options = Options {
+        count: 100,
+        player1: Player::Difficulty(Difficulty::Hard),
+        player2: Player::Difficulty(Difficulty::Hard),
+        evaluator: Difficulty::Insane,
+        game_types: vec![GameType::Connect4, GameType::TOOTandOTTO],
+        board_sizes: Vec::new(),
+        opening_plies: 4,
+        seed: random_seed(),
+        output: String::from("dataset"),
+        threads: available_threads(),
+    };
+
+    let mut args = std::env::args().skip(1);
+    while let Some(flag) = args.next() {
+        let value = args.next().ok_or(format!("missing value for {}", flag))?;
+        match flag.as_str() {
+            "--count" => options.count = value.parse().map_err(|_| format!("invalid count: {}", value))?,
+            "--player1" => options.player1 = Player::parse(&value)?,
+            "--player2" => options.player2 = Player::parse(&value)?,
+            "--evaluator" => options.evaluator = value.parse()?,
+            "--games" => {
+                options.game_types = value.split(',').map(|game| game.trim().parse()).collect::<Result<_, _>>()?;
+            },
+            "--sizes" => {
+                options.board_sizes = if value == "all" {
+                    BoardSize::to_vec()
+                } else {
+                    value.split(',').map(|size| size.trim().parse()).collect::<Result<_, _>>()?
+                };
+            },
+            "--opening" => options.opening_plies = value.parse().map_err(|_| format!("invalid opening: {}", value))?,
+            "--seed" => options.seed = value.parse().map_err(|_| format!("invalid seed: {}", value))?,
+            "--output" => options.output = value,
+            "--threads" => options.threads = value.parse().map_err(|_| format!("invalid threads: {}", value))?,
+            _ => return Err(format!("unknown flag: {}", flag)),
+        }
+    }
+
+    if options.evaluator == Difficulty::Adaptive {
+        return Err(String::from("the evaluator is easy, medium, hard or insane"));
+    }
+    Ok(options)
+}
+
+// every game type with each of its board sizes, the games go round these
+fn variants(options: &Options) -> Vec<(GameType, BoardSize)> {
+    let mut variants = Vec::new();
+    for game_type in &options.game_types {
+        let board_sizes = if options.board_sizes.is_empty() {
+            // same defaults as the CLI and the web pages
+            match game_type {
+                GameType::Connect4 => vec![BoardSize::SevenBySix],
+                GameType::TOOTandOTTO => vec![BoardSize::SixByFour],
+            }
+        } else {
+            options.board_sizes.clone()
+        };
+        for board_size in board_sizes {
+            variants.push((game_type.clone(), board_size));
+        }
+    }
+    variants
+}
+
+fn create(path: &str) -> BufWriter<File> {
+    match File::create(path) {
+        Ok(file) => BufWriter::new(file),
+        Err(err) => {
+            eprintln!("failed to create {}: {}", path, err);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn main() {
+    let options = match parse_args() {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(2);
+        }
+    };
+    println!(
+        "{} games, {} against {}, evaluated at {} difficulty, seed {}",
+        options.count,
+        options.player1.name(),
+        options.player2.name(),
+        options.evaluator.to_string(),
+        options.seed
+    );
+
+    let json_path = format!("{}.jsonl", options.output);
+    let binary_path = format!("{}.bin", options.output);
+    let mut json = create(&json_path);
+    let mut binary = create(&binary_path);
+    let mut rng = StdRng::seed_from_u64(options.seed);
+    let variants = variants(&options);
+    let mut positions = 0;
+    let mut wins = [0; 3]; // draws, player 1 wins, player 2 wins
+
+    let written = write_header(&mut binary).and_then(|_| {
+        for game_id in 0..options.count {
+            let (game_type, board_size) = &variants[game_id % variants.len()];
+            let (rows, columns) = (board_size.get_row(), board_size.get_column());
+            let game = BoardGame::new_game(game_type, rows, columns, true);
+            let opening = random_opening(&game, options.opening_plies, &mut rng);
+            let mut player1 = options.player1.new_ai(game_type, *board_size, rng.gen(), options.threads);
+            let mut player2 = options.player2.new_ai(game_type, *board_size, rng.gen(), options.threads);
+            let record = play_game(game.clone(), &opening, player1.as_mut(), player2.as_mut());
+            wins[record.winner] += 1;
+
+            let seed = rng.gen();
+            let game_positions = match game_type {
+                GameType::Connect4 => {
+                    let mut evaluator = Connect4AI::new(rows, columns, options.evaluator, seed);
+                    evaluator.set_threads(options.threads);
+                    game_positions(game_id as u32, game, &record.moves, record.winner, &mut |game| evaluator.analyze(game))
+                },
+                GameType::TOOTandOTTO => {
+                    let mut evaluator = TootOttoAI::new(rows, columns, options.evaluator, seed);
+                    evaluator.set_threads(options.threads);
+                    game_positions(game_id as u32, game, &record.moves, record.winner, &mut |game| evaluator.analyze(game))
+                },
+            };
+            for position in &game_positions {
+                writeln!(json, "{}", position.to_json())?;
+                position.write_to(&mut binary)?;
+            }
+            positions += game_positions.len();
+            if (game_id + 1) % 10 == 0 || game_id + 1 == options.count {
+                println!("{}/{} games, {} positions", game_id + 1, options.count, positions);
+            }
+        }
+        json.flush()?;
+        binary.flush()
+    });
+    if let Err(err) = written {
+        eprintln!("failed to write the dataset: {}", err);
+        std::process::exit(1);
+    }
+
+    println!("player 1 won {}, player 2 won {}, {} draws", wins[1], wins[2], wins[0]);
+    println!("wrote {} and {}", json_path, binary_path);
+}
@@ -0,0 +1,115 @@
+// Generates the solution tables the solver embeds (model/tables/), see model::solution_table
+//
+// The tables only need to be made again if the solver's scores change. The 6 x 4 TOOT-OTTO
+// table takes a couple of hours, the others a few minutes at most.
+//
+// usage (from the model folder):
+//     cargo run --release --bin solve -- [--game connect4|toot-otto] [--size "6 x 4"]
+//         [--plies N] [--output FILE]
+use std::fs;
+use std::io::Write;
+use std::time::Instant;
+use model::board_size::BoardSize;
+use model::game::GameType;
+use model::solution_table::{generate, has_table};
+
+struct Options {
+    game_type: GameType,
+    board_size: BoardSize,
+    plies: usize,
+    output: String,
+}
+
+// deep enough that the positions after the table solve in well under a second
+fn default_plies(game_type: &GameType, board_size: BoardSize) -> usize {
+    match (game_type, board_size) {
+        (GameType::Connect4, BoardSize::FiveByFour) => 2,
+        (GameType::TOOTandOTTO, BoardSize::SixByFour) => 6,
+        _ => 4,
+    }
+}
+
+fn parse_args() -> Result<Options, String> {
+    let mut game_type = GameType::Connect4;
+    let mut board_size = BoardSize::SixByFour;
+    let mut plies = None;
+    let mut output = String::new();
+
+    let mut args = std::env::args().skip(1);
+    while let Some(flag) = args.next() {
+        let value = args.next().ok_or(format!("missing value for {}", flag))?;
+        match flag.as_str() {
+            "--game" => game_type = value.parse()?,
+            "--size" => board_size = value.parse()?,
+            "--plies" => plies = Some(value.parse().map_err(|_| format!("invalid plies: {}", value))?),
+            "--output" => output = value,
+            _ => return Err(format!("unknown flag: {}", flag)),
+        }
+    }
+
+    if !has_table(&game_type, board_size.get_row(), board_size.get_column()) {
+        return Err(format!("no solution table for {} on {}", game_type.to_string(), board_size.to_string()));
+    }
+    if output.is_empty() {
+        let name = match game_type {
+            GameType::Connect4 => "connect4",
+            GameType::TOOTandOTTO => "toot_otto",
+        };
+        output = format!("tables/{}_{}x{}.bin", name, board_size.get_column(), board_size.get_row());
+    }
+
+    Ok(Options {
+        plies: plies.unwrap_or_else(|| default_plies(&game_type, board_size)),
+        game_type,
+        board_size,
+        output,
+    })
+}
+
+fn main() {
+    let options = match parse_args() {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(2);
+        }
+    };
+    println!(
+        "solving {} on {} up to {} plies",
+        options.game_type.to_string(),
+        options.board_size.to_string(),
+        options.plies
+    );
+
+    let start = Instant::now();
+    let result = generate(
+        &options.game_type,
+        options.board_size.get_row(),
+        options.board_size.get_column(),
+        options.plies,
+        |ply, solved, count| {
+            if solved % 100 == 0 || solved == count {
+                print!("\rply {}: {}/{} positions, {:.0}s", ply, solved, count, start.elapsed().as_secs_f64());
+                std::io::stdout().flush().ok();
+            }
+            if solved == count {
+                println!();
+            }
+        },
+    );
+    let table = match result {
+        Ok(table) => table,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    };
+
+    match fs::write(&options.output, &table) {
+        Ok(_) => println!("{} bytes written to {}", table.len(), options.output),
+        Err(err) => {
+            eprintln!("failed to write {}: {}", options.output, err);
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,399 @@
+// Match runner between AI configurations, reports Elo differences with error bars
+//
+// Every pairing plays pairs of games from the same random opening with colours swapped,
+// on every selected board size and game type. With --sprt the pairs stop early once the
+// sequential probability ratio test accepts either hypothesis.
+//
+// usage (from the model folder):
+//     cargo run --release --bin tournament -- [--engines easy,medium,hard,insane,strength:50,mcts:2000,adaptive:1.5]
+//         [--mode round-robin|gauntlet] [--games connect4,toot-otto] [--sizes "7x6,6x4"|all]
+//         [--pairs 10] [--opening 2] [--seed N] [--sprt ELO0,ELO1]
+//         [--threads N]   (needs --features parallel to use more than 1 core)
+use std::collections::HashMap;
+use rand::prelude::*;
+use rand::rngs::StdRng;
+use model::adaptive::{AdaptiveAI, DEFAULT_LEVEL};
+use model::ai::{Connect4AI, Difficulty, SearchAI, TootOttoAI, random_seed};
+use model::board_size::BoardSize;
+use model::game::{BoardGame, GameType};
+use model::mcts::{MctsAI, MctsLimit};
+use model::parallel::available_threads;
+use model::selfplay::{GameAI, play_game, random_opening};
+
+// type I and type II error rates of the SPRT
+const SPRT_ALPHA: f64 = 0.05;
+const SPRT_BETA: f64 = 0.05;
+
+#[derive(Clone, Debug)]
+enum EngineKind {
+    Minimax(Difficulty),
+    Strength(u8), // minimax at any strength from 0 to 100, see SearchAI::set_strength()
+    Mcts(u32),
+    Adaptive(f64), // the starting level, see model::adaptive
+}
+
+#[derive(Clone, Debug)]
+struct Engine {
+    name: String,
+    kind: EngineKind,
+}
+
+impl Engine {
+    fn parse(spec: &str) -> Result<Self, String> {
+        let kind = if let Some(playouts) = spec.strip_prefix("mcts:") {
+            EngineKind::Mcts(playouts.parse().map_err(|_| format!("invalid playout count: {}", playouts))?)
+        } else if spec == "mcts" {
+            EngineKind::Mcts(1000)
+        } else if let Some(strength) = spec.strip_prefix("strength:") {
+            EngineKind::Strength(strength.parse().map_err(|_| format!("invalid strength: {}", strength))?)
+        } else if let Some(level) = spec.strip_prefix("adaptive:") {
+            EngineKind::Adaptive(level.parse().map_err(|_| format!("invalid level: {}", level))?)
+        } else if spec == "adaptive" {
+            EngineKind::Adaptive(DEFAULT_LEVEL)
+        } else {
+            EngineKind::Minimax(spec.parse()?)
+        };
+        let name = match &kind {
+            EngineKind::Minimax(difficulty) => difficulty.to_string(),
+            EngineKind::Strength(strength) => format!("Strength-{}", strength),
+            EngineKind::Mcts(playouts) => format!("MCTS-{}", playouts),
+            EngineKind::Adaptive(level) => format!("Adaptive-{}", level),
+        };
+        Ok(Self { name, kind })
+    }
+
+    // MCTS always runs on one thread
+    fn new_ai(&self, game_type: &GameType, board_size: BoardSize, seed: u64, threads: usize) -> Box<dyn GameAI> {
+        let rows = board_size.get_row();
+        let columns = board_size.get_column();
+        match (&self.kind, game_type) {
+            (EngineKind::Minimax(difficulty), GameType::Connect4) => {
+                let mut ai = Connect4AI::new(rows, columns, *difficulty, seed);
+                ai.set_threads(threads);
+                Box::new(ai)
+            },
+            (EngineKind::Minimax(difficulty), GameType::TOOTandOTTO) => {
+                let mut ai = TootOttoAI::new(rows, columns, *difficulty, seed);
+                ai.set_threads(threads);
+                Box::new(ai)
+            },
+            (EngineKind::Strength(strength), GameType::Connect4) => {
+                let mut ai = Connect4AI::new(rows, columns, Difficulty::Hard, seed);
+                ai.set_strength(*strength);
+                ai.set_threads(threads);
+                Box::new(ai)
+            },
+            (EngineKind::Strength(strength), GameType::TOOTandOTTO) => {
+                let mut ai = TootOttoAI::new(rows, columns, Difficulty::Hard, seed);
+                ai.set_strength(*strength);
+                ai.set_threads(threads);
+                Box::new(ai)
+            },
+            (EngineKind::Mcts(playouts), _) => Box::new(MctsAI::new(MctsLimit::Playouts(*playouts), true, seed)),
+            (EngineKind::Adaptive(level), _) => Box::new(AdaptiveAI::new(game_type, rows, columns, *level, seed)),
+        }
+    }
+}
+
+#[derive(PartialEq)]
+enum Mode {
+    RoundRobin,
+    Gauntlet,
+}
+
+struct Options {
+    engines: Vec<Engine>,
+    mode: Mode,
+    game_types: Vec<GameType>,
+    board_sizes: Vec<BoardSize>,
+    pairs: usize,
+    opening_plies: usize,
+    seed: u64,
+    sprt: Option<(f64, f64)>,
+    threads: usize,
+}
+
+fn parse_args() -> Result<Options, String> {
+    let mut options = Options {
+        engines: Vec::new(),
+        mode: Mode::RoundRobin,
+        game_types: vec![GameType::Connect4, GameType::TOOTandOTTO],
+        board_sizes: Vec::new(),
+        pairs: 10,
+        opening_plies: 2,
+        seed: random_seed(),
+        sprt: None,
+        threads: available_threads(),
+    };
+
+    let mut args = std::env::args().skip(1);
+    while let Some(flag) = args.next() {
+        let value = args.next().ok_or(format!("missing value for {}", flag))?;
+        match flag.as_str() {
+            "--engines" => {
+                options.engines = value.split(',').map(|spec| Engine::parse(spec.trim())).collect::<Result<_, _>>()?;
+            },
+            "--mode" => {
+                options.mode = match value.as_str() {
+                    "round-robin" => Mode::RoundRobin,
+                    "gauntlet" => Mode::Gauntlet,
+                    _ => return Err(format!("unknown mode: {}", value)),
+                };
+            },
+            "--games" => {
+                options.game_types = value.split(',').map(|game| game.trim().parse()).collect::<Result<_, _>>()?;
+            },
+            "--sizes" => {
+                options.board_sizes = if value == "all" {
+                    BoardSize::to_vec()
+                } else {
+                    value.split(',').map(|size| size.trim().parse()).collect::<Result<_, _>>()?
+                };
+            },
+            "--pairs" => options.pairs = value.parse().map_err(|_| format!("invalid pairs: {}", value))?,
+            "--opening" => options.opening_plies = value.parse().map_err(|_| format!("invalid opening: {}", value))?,
+            "--seed" => options.seed = value.parse().map_err(|_| format!("invalid seed: {}", value))?,
+            "--sprt" => {
+                let bounds: Vec<f64> = value.split(',').map(|elo| elo.trim().parse::<f64>())
+                    .collect::<Result<_, _>>()
+                    .map_err(|_| format!("invalid SPRT bounds: {}", value))?;
+                if bounds.len() != 2 || bounds[0] >= bounds[1] {
+                    return Err(String::from("--sprt expects ELO0,ELO1 with ELO0 < ELO1"));
+                }
+                options.sprt = Some((bounds[0], bounds[1]));
+            },
+            "--threads" => options.threads = value.parse().map_err(|_| format!("invalid threads: {}", value))?,
+            _ => return Err(format!("unknown flag: {}", flag)),
+        }
+    }
+
+    if options.engines.is_empty() {
+        options.engines = Difficulty::fixed().iter().map(|difficulty| Engine::parse(&difficulty.to_string()).unwrap()).collect();
+    }
+    if options.engines.len() < 2 {
+        return Err(String::from("at least 2 engines are needed"));
+    }
+    if options.pairs == 0 {
+        return Err(String::from("at least 1 pair of games is needed"));
+    }
+
+    Ok(options)
+}
+
+// wins, draws and losses from the point of view of the first engine of a pairing
+#[derive(Default, Clone, Copy)]
+struct Score {
+    wins: u32,
+    draws: u32,
+    losses: u32,
+}
+
+impl Score {
+    fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    fn add(&mut self, other: &Score) {
+        self.wins += other.wins;
+        self.draws += other.draws;
+        self.losses += other.losses;
+    }
+
+    fn mean(&self) -> f64 {
+        (self.wins as f64 + 0.5 * self.draws as f64) / self.games() as f64
+    }
+
+    // per game variance of the score
+    fn variance(&self) -> f64 {
+        let mean = self.mean();
+        let games = self.games() as f64;
+        (self.wins as f64 * (1.0 - mean).powi(2)
+            + self.draws as f64 * (0.5 - mean).powi(2)
+            + self.losses as f64 * mean.powi(2)) / games
+    }
+
+    // Elo difference and the 95% confidence interval around it
+    fn elo(&self) -> (f64, f64, f64) {
+        let mean = self.mean();
+        let margin = 1.96 * (self.variance() / self.games() as f64).sqrt();
+        (score_to_elo(mean), score_to_elo(mean - margin), score_to_elo(mean + margin))
+    }
+
+    // log likelihood ratio of elo1 against elo0
+    fn llr(&self, elo0: f64, elo1: f64) -> f64 {
+        if self.games() == 0 {
+            return 0.0;
+        }
+        // one extra draw keeps the variance above 0 during an unbeaten run
+        let variance = Score { draws: self.draws + 1, ..*self }.variance();
+        let (score0, score1) = (elo_to_score(elo0), elo_to_score(elo1));
+        self.games() as f64 * (score1 - score0) * (2.0 * self.mean() - score0 - score1) / (2.0 * variance)
+    }
+}
+
+fn score_to_elo(score: f64) -> f64 {
+    // clamped so a perfect score gives a large but finite number
+    let score = score.max(0.001).min(0.999);
+    -400.0 * (1.0 / score - 1.0).log10()
+}
+
+fn elo_to_score(elo: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-elo / 400.0))
+}
+
+fn format_elo(score: &Score) -> String {
+    let (elo, low, high) = score.elo();
+    format!("Elo {:+.0} [{:+.0}, {:+.0}]", elo, low, high)
+}
+
+fn format_score(score: &Score) -> String {
+    format!("+{} ={} -{} ({:.1}%)", score.wins, score.draws, score.losses, score.mean() * 100.0)
+}
+
+// play pairs of games from the same opening until the pairs run out or the SPRT stops
+fn play_pairing(
+    options: &Options,
+    first: &Engine,
+    second: &Engine,
+    game_type: &GameType,
+    board_size: BoardSize,
+    rng: &mut StdRng,
+) -> (Score, Option<&'static str>) {
+    let mut score = Score::default();
+    let game = BoardGame::new_game(game_type, board_size.get_row(), board_size.get_column(), true);
+
+    for _ in 0..options.pairs {
+        let opening = random_opening(&game, options.opening_plies, rng);
+        for first_player in 1..=2 {
+            let mut first_ai = first.new_ai(game_type, board_size, rng.gen(), options.threads);
+            let mut second_ai = second.new_ai(game_type, board_size, rng.gen(), options.threads);
+            let record = if first_player == 1 {
+                play_game(game.clone(), &opening, first_ai.as_mut(), second_ai.as_mut())
+            } else {
+                play_game(game.clone(), &opening, second_ai.as_mut(), first_ai.as_mut())
+            };
+
+            if record.winner == 0 {
+                score.draws += 1;
+            } else if record.winner == first_player {
+                score.wins += 1;
+            } else {
+                score.losses += 1;
+            }
+        }
+
+        if let Some((elo0, elo1)) = options.sprt {
+            let llr = score.llr(elo0, elo1);
+            if llr >= ((1.0 - SPRT_BETA) / SPRT_ALPHA).ln() {
+                return (score, Some("H1 accepted"));
+            } else if llr <= (SPRT_BETA / (1.0 - SPRT_ALPHA)).ln() {
+                return (score, Some("H0 accepted"));
+            }
+        }
+    }
+
+    (score, None)
+}
+
+// ratings that best explain all the results, the first engine is fixed at 0
+fn estimate_ratings(engine_count: usize, results: &HashMap<(usize, usize), Score>) -> Vec<f64> {
+    let mut ratings = vec![0.0; engine_count];
+    for _ in 0..1000 {
+        for engine in 1..engine_count {
+            let mut actual = 0.0;
+            let mut expected = 0.0;
+            let mut games = 0.0;
+            for (&(first, second), score) in results {
+                let (opponent, points) = if first == engine {
+                    (second, score.wins as f64 + 0.5 * score.draws as f64)
+                } else if second == engine {
+                    (first, score.losses as f64 + 0.5 * score.draws as f64)
+                } else {
+                    continue;
+                };
+                actual += points;
+                expected += score.games() as f64 * elo_to_score(ratings[engine] - ratings[opponent]);
+                games += score.games() as f64;
+            }
+            if games > 0.0 {
+                // clamped like score_to_elo() so an unbeaten engine doesn't run off
+                ratings[engine] += 100.0 * (actual - expected) / games;
+                ratings[engine] = ratings[engine].max(-1200.0).min(1200.0);
+            }
+        }
+    }
+
+    ratings
+}
+
+fn main() {
+    let options = match parse_args() {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(2);
+        }
+    };
+
+    let mut pairings = Vec::new();
+    for first in 0..options.engines.len() {
+        for second in first + 1..options.engines.len() {
+            if options.mode == Mode::RoundRobin || first == 0 {
+                pairings.push((first, second));
+            }
+        }
+    }
+
+    println!("seed {}", options.seed);
+    let mut rng = StdRng::seed_from_u64(options.seed);
+    let mut totals: HashMap<(usize, usize), Score> = HashMap::new();
+
+    for game_type in &options.game_types {
+        let board_sizes = if options.board_sizes.is_empty() {
+            // same defaults as the CLI and the web pages
+            match game_type {
+                GameType::Connect4 => vec![BoardSize::SevenBySix],
+                GameType::TOOTandOTTO => vec![BoardSize::SixByFour],
+            }
+        } else {
+            options.board_sizes.clone()
+        };
+
+        for board_size in board_sizes {
+            println!("\n{} on {}", game_type.to_string(), board_size.to_string());
+            for &(first, second) in &pairings {
+                let (first_engine, second_engine) = (&options.engines[first], &options.engines[second]);
+                let (score, sprt_result) = play_pairing(&options, first_engine, second_engine, game_type, board_size, &mut rng);
+                println!(
+                    "  {} vs {}: {} {}{}",
+                    first_engine.name,
+                    second_engine.name,
+                    format_score(&score),
+                    format_elo(&score),
+                    sprt_result.map(|result| format!(" - SPRT {}", result)).unwrap_or_default()
+                );
+                totals.entry((first, second)).or_default().add(&score);
+            }
+        }
+    }
+
+    println!("\nAll games");
+    for &(first, second) in &pairings {
+        let score = totals[&(first, second)];
+        println!(
+            "  {} vs {}: {} {}",
+            options.engines[first].name,
+            options.engines[second].name,
+            format_score(&score),
+            format_elo(&score)
+        );
+    }
+
+    println!("\nRatings relative to {}", options.engines[0].name);
+    let ratings = estimate_ratings(options.engines.len(), &totals);
+    let mut order: Vec<usize> = (0..options.engines.len()).collect();
+    order.sort_by(|&a, &b| ratings[b].partial_cmp(&ratings[a]).unwrap_or(std::cmp::Ordering::Equal));
+    for engine in order {
+        println!("  {:<12} {:+.0}", options.engines[engine].name, ratings[engine]);
+    }
+}
@@ -0,0 +1,153 @@
+// Exports the search tree of one AI move, to see why it played what it did, see model::trace
+//
+// The position is given as the moves played so far (see model::notation): "3 3 4" for
+// Connect 4 and "3T 2O" for TOOT-OTTO. The JSON has one object per node, the DOT file
+// renders with Graphviz (dot -Tsvg trace.dot -o trace.svg).
+//
+// usage (from the model folder):
+//     cargo run --release --bin trace -- [--game connect4|toot-otto] [--size "7 x 6"]
+//         [--moves "3 3 4"] [--difficulty hard|--strength N] [--seed N]
+//         [--max-depth 3] [--max-nodes 5000] [--format json|dot] [--output FILE]
+use std::fs;
+use model::ai::{Connect4AI, Difficulty, SearchAI, TootOttoAI, random_seed};
+use model::board_size::BoardSize;
+use model::disc::DiscType;
+use model::game::{BoardGame, GameType};
+use model::notation::parse_moves;
+use model::trace::SearchTrace;
+
+enum Format {
+    Json,
+    Dot,
+}
+
+struct Options {
+    game_type: GameType,
+    board_size: BoardSize,
+    moves: String,
+    difficulty: Difficulty,
+    strength: Option<u8>,
+    seed: u64,
+    max_depth: usize,
+    max_nodes: usize,
+    format: Format,
+    output: Option<String>,
+}
+
+fn parse_args() -> Result<Options, String> {
+    let mut options = Options {
+        game_type: GameType::Connect4,
+        board_size: BoardSize::SevenBySix,
+        moves: String::new(),
+        difficulty: Difficulty::Hard,
+        strength: None,
+        seed: random_seed(),
+        max_depth: 3,
+        max_nodes: 5000,
+        format: Format::Json,
+        output: None,
+    };
+
+    let mut args = std::env::args().skip(1);
+    while let Some(flag) = args.next() {
+        let value = args.next().ok_or(format!("missing value for {}", flag))?;
+        match flag.as_str() {
+            "--game" => options.game_type = value.parse()?,
+            "--size" => options.board_size = value.parse()?,
+            "--moves" => options.moves = value,
+            "--difficulty" => options.difficulty = value.parse()?,
+            "--strength" => options.strength = Some(value.parse().map_err(|_| format!("invalid strength: {}", value))?),
+            "--seed" => options.seed = value.parse().map_err(|_| format!("invalid seed: {}", value))?,
+            "--max-depth" => options.max_depth = value.parse().map_err(|_| format!("invalid depth: {}", value))?,
+            "--max-nodes" => options.max_nodes = value.parse().map_err(|_| format!("invalid node count: {}", value))?,
+            "--format" => {
+                options.format = match value.as_str() {
+                    "json" => Format::Json,
+                    "dot" => Format::Dot,
+                    _ => return Err(format!("unknown format: {}", value)),
+                }
+            },
+            "--output" => options.output = Some(value),
+            _ => return Err(format!("unknown flag: {}", flag)),
+        }
+    }
+    Ok(options)
+}
+
+fn play_moves(options: &Options) -> Result<BoardGame, String> {
+    let mut game = BoardGame::new_game(&options.game_type, options.board_size.get_row(), options.board_size.get_column(), true);
+    for game_move in parse_moves(&game, &options.moves)? {
+        game.play_move(game_move);
+    }
+    Ok(game)
+}
+
+fn trace_move(options: &Options, game: &BoardGame) -> ((usize, DiscType), Option<SearchTrace>) {
+    let rows = options.board_size.get_row();
+    let columns = options.board_size.get_column();
+    match options.game_type {
+        GameType::Connect4 => {
+            let mut ai = Connect4AI::new(rows, columns, options.difficulty, options.seed);
+            if let Some(strength) = options.strength {
+                ai.set_strength(strength);
+            }
+            ai.start_trace(options.max_depth, options.max_nodes);
+            let column = ai.find_best_move(game.clone());
+            ((column, game.get_current_disc_type()), ai.take_trace())
+        },
+        GameType::TOOTandOTTO => {
+            let mut ai = TootOttoAI::new(rows, columns, options.difficulty, options.seed);
+            if let Some(strength) = options.strength {
+                ai.set_strength(strength);
+            }
+            ai.start_trace(options.max_depth, options.max_nodes);
+            let game_move = ai.find_best_move(game.clone());
+            (game_move, ai.take_trace())
+        },
+    }
+}
+
+fn main() {
+    let options = match parse_args() {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(2);
+        }
+    };
+    let game = match play_moves(&options) {
+        Ok(game) => game,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(2);
+        }
+    };
+
+    let ((column, disc_type), trace) = trace_move(&options, &game);
+    let trace = trace.unwrap_or_else(|| SearchTrace::new(options.max_depth, options.max_nodes));
+    eprintln!(
+        "seed {}: the AI plays column {} ({}), {} nodes recorded{}",
+        options.seed,
+        column,
+        disc_type,
+        trace.nodes.len(),
+        if trace.truncated { ", truncated" } else { "" }
+    );
+    if trace.nodes.is_empty() {
+        eprintln!("nothing was searched, Insane plays this board size from the solution table");
+    }
+
+    let text = match options.format {
+        Format::Json => trace.to_json(),
+        Format::Dot => trace.to_dot(),
+    };
+    match &options.output {
+        Some(output) => {
+            if let Err(err) = fs::write(output, text) {
+                eprintln!("failed to write {}: {}", output, err);
+                std::process::exit(1);
+            }
+        },
+        None => print!("{}", text),
+    }
+}
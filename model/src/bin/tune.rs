@@ -0,0 +1,212 @@
+// Self-play tuning of the evaluation weights in model::eval_params::EvalParams
+//
+// A candidate is made by nudging one weight of the current best parameters, then the two
+// play a match against each other. The candidate replaces the best one if it scores well
+// enough, and the result is written to the params file the AI embeds (model/params/).
+// The reward of a won position isn't tuned: wins are only ever compared with other wins,
+// so any value well above the chain weights plays the same moves.
+//
+// usage (from the model folder):
+//     cargo run --release --bin tune -- [--game connect4|toot-otto] [--size "7 x 6"]
+//         [--difficulty hard] [--iterations 30] [--games 20] [--seed N] [--output FILE]
+//         [--threads N]   (needs --features parallel to use more than 1 core)
+use std::fs;
+use rand::prelude::*;
+use rand::rngs::StdRng;
+use model::ai::{Connect4AI, Difficulty, SearchAI, TootOttoAI, random_seed};
+use model::board_size::BoardSize;
+use model::eval_params::EvalParams;
+use model::game::{BoardGame, GameType};
+use model::parallel::available_threads;
+use model::selfplay::{GameAI, play_game, random_opening};
+
+// score the candidate needs in a match to replace the current best parameters
+const ACCEPT_SCORE: f64 = 0.55;
+// random moves at the start of every pair of games
+const OPENING_PLIES: usize = 2;
+
+struct Options {
+    game_type: GameType,
+    board_size: BoardSize,
+    difficulty: Difficulty,
+    iterations: usize,
+    games: usize,
+    seed: u64,
+    output: String,
+    threads: usize,
+}
+
+fn parse_args() -> Result<Options, String> {
+    let mut options = Options {
+        game_type: GameType::Connect4,
+        board_size: BoardSize::SevenBySix,
+        difficulty: Difficulty::Hard,
+        iterations: 30,
+        games: 20,
+        seed: random_seed(),
+        output: String::new(),
+        threads: available_threads(),
+    };
+    let mut board_size = None;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(flag) = args.next() {
+        let value = args.next().ok_or(format!("missing value for {}", flag))?;
+        match flag.as_str() {
+            "--game" => options.game_type = value.parse()?,
+            "--size" => board_size = Some(value.parse()?),
+            "--difficulty" => options.difficulty = value.parse()?,
+            "--iterations" => options.iterations = value.parse().map_err(|_| format!("invalid iterations: {}", value))?,
+            "--games" => options.games = value.parse().map_err(|_| format!("invalid games: {}", value))?,
+            "--seed" => options.seed = value.parse().map_err(|_| format!("invalid seed: {}", value))?,
+            "--output" => options.output = value,
+            "--threads" => options.threads = value.parse().map_err(|_| format!("invalid threads: {}", value))?,
+            _ => return Err(format!("unknown flag: {}", flag)),
+        }
+    }
+
+    // same defaults as the CLI and the web pages
+    options.board_size = board_size.unwrap_or(match options.game_type {
+        GameType::Connect4 => BoardSize::SevenBySix,
+        GameType::TOOTandOTTO => BoardSize::SixByFour,
+    });
+    if options.output.is_empty() {
+        options.output = match options.game_type {
+            GameType::Connect4 => String::from("params/connect4.txt"),
+            GameType::TOOTandOTTO => String::from("params/toot_otto.txt"),
+        };
+    }
+    if options.games < 2 {
+        return Err(String::from("a match needs at least 2 games"));
+    }
+
+    Ok(options)
+}
+
+fn new_ai(options: &Options, params: EvalParams, seed: u64) -> Box<dyn GameAI> {
+    let rows = options.board_size.get_row();
+    let columns = options.board_size.get_column();
+    match options.game_type {
+        GameType::Connect4 => {
+            let mut ai = Connect4AI::new(rows, columns, options.difficulty, seed);
+            ai.set_eval_params(params);
+            ai.set_threads(options.threads);
+            Box::new(ai)
+        },
+        GameType::TOOTandOTTO => {
+            let mut ai = TootOttoAI::new(rows, columns, options.difficulty, seed);
+            ai.set_eval_params(params);
+            ai.set_threads(options.threads);
+            Box::new(ai)
+        }
+    }
+}
+
+// change one chain weight or the depth penalty by 10% to 50% either way, chain weights have
+// to keep growing with the chain length
+fn perturb(params: &EvalParams, rng: &mut StdRng) -> EvalParams {
+    loop {
+        let mut candidate = *params;
+        let factor = 1.0 + rng.gen_range(0.1, 0.5) * if rng.gen::<bool>() { 1.0 } else { -1.0 };
+        let index = rng.gen_range(1, candidate.chain_weights.len() + 1);
+        let weight = if index < candidate.chain_weights.len() {
+            &mut candidate.chain_weights[index]
+        } else {
+            &mut candidate.depth_penalty
+        };
+        let changed = ((*weight as f64) * factor).round() as i64;
+        // make sure small weights can still move
+        *weight = if changed == *weight { *weight + factor.signum() as i64 } else { changed };
+
+        let increasing = candidate.chain_weights.windows(2).all(|pair| pair[0] < pair[1]);
+        if increasing && candidate.depth_penalty >= 0 && candidate != *params {
+            return candidate;
+        }
+    }
+}
+
+// the candidate's score, 1 for a win and 0.5 for a draw, every opening is played from both sides
+fn play_match(options: &Options, candidate: &EvalParams, best: &EvalParams, rng: &mut StdRng) -> f64 {
+    let rows = options.board_size.get_row();
+    let columns = options.board_size.get_column();
+    let mut score = 0.0;
+
+    for _ in 0..options.games / 2 {
+        let game = BoardGame::new_game(&options.game_type, rows, columns, true);
+        let opening = random_opening(&game, OPENING_PLIES, rng);
+        for candidate_player in 1..=2 {
+            let mut candidate_ai = new_ai(options, *candidate, rng.gen());
+            let mut best_ai = new_ai(options, *best, rng.gen());
+            let record = if candidate_player == 1 {
+                play_game(game.clone(), &opening, candidate_ai.as_mut(), best_ai.as_mut())
+            } else {
+                play_game(game.clone(), &opening, best_ai.as_mut(), candidate_ai.as_mut())
+            };
+
+            if record.winner == candidate_player {
+                score += 1.0;
+            } else if record.winner == 0 {
+                score += 0.5;
+            }
+        }
+    }
+
+    score / (options.games / 2 * 2) as f64
+}
+
+fn write_params(options: &Options, params: &EvalParams) {
+    let text = format!(
+        "# evaluation weights for {} written by the tune binary, see model/src/eval_params.rs\n\
+         # board size {}, difficulty {}, seed {}\n{}",
+        options.game_type.to_string(),
+        options.board_size.to_string(),
+        options.difficulty.to_string(),
+        options.seed,
+        params
+    );
+    match fs::write(&options.output, text) {
+        Ok(_) => println!("parameters written to {}", options.output),
+        Err(err) => eprintln!("failed to write {}: {}", options.output, err),
+    }
+}
+
+fn main() {
+    let options = match parse_args() {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(2);
+        }
+    };
+    println!(
+        "tuning {} on {} at {} difficulty, seed {}",
+        options.game_type.to_string(),
+        options.board_size.to_string(),
+        options.difficulty.to_string(),
+        options.seed
+    );
+
+    let mut rng = StdRng::seed_from_u64(options.seed);
+    let mut best = EvalParams::tuned(&options.game_type);
+    print!("starting from:\n{}", best);
+
+    for iteration in 1..=options.iterations {
+        let candidate = perturb(&best, &mut rng);
+        let score = play_match(&options, &candidate, &best, &mut rng);
+        let accepted = score >= ACCEPT_SCORE;
+        println!(
+            "iteration {}/{}: candidate scored {:.1}%{}",
+            iteration,
+            options.iterations,
+            score * 100.0,
+            if accepted { " - accepted" } else { "" }
+        );
+        if accepted {
+            best = candidate;
+            print!("{}", best);
+            write_params(&options, &best);
+        }
+    }
+
+    print!("final parameters:\n{}", best);
+}
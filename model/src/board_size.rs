@@ -1,4 +1,6 @@
 
+use std::str::FromStr;
+
 // common board sizes from wikipedia page: https://en.wikipedia.org/wiki/Connect_Four
 // its in the form of "# columns" By "# of rows"
 #[derive(PartialEq, Copy, Clone, Debug)]
@@ -67,4 +69,16 @@ impl BoardSize {
             BoardSize::EightByEight
         ]
     }
-}
\ No newline at end of file
+}
+
+// accepts the same "7 x 6" form as to_string(), with or without the spaces
+impl FromStr for BoardSize {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let wanted: String = s.chars().filter(|c| !c.is_whitespace()).collect::<String>().to_lowercase();
+        BoardSize::to_vec().into_iter()
+            .find(|size| size.to_string().replace(' ', "") == wanted)
+            .ok_or(format!("unknown board size: {}", s))
+    }
+}
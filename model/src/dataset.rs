@@ -0,0 +1,315 @@
+// Positions of self-play games with how the game ended and the AI's evaluation, for training
+// and testing evaluation functions. Made by the dataset tool (bin/dataset.rs).
+//
+// The same records are written as JSON lines, one position per line:
+//
+//     {"game": 0, "type": "TOOT-OTTO", "rows": 4, "columns": 6, "ply": 3, "player": 2,
+//      "board": "......|......|......|..TO.T", "move": "2O", "result": 1,
+//      "score": -15, "best": "2O", "outcome": null}
+//
+// and as a binary file: the bytes "CTDS" and the format version, then the records one after
+// another, little endian:
+//
+//     u32 game, u8 type (0 Connect 4, 1 TOOT-OTTO), u8 rows, u8 columns, u8 ply, u8 player,
+//     the cells, i8 result, i32 score, u8 move, u8 best move, i8 outcome
+//
+// The board is row by row from the top, "." for an empty cell in the text form and 2 bits a
+// cell in the binary one, 4 to a byte (0 empty, 1 red or T, 2 yellow or O). A move is its
+// column, plus 128 for an O. `result` is how the game ended for the player to move, 1 for a
+// win, 0 a draw and -1 a loss. `score` and `best` are the AI's best move there, with its
+// proven outcome (the same values as result, null or 2 when it isn't proven).
+use std::convert::TryInto;
+use std::io::{self, Read, Write};
+use crate::ai::{MoveEvaluation, MoveOutcome};
+use crate::disc::DiscType;
+use crate::game::{BoardGame, GameType};
+use crate::notation::format_move;
+
+pub const MAGIC: &[u8; 4] = b"CTDS";
+pub const VERSION: u8 = 1;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Position {
+    pub game: u32,
+    pub game_type: GameType,
+    pub board: Vec<Vec<DiscType>>, // top row first, like Board
+    pub ply: usize,                // moves played before this position
+    pub player: usize,             // to move
+    pub game_move: (usize, DiscType), // played here
+    pub result: i8,
+    pub score: i64,
+    pub best_move: (usize, DiscType),
+    pub outcome: Option<MoveOutcome>,
+}
+
+// every position of a finished game before each of its moves, `analyze` scores every move of
+// a position best first, like SearchAI::analyze()
+pub fn game_positions(
+    game_id: u32,
+    mut game: BoardGame,
+    moves: &[(usize, DiscType)],
+    winner: usize,
+    analyze: &mut dyn FnMut(&BoardGame) -> Vec<MoveEvaluation>,
+) -> Vec<Position> {
+    let mut positions = Vec::new();
+    for (ply, game_move) in moves.iter().enumerate() {
+        let evaluations = analyze(&game);
+        let best = &evaluations[0];
+        positions.push(Position {
+            game: game_id,
+            game_type: game.game_type.clone(),
+            board: game.game_board.board.clone(),
+            ply,
+            player: game.current_player,
+            game_move: *game_move,
+            result: match winner {
+                0 => 0,
+                winner if winner == game.current_player => 1,
+                _ => -1,
+            },
+            score: best.score,
+            best_move: (best.column, best.disc_type),
+            outcome: best.outcome,
+        });
+        if game.play_move(*game_move).is_some() {
+            break;
+        }
+    }
+    positions
+}
+
+fn outcome_value(outcome: Option<MoveOutcome>) -> i8 {
+    match outcome {
+        Some(MoveOutcome::Win) => 1,
+        Some(MoveOutcome::Draw) => 0,
+        Some(MoveOutcome::Loss) => -1,
+        None => 2,
+    }
+}
+
+fn cell_code(disc_type: DiscType) -> u8 {
+    match disc_type {
+        DiscType::Empty => 0,
+        DiscType::Red | DiscType::T => 1,
+        DiscType::Yellow | DiscType::O => 2,
+    }
+}
+
+fn cell_disc(game_type: &GameType, code: u8) -> io::Result<DiscType> {
+    match (code, game_type) {
+        (0, _) => Ok(DiscType::Empty),
+        (1, GameType::Connect4) => Ok(DiscType::Red),
+        (2, GameType::Connect4) => Ok(DiscType::Yellow),
+        (1, GameType::TOOTandOTTO) => Ok(DiscType::T),
+        (2, GameType::TOOTandOTTO) => Ok(DiscType::O),
+        _ => Err(invalid("invalid cell")),
+    }
+}
+
+fn move_byte((column, disc_type): (usize, DiscType)) -> u8 {
+    column as u8 | if disc_type == DiscType::O { 128 } else { 0 }
+}
+
+// in Connect 4 the disc comes from the player who moved
+fn byte_move(game_type: &GameType, player: usize, byte: u8) -> (usize, DiscType) {
+    let disc_type = match game_type {
+        GameType::Connect4 => if player == 1 { DiscType::Red } else { DiscType::Yellow },
+        GameType::TOOTandOTTO => if byte & 128 != 0 { DiscType::O } else { DiscType::T },
+    };
+    ((byte & 127) as usize, disc_type)
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+pub fn write_header(out: &mut dyn Write) -> io::Result<()> {
+    out.write_all(MAGIC)?;
+    out.write_all(&[VERSION])
+}
+
+pub fn read_header(input: &mut dyn Read) -> io::Result<()> {
+    let mut header = [0; 5];
+    input.read_exact(&mut header)?;
+    if &header[..4] != MAGIC {
+        return Err(invalid("not a dataset file"));
+    }
+    if header[4] != VERSION {
+        return Err(invalid(&format!("dataset version {} is not supported", header[4])));
+    }
+    Ok(())
+}
+
+impl Position {
+    pub fn to_json(&self) -> String {
+        let rows: Vec<String> = self.board.iter()
+            .map(|row| row.iter().map(|disc_type| match disc_type {
+                DiscType::Empty => String::from("."),
+                disc_type => disc_type.to_string(),
+            }).collect())
+            .collect();
+        let outcome = match outcome_value(self.outcome) {
+            2 => String::from("null"),
+            outcome => outcome.to_string(),
+        };
+        format!(
+            "{{\"game\": {}, \"type\": \"{}\", \"rows\": {}, \"columns\": {}, \"ply\": {}, \"player\": {}, \"board\": \"{}\", \
+             \"move\": \"{}\", \"result\": {}, \"score\": {}, \"best\": \"{}\", \"outcome\": {}}}",
+            self.game,
+            self.game_type.to_string(),
+            self.board.len(),
+            self.board[0].len(),
+            self.ply,
+            self.player,
+            rows.join("|"),
+            format_move(&self.game_type, self.game_move),
+            self.result,
+            self.score,
+            format_move(&self.game_type, self.best_move),
+            outcome
+        )
+    }
+
+    pub fn write_to(&self, out: &mut dyn Write) -> io::Result<()> {
+        let game_type = match self.game_type {
+            GameType::Connect4 => 0,
+            GameType::TOOTandOTTO => 1,
+        };
+        out.write_all(&self.game.to_le_bytes())?;
+        out.write_all(&[game_type, self.board.len() as u8, self.board[0].len() as u8, self.ply as u8, self.player as u8])?;
+        let cells: Vec<u8> = self.board.iter().flatten().map(|disc_type| cell_code(*disc_type)).collect();
+        let packed: Vec<u8> = cells.chunks(4)
+            .map(|chunk| chunk.iter().enumerate().fold(0, |byte, (index, code)| byte | code << (2 * index)))
+            .collect();
+        out.write_all(&packed)?;
+        out.write_all(&[self.result as u8])?;
+        // win and loss scores are around a million, far inside an i32
+        let score = self.score.max(i32::MIN as i64).min(i32::MAX as i64) as i32;
+        out.write_all(&score.to_le_bytes())?;
+        out.write_all(&[move_byte(self.game_move), move_byte(self.best_move), outcome_value(self.outcome) as u8])
+    }
+
+    // the next record, None at the end of the file
+    pub fn read_from(input: &mut dyn Read) -> io::Result<Option<Position>> {
+        // the end of the file only between records, not partway through the game id
+        let mut game = [0; 4];
+        let mut read = 0;
+        while read < game.len() {
+            match input.read(&mut game[read..]) {
+                Ok(0) if read == 0 => return Ok(None),
+                Ok(0) => return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "record cut short")),
+                Ok(count) => read += count,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => (),
+                Err(err) => return Err(err),
+            }
+        }
+        let mut fields = [0; 5];
+        input.read_exact(&mut fields)?;
+        let game_type = match fields[0] {
+            0 => GameType::Connect4,
+            1 => GameType::TOOTandOTTO,
+            _ => return Err(invalid("invalid game type")),
+        };
+        let (rows, columns) = (fields[1] as usize, fields[2] as usize);
+        let player = fields[4] as usize;
+
+        let mut packed = vec![0; (rows * columns + 3) / 4];
+        input.read_exact(&mut packed)?;
+        let mut board = vec![vec![DiscType::Empty; columns]; rows];
+        for cell in 0..rows * columns {
+            let code = packed[cell / 4] >> (2 * (cell % 4)) & 3;
+            board[cell / columns][cell % columns] = cell_disc(&game_type, code)?;
+        }
+
+        let mut rest = [0; 8];
+        input.read_exact(&mut rest)?;
+        let outcome = match rest[7] as i8 {
+            1 => Some(MoveOutcome::Win),
+            0 => Some(MoveOutcome::Draw),
+            -1 => Some(MoveOutcome::Loss),
+            _ => None,
+        };
+        Ok(Some(Position {
+            game: u32::from_le_bytes(game),
+            game_type: game_type.clone(),
+            board,
+            ply: fields[3] as usize,
+            player,
+            game_move: byte_move(&game_type, player, rest[5]),
+            result: rest[0] as i8,
+            score: i32::from_le_bytes(rest[1..5].try_into().unwrap()) as i64,
+            best_move: byte_move(&game_type, player, rest[6]),
+            outcome,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn toot_otto() -> Position {
+        // 3 x 5, 15 cells so the last byte is only partly used
+        let mut board = vec![vec![DiscType::Empty; 5]; 3];
+        board[2][0] = DiscType::T;
+        board[2][1] = DiscType::O;
+        board[2][4] = DiscType::O;
+        board[1][4] = DiscType::T;
+        Position {
+            game: 70000,
+            game_type: GameType::TOOTandOTTO,
+            board,
+            ply: 4,
+            player: 1,
+            game_move: (3, DiscType::O),
+            result: -1,
+            score: -1000000,
+            best_move: (4, DiscType::O),
+            outcome: Some(MoveOutcome::Loss),
+        }
+    }
+
+    fn connect4() -> Position {
+        let mut board = vec![vec![DiscType::Empty; 7]; 6];
+        board[5][3] = DiscType::Red;
+        board[4][3] = DiscType::Yellow;
+        Position {
+            game: 1,
+            game_type: GameType::Connect4,
+            board,
+            ply: 2,
+            player: 1,
+            game_move: (2, DiscType::Red),
+            result: 0,
+            score: 12,
+            best_move: (3, DiscType::Red),
+            outcome: None,
+        }
+    }
+
+    #[test]
+    fn write_and_read_back() {
+        let positions = vec![toot_otto(), connect4()];
+        let mut bytes = Vec::new();
+        write_header(&mut bytes).unwrap();
+        for position in &positions {
+            position.write_to(&mut bytes).unwrap();
+        }
+        let mut input = &bytes[..];
+        read_header(&mut input).unwrap();
+        let mut read = Vec::new();
+        while let Some(position) = Position::read_from(&mut input).unwrap() {
+            read.push(position);
+        }
+        assert_eq!(read, positions);
+    }
+
+    #[test]
+    fn cut_short() {
+        let mut bytes = Vec::new();
+        connect4().write_to(&mut bytes).unwrap();
+        assert!(Position::read_from(&mut &bytes[..2]).is_err());
+        assert!(Position::read_from(&mut &bytes[..bytes.len() - 1]).is_err());
+        assert_eq!(Position::read_from(&mut &bytes[..0]).unwrap(), None);
+    }
+}
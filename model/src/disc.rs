@@ -1,4 +1,5 @@
 use std::fmt::Display;
+use std::str::FromStr;
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum DiscType {
@@ -20,3 +21,19 @@ impl Display for DiscType {
         }
     }
 }
+
+// inverse of Display, so a board can be sent around as text
+impl FromStr for DiscType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "R" => Ok(DiscType::Red),
+            "Y" => Ok(DiscType::Yellow),
+            "T" => Ok(DiscType::T),
+            "O" => Ok(DiscType::O),
+            "E" => Ok(DiscType::Empty),
+            _ => Err(format!("unknown disc type: {}", s)),
+        }
+    }
+}
@@ -0,0 +1,363 @@
+// Line based engine protocol, so other programs can drive the AI over stdin and stdout
+//
+// Run with `model engine`. Modelled on the UCI protocol of chess engines: the program sends
+// one command per line and the engine answers with lines of its own.
+//
+//     hello                          id lines, the options, then hellook
+//     isready                        readyok
+//     setoption name seed value 42   also threads, see hello for the options
+//     newgame [connect4|toot-otto] [7x6]  an empty board, Connect 4 on 7 x 6 by default
+//     position [startpos] [moves 3 3 4]   the position after these moves on the empty board
+//     go [depth N] [movetime MS] [infinite]
+//     stop                           ends the search, the engine answers bestmove right away
+//     show                           the board, as info string lines
+//     quit
+//
+// `go` searches 1 move ahead, then 2 and so on, and reports every finished depth with
+// "info depth D score S time MS pv MOVES", adding "outcome win|draw|loss" once the result
+// is proven. It ends with "bestmove MOVE" (or "bestmove none" when the game is over) from
+// the deepest finished depth. Without a depth it keeps going until the movetime runs out,
+// `stop` is sent or the board is full. Moves are written as in model::notation and scores
+// are for the player to move. Mistakes are answered with "info string error: ...".
+use std::io::{stdin, stdout, BufRead, Write};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use model::ai::{Connect4AI, Difficulty, MoveOutcome, SearchAI, TootOttoAI, random_seed};
+use model::board_size::BoardSize;
+use model::disc::DiscType;
+use model::game::{BoardGame, GameType};
+use model::notation::{format_move, format_moves, parse_moves};
+
+// writes one line of the engine's answers, to stdout except in the tests
+type Say = Arc<dyn Fn(&str) + Send + Sync>;
+
+fn say_stdout(line: &str) {
+    let mut out = stdout();
+    writeln!(out, "{}", line).ok();
+    out.flush().ok();
+}
+
+#[derive(Clone)]
+struct Settings {
+    game_type: GameType,
+    board_size: BoardSize,
+    seed: u64,
+    threads: usize,
+}
+
+struct Search {
+    stop: Arc<AtomicBool>,
+    handle: JoinHandle<()>,
+}
+
+struct Engine {
+    settings: Settings,
+    moves: Vec<(usize, DiscType)>,
+    search: Option<Search>,
+    say: Say,
+}
+
+impl Engine {
+    fn new(say: Say) -> Self {
+        Self {
+            settings: Settings {
+                game_type: GameType::Connect4,
+                board_size: BoardSize::SevenBySix,
+                seed: random_seed(),
+                threads: 1,
+            },
+            moves: Vec::new(),
+            search: None,
+            say,
+        }
+    }
+
+    fn empty_game(&self) -> BoardGame {
+        let board_size = self.settings.board_size;
+        BoardGame::new_game(&self.settings.game_type, board_size.get_row(), board_size.get_column(), true)
+    }
+
+    fn game(&self) -> BoardGame {
+        let mut game = self.empty_game();
+        for game_move in &self.moves {
+            game.play_move(*game_move);
+        }
+        game
+    }
+
+    // ends the search in progress, which answers with its bestmove
+    fn stop_search(&mut self) {
+        if let Some(search) = self.search.take() {
+            search.stop.store(true, Ordering::Relaxed);
+            search.handle.join().ok();
+        }
+    }
+
+    // false to quit
+    fn handle(&mut self, line: &str) -> Result<bool, String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let command = match words.first() {
+            Some(command) => *command,
+            None => return Ok(true),
+        };
+        // nothing changes under a running search
+        if command != "isready" && command != "show" {
+            self.stop_search();
+        }
+        match command {
+            "hello" => self.hello(),
+            "isready" => (self.say)("readyok"),
+            "setoption" => self.set_option(&words[1..])?,
+            "newgame" => self.new_game(&words[1..])?,
+            "position" => self.position(&words[1..])?,
+            "go" => self.go(&words[1..])?,
+            "stop" => (),
+            "show" => {
+                for row in &self.game().game_board.board {
+                    let cells: Vec<String> = row.iter().map(|disc_type| disc_type.to_string()).collect();
+                    (self.say)(&format!("info string {}", cells.join(" ")));
+                }
+            },
+            "quit" => return Ok(false),
+            _ => return Err(format!("unknown command: {}", command)),
+        }
+        Ok(true)
+    }
+
+    fn hello(&self) {
+        let say = &self.say;
+        say("id name Connect 4 and TOOT-OTTO engine");
+        say("id games connect4 toot-otto");
+        let sizes: Vec<String> = BoardSize::to_vec().iter().map(|size| size.to_string().replace(' ', "")).collect();
+        say(&format!("id sizes {}", sizes.join(" ")));
+        say("option name seed type spin default random");
+        say("option name threads type spin default 1 min 1");
+        say("hellook");
+    }
+
+    fn set_option(&mut self, words: &[&str]) -> Result<(), String> {
+        let (name, value) = match words {
+            ["name", name, "value", value] => (*name, *value),
+            _ => return Err(String::from("setoption name NAME value VALUE")),
+        };
+        let invalid = |_| format!("invalid value for {}: {}", name, value);
+        match name.to_lowercase().as_str() {
+            "seed" => self.settings.seed = value.parse().map_err(invalid)?,
+            "threads" => self.settings.threads = value.parse::<usize>().map_err(invalid)?.max(1),
+            _ => return Err(format!("unknown option: {}", name)),
+        }
+        Ok(())
+    }
+
+    fn new_game(&mut self, words: &[&str]) -> Result<(), String> {
+        let mut settings = self.settings.clone();
+        settings.game_type = GameType::Connect4;
+        settings.board_size = BoardSize::SevenBySix;
+        for word in words {
+            if let Ok(game_type) = word.parse() {
+                settings.game_type = game_type;
+            } else {
+                settings.board_size = word.parse()?;
+            }
+        }
+        self.settings = settings;
+        self.moves.clear();
+        Ok(())
+    }
+
+    fn position(&mut self, words: &[&str]) -> Result<(), String> {
+        let words = match words.first() {
+            Some(&"startpos") => &words[1..],
+            _ => words,
+        };
+        let moves = match words.first() {
+            Some(&"moves") => words[1..].join(" "),
+            None => String::new(),
+            Some(word) => return Err(format!("expected moves, not {}", word)),
+        };
+        self.moves = parse_moves(&self.empty_game(), &moves)?;
+        Ok(())
+    }
+
+    fn go(&mut self, words: &[&str]) -> Result<(), String> {
+        let game = self.game();
+        let mut max_depth = game.game_board.board_rows * game.game_board.board_columns - self.moves.len();
+        let mut movetime = None;
+        let mut words = words.iter();
+        while let Some(word) = words.next() {
+            match *word {
+                "infinite" => (),
+                "depth" | "movetime" => {
+                    let value: u64 = words.next()
+                        .and_then(|value| value.parse().ok())
+                        .ok_or(format!("{} needs a number", word))?;
+                    if *word == "depth" {
+                        max_depth = max_depth.min(value.max(1) as usize);
+                    } else {
+                        movetime = Some(Duration::from_millis(value));
+                    }
+                },
+                _ => return Err(format!("unknown go limit: {}", word)),
+            }
+        }
+
+        let stop = Arc::new(AtomicBool::new(false));
+        if let Some(movetime) = movetime {
+            let stop = stop.clone();
+            thread::spawn(move || {
+                thread::sleep(movetime);
+                stop.store(true, Ordering::Relaxed);
+            });
+        }
+        let settings = self.settings.clone();
+        let search_stop = stop.clone();
+        let say = self.say.clone();
+        let handle = thread::spawn(move || search(game, settings, max_depth, search_stop, say));
+        self.search = Some(Search { stop, handle });
+        Ok(())
+    }
+}
+
+fn searcher(settings: &Settings) -> Box<dyn SearchAI> {
+    let rows = settings.board_size.get_row();
+    let columns = settings.board_size.get_column();
+    let mut ai: Box<dyn SearchAI> = match settings.game_type {
+        GameType::Connect4 => Box::new(Connect4AI::new(rows, columns, Difficulty::Hard, settings.seed)),
+        GameType::TOOTandOTTO => Box::new(TootOttoAI::new(rows, columns, Difficulty::Hard, settings.seed)),
+    };
+    ai.set_threads(settings.threads);
+    ai
+}
+
+// iterative deepening, runs on its own thread until `stop` is set or `max_depth` is done
+fn search(game: BoardGame, settings: Settings, max_depth: usize, stop: Arc<AtomicBool>, say: Say) {
+    let start = Instant::now();
+    let mut searcher = searcher(&settings);
+    let mut best = None;
+    for depth in 1..=max_depth {
+        searcher.set_depth(Some(depth));
+        // depth 1 always finishes, so there is a move to answer with
+        if depth == 2 {
+            searcher.set_stop(stop.clone());
+        }
+        let evaluations = searcher.analyze(&game);
+        if depth > 1 && stop.load(Ordering::Relaxed) {
+            break; // the unfinished depth is thrown away
+        }
+        let first = match evaluations.first() {
+            Some(first) => first,
+            None => break, // the game is over
+        };
+        best = Some((first.column, first.disc_type));
+        let outcome = match first.outcome {
+            Some(MoveOutcome::Win) => " outcome win",
+            Some(MoveOutcome::Draw) => " outcome draw",
+            Some(MoveOutcome::Loss) => " outcome loss",
+            None => "",
+        };
+        say(&format!(
+            "info depth {} score {}{} time {} pv {}",
+            depth,
+            first.score,
+            outcome,
+            start.elapsed().as_millis(),
+            format_moves(&settings.game_type, &first.principal_variation)
+        ));
+        // a shorter win can't be found deeper
+        if first.outcome == Some(MoveOutcome::Win) {
+            break;
+        }
+    }
+    match best {
+        Some(game_move) => say(&format!("bestmove {}", format_move(&settings.game_type, game_move))),
+        None => say("bestmove none"),
+    }
+}
+
+// the exit code of `model engine`
+pub fn run() -> i32 {
+    let mut engine = Engine::new(Arc::new(say_stdout));
+    let input = stdin();
+    for line in input.lock().lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+        match engine.handle(&line) {
+            Ok(true) => (),
+            Ok(false) => break,
+            Err(err) => (engine.say)(&format!("info string error: {}", err)),
+        }
+    }
+    engine.stop_search();
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    fn engine() -> (Engine, Arc<Mutex<Vec<String>>>) {
+        let lines = Arc::new(Mutex::new(Vec::new()));
+        let said = lines.clone();
+        let mut engine = Engine::new(Arc::new(move |line: &str| said.lock().unwrap().push(line.to_string())));
+        engine.handle("setoption name seed value 1").unwrap();
+        (engine, lines)
+    }
+
+    // lets the search finish on its own, where stop_search() would end it
+    fn wait(engine: &mut Engine) {
+        if let Some(search) = engine.search.take() {
+            search.handle.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn go_to_a_depth() {
+        let (mut engine, lines) = engine();
+        engine.handle("position startpos moves 3 3").unwrap();
+        engine.handle("go depth 3").unwrap();
+        wait(&mut engine);
+        let lines = lines.lock().unwrap();
+        assert_eq!(lines.len(), 4);
+        for (depth, line) in lines.iter().take(3).enumerate() {
+            assert!(line.starts_with(&format!("info depth {} score ", depth + 1)), "{}", line);
+        }
+        assert!(lines[3].starts_with("bestmove "), "{}", lines[3]);
+    }
+
+    #[test]
+    fn takes_the_win() {
+        let (mut engine, lines) = engine();
+        engine.handle("position moves 0 1 0 1 0 1").unwrap();
+        engine.handle("go depth 4").unwrap();
+        wait(&mut engine);
+        let lines = lines.lock().unwrap();
+        // no deeper search after a win in one
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("info depth 1 score ") && lines[0].contains(" outcome win "), "{}", lines[0]);
+        assert_eq!(lines[1], "bestmove 0");
+    }
+
+    #[test]
+    fn stop_answers_right_away() {
+        let (mut engine, lines) = engine();
+        engine.handle("go infinite").unwrap();
+        engine.handle("stop").unwrap();
+        assert!(engine.search.is_none());
+        let lines = lines.lock().unwrap();
+        let last = lines.last().unwrap();
+        assert!(last.starts_with("bestmove ") && last != "bestmove none", "{}", last);
+    }
+
+    #[test]
+    fn no_position_after_the_end() {
+        let (mut engine, _) = engine();
+        engine.handle("position moves 3").unwrap();
+        assert!(engine.handle("position moves 0 1 0 1 0 1 0").is_err());
+        assert_eq!(engine.moves.len(), 1);
+    }
+}
@@ -0,0 +1,83 @@
+use std::fmt::Display;
+use crate::game::GameType;
+
+// weights used by check_state(), TootOttoAI::evaluate() and the minimax value functions in ai.rs
+// the defaults are the values the AI was originally written with
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub struct EvalParams {
+    // indexed by the absolute value of a 4-cell window sum, 4 being a finished line
+    pub chain_weights: [i64; 5],
+    // score of a won position, must stay well above any sum of chain weights. Not tuned, see
+    // the tune binary.
+    pub reward: i64,
+    // subtracted as depth_penalty * depth * depth, so quicker wins are preferred
+    pub depth_penalty: i64,
+}
+
+impl Default for EvalParams {
+    fn default() -> Self {
+        Self {
+            chain_weights: [0, 1, 8, 27, 64],
+            reward: 999999,
+            depth_penalty: 1,
+        }
+    }
+}
+
+impl EvalParams {
+    // the parameters written by the tune binary, embedded so the frontend gets them too
+    pub fn tuned(game_type: &GameType) -> Self {
+        let text = match game_type {
+            GameType::Connect4 => include_str!("../params/connect4.txt"),
+            GameType::TOOTandOTTO => include_str!("../params/toot_otto.txt"),
+        };
+        EvalParams::parse(text).expect("embedded evaluation parameters are invalid")
+    }
+
+    // parse the "key = value" format written by Display, '#' starts a comment
+    // keys that are missing keep their default value
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut params = EvalParams::default();
+        for line in text.lines() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let mut parts = line.splitn(2, '=');
+            let key = parts.next().unwrap_or("").trim();
+            let value = parts.next().ok_or(format!("missing '=' in line: {}", line))?.trim();
+            match key {
+                "chain_weights" => {
+                    let weights = value.split_whitespace()
+                        .map(|weight| weight.parse::<i64>().map_err(|err| format!("invalid chain weight {}: {}", weight, err)))
+                        .collect::<Result<Vec<i64>, String>>()?;
+                    if weights.len() != params.chain_weights.len() {
+                        return Err(format!("expected {} chain weights, got {}", params.chain_weights.len(), weights.len()));
+                    }
+                    params.chain_weights.copy_from_slice(&weights);
+                },
+                "reward" => params.reward = value.parse().map_err(|err| format!("invalid reward: {}", err))?,
+                "depth_penalty" => params.depth_penalty = value.parse().map_err(|err| format!("invalid depth penalty: {}", err))?,
+                _ => return Err(format!("unknown parameter: {}", key)),
+            }
+        }
+
+        Ok(params)
+    }
+
+    // value of one 4-cell window, keeps the sign of the sum
+    pub fn chain_value(&self, sum: i64) -> i64 {
+        let weight = self.chain_weights[sum.abs() as usize];
+        if sum < 0 { -weight } else { weight }
+    }
+}
+
+impl Display for EvalParams {
+    fn fmt(&self, w: &mut std::fmt::Formatter) -> std::result::Result<(), std::fmt::Error> {
+        let weights: Vec<String> = self.chain_weights.iter().map(|weight| weight.to_string()).collect();
+        writeln!(w, "chain_weights = {}", weights.join(" "))?;
+        writeln!(w, "reward = {}", self.reward)?;
+        writeln!(w, "depth_penalty = {}", self.depth_penalty)
+    }
+}
@@ -1,4 +1,5 @@
 #![allow(non_snake_case)]
+use std::str::FromStr;
 pub use crate::disc::DiscType;
 pub use crate::player::{Player, PlayerType};
 pub use crate::board::Board;
@@ -30,6 +31,18 @@ impl ToString for GameType {
     }
 }
 
+impl FromStr for GameType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "connect-4" | "connect4" => Ok(GameType::Connect4),
+            "toot-otto" | "tootandotto" | "toot" => Ok(GameType::TOOTandOTTO),
+            _ => Err(format!("unknown game type: {}", s)),
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct BoardGame {
     pub game_board: Board,
@@ -64,6 +77,13 @@ impl BoardGame {
         }
     }
 
+    pub fn new_game(game_type: &GameType, board_rows: usize, board_columns: usize, vs_ai: bool) -> Self {
+        match game_type {
+            GameType::Connect4 => BoardGame::new_connect4(board_rows, board_columns, vs_ai),
+            GameType::TOOTandOTTO => BoardGame::new_toot_and_otto(board_rows, board_columns, vs_ai),
+        }
+    }
+
     fn switch_turn(&mut self) {
         if self.current_player == 1 {
             self.current_player = 2
@@ -93,6 +113,41 @@ impl BoardGame {
         self.player2.disc_type = disc_type;
     }
 
+    // every (column, disc) pair the current player is allowed to play
+    pub fn legal_moves(&self) -> Vec<(usize, DiscType)> {
+        let columns = self.game_board.get_valid_columns();
+        match self.game_type {
+            GameType::Connect4 => {
+                let disc_type = self.get_current_disc_type();
+                columns.into_iter().map(|column| (column, disc_type)).collect()
+            },
+            GameType::TOOTandOTTO => {
+                let mut moves = Vec::new();
+                for column in columns {
+                    moves.push((column, DiscType::T));
+                    moves.push((column, DiscType::O));
+                }
+                moves
+            }
+        }
+    }
+
+    // place the move and return the winner: Some(0) for a draw, Some(1)/Some(2) for a win, None if ongoing
+    // the turn is switched by check() when the game is still going
+    pub fn play_move(&mut self, (column, disc_type): (usize, DiscType)) -> Option<usize> {
+        let current_disc_type = self.get_current_disc_type();
+        self.change_disc_type(disc_type); // does nothing for Connect 4
+        self.place_disc(column);
+        let result = match self.check() {
+            GameEvent::Player1Win => Some(1),
+            GameEvent::Player2Win => Some(2),
+            GameEvent::Draw => Some(0),
+            _ => None,
+        };
+        self.change_disc_type(current_disc_type);
+        result
+    }
+
     pub fn place_disc(&mut self, column: usize) -> GameEvent {
         self.game_board.place_disc(column, self.get_current_disc_type())
     }
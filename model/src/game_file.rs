@@ -0,0 +1,171 @@
+// Saved games of the command line program, a small text file that describes itself:
+//
+//     # Connect 4 / TOOT-OTTO saved game
+//     game: Connect-4
+//     size: 7 x 6
+//     player1: human
+//     player2: computer
+//     difficulty: Hard
+//     seed: 42
+//     moves: 3 3 4
+//
+// The moves are from the empty board, written as in model::notation. Without a seed the
+// computer players get a random one. Lines starting with # are comments and unknown keys
+// are skipped, so a file can carry more than this version reads.
+use std::fmt::Display;
+use std::str::FromStr;
+use crate::ai::Difficulty;
+use crate::board_size::BoardSize;
+use crate::disc::DiscType;
+use crate::game::{BoardGame, GameType};
+use crate::notation::{format_moves, parse_game};
+
+#[derive(Clone, Debug)]
+pub struct GameFile {
+    pub game_type: GameType,
+    pub board_size: BoardSize,
+    pub humans: (bool, bool), // whether player 1 and player 2 are people, the others are the computer
+    pub difficulty: Difficulty,
+    pub seed: Option<u64>, // of the computer players, None for a random one
+    pub moves: Vec<(usize, DiscType)>,
+}
+
+impl GameFile {
+    pub fn empty_game(&self) -> BoardGame {
+        BoardGame::new_game(&self.game_type, self.board_size.get_row(), self.board_size.get_column(), !self.humans.1)
+    }
+
+    // the game after all the moves, and its winner if it's over (0 for a draw)
+    pub fn game(&self) -> (BoardGame, Option<usize>) {
+        let mut game = self.empty_game();
+        let mut winner = None;
+        for game_move in &self.moves {
+            winner = game.play_move(*game_move);
+        }
+        (game, winner)
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|err| format!("failed to read {}: {}", path, err))?;
+        text.parse().map_err(|err| format!("{}: {}", path, err))
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        std::fs::write(path, self.to_string()).map_err(|err| format!("failed to write {}: {}", path, err))
+    }
+}
+
+fn player_text(human: bool) -> &'static str {
+    if human { "human" } else { "computer" }
+}
+
+fn parse_player(text: &str) -> Result<bool, String> {
+    match text {
+        "human" => Ok(true),
+        "computer" => Ok(false),
+        _ => Err(format!("a player is human or computer, not {}", text)),
+    }
+}
+
+impl Display for GameFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::result::Result<(), std::fmt::Error> {
+        writeln!(f, "# Connect 4 / TOOT-OTTO saved game")?;
+        writeln!(f, "game: {}", self.game_type.to_string())?;
+        writeln!(f, "size: {}", self.board_size.to_string())?;
+        writeln!(f, "player1: {}", player_text(self.humans.0))?;
+        writeln!(f, "player2: {}", player_text(self.humans.1))?;
+        writeln!(f, "difficulty: {}", self.difficulty.to_string())?;
+        if let Some(seed) = self.seed {
+            writeln!(f, "seed: {}", seed)?;
+        }
+        writeln!(f, "moves: {}", format_moves(&self.game_type, &self.moves))
+    }
+}
+
+impl FromStr for GameFile {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut game_type = None;
+        let mut board_size = None;
+        let mut humans = (true, false);
+        let mut difficulty = Difficulty::Hard;
+        let mut seed = None;
+        let mut moves = String::new();
+        for (number, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = match line.find(':') {
+                Some(colon) => (line[..colon].trim(), line[colon + 1..].trim()),
+                None => return Err(format!("line {} is not \"key: value\"", number + 1)),
+            };
+            match key {
+                "game" => game_type = Some(value.parse::<GameType>()?),
+                "size" => board_size = Some(value.parse::<BoardSize>()?),
+                "player1" => humans.0 = parse_player(value)?,
+                "player2" => humans.1 = parse_player(value)?,
+                "difficulty" => difficulty = value.parse()?,
+                "seed" => seed = Some(value.parse().map_err(|_| format!("invalid seed: {}", value))?),
+                "moves" => moves = value.to_string(),
+                _ => (),
+            }
+        }
+
+        let mut file = GameFile {
+            game_type: game_type.ok_or("the game type is missing")?,
+            board_size: board_size.ok_or("the board size is missing")?,
+            humans,
+            difficulty,
+            seed,
+            moves: Vec::new(),
+        };
+        file.moves = parse_game(&file.empty_game(), &moves)?;
+        Ok(file)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn save_and_load() {
+        let text = "# Connect 4 / TOOT-OTTO saved game\n\
+                    game: TOOT-OTTO\n\
+                    size: 6 x 4\n\
+                    player1: human\n\
+                    player2: computer\n\
+                    difficulty: Insane\n\
+                    seed: 0\n\
+                    moves: 3T 2O\n";
+        let file: GameFile = text.parse().unwrap();
+        assert_eq!(file.game_type, GameType::TOOTandOTTO);
+        assert_eq!(file.board_size, BoardSize::SixByFour);
+        assert_eq!(file.humans, (true, false));
+        assert_eq!(file.difficulty, Difficulty::Insane);
+        assert_eq!(file.seed, Some(0));
+        assert_eq!(file.moves, vec![(3, DiscType::T), (2, DiscType::O)]);
+        assert_eq!(file.to_string(), text);
+    }
+
+    #[test]
+    fn defaults_and_unknown_keys() {
+        let file: GameFile = "game: Connect-4\nsize: 7 x 6\ntheme: dark\n".parse().unwrap();
+        assert_eq!(file.humans, (true, false));
+        assert_eq!(file.difficulty, Difficulty::Hard);
+        assert_eq!(file.seed, None);
+        assert!(file.moves.is_empty());
+        assert!(!file.to_string().contains("seed"));
+    }
+
+    #[test]
+    fn invalid_files() {
+        assert!("size: 7 x 6\n".parse::<GameFile>().is_err());
+        assert!("game: Connect-4\n".parse::<GameFile>().is_err());
+        assert!("game: Connect-4\nsize: 7 x 6\nseed: -1\n".parse::<GameFile>().is_err());
+        assert!("game: Connect-4\nsize: 7 x 6\nmoves: 9\n".parse::<GameFile>().is_err());
+        assert!("game: Connect-4\nsize 7 x 6\n".parse::<GameFile>().is_err());
+    }
+}
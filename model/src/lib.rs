@@ -4,3 +4,15 @@ pub mod player;
 pub mod game;
 pub mod ai;
 pub mod board_size;
+pub mod mcts;
+pub mod eval_params;
+pub mod selfplay;
+pub mod parallel;
+pub mod solver;
+pub mod solution_table;
+pub mod adaptive;
+pub mod trace;
+pub mod notation;
+pub mod game_file;
+pub mod review;
+pub mod dataset;
@@ -1,273 +1,735 @@
-mod disc;
-mod board;
-mod player;
-mod game;
-mod ai;
-use game::*;
-
-fn read_input() -> usize {
-    loop {
-        let mut line = String::new();
+// Command line version of the games
+//
+// usage (from the model folder): cargo run -- <command> [flags], `cargo run -- help` for the flags
+//     play      play against the computer, or against another person with --human both
+//     analyze   score every move of a position with the AI, or review recorded games
+//     selfplay  let two difficulties play each other
+//     solve     the exact result of a position, on the boards the solver handles
+//     engine    let another program use the AI, see engine.rs
+//     replay    step through a saved game
+// Without a command it plays. On a terminal `play` opens the full screen UI in tui.rs,
+// otherwise (or with --ui plain) moves are typed and printed as in model::notation, so
+// the moves of a game can also be piped in, one or more per line. Games are saved and
+// loaded in the format of model::game_file.
+//
+// exit codes: 0 when done, 1 for errors and when a game is left unfinished (quit or the input
+// ended), 2 for bad arguments
+mod engine;
+mod tui;
 
-        std::io::stdin()
-            .read_line(&mut line)
-            .expect("unable to read input");
+use std::collections::VecDeque;
+use std::io::{self, BufRead, Write};
+use crossterm::tty::IsTty;
+use rand::prelude::*;
+use rand::rngs::StdRng;
+use model::adaptive::{AdaptiveAI, DEFAULT_LEVEL};
+use model::ai::{Connect4AI, Difficulty, MoveOutcome, SearchAI, TootOttoAI, random_seed};
+use model::board_size::BoardSize;
+use model::disc::DiscType;
+use model::game::{BoardGame, GameType};
+use model::game_file::GameFile;
+use model::notation::{format_move, format_moves, parse_game, parse_move, parse_moves};
+use model::review::{review_game, MoveClass, MoveReview};
+use model::selfplay::{GameAI, play_game, random_opening};
+use model::solver::{Solver, TABLE_SIZE};
+use tui::Tui;
 
-        match line.trim().parse::<usize>() {
-            Ok(num) => return num,
-            Err(_) => println!("invalid input, try again")
-        }
+// where `save` and `load` go without a file name, unless the game came from a file
+const DEFAULT_SAVE: &str = "saved_game.txt";
+
+// why a command stopped early
+enum Failure {
+    Error(String),
+    Closed, // stdout was closed, e.g. by `model analyze ... | head`, there's no one left to tell
+}
+
+impl From<String> for Failure {
+    fn from(err: String) -> Self {
+        Failure::Error(err)
     }
 }
 
-fn play_connect4_with_computer(row: usize, column: usize, difficulty: ai::Difficulty) {
-    let mut game = BoardGame::new_connect4(row, column, true);
-    let mut ai = ai::Connect4AI::new(row, column, difficulty);
-    loop {
-        println!("{}", game.game_board);
-        let column_to_place: usize;
-        if game.current_player == 1 {
-            println!("Player 1's turn, current disc: {}", game.player1.disc_type);
-            println!("please type the column that you wish to insert your piece: ");
-            
-            column_to_place = read_input();
-            if column_to_place >= column {
-                println!("index out of bound, try again");
-                continue;
-            }
-        } else {
-            println!("Computer's turn, computer disc: {}", game.player2.disc_type);
-            let clone_game = game.clone();
-            column_to_place = ai.find_best_move(clone_game);
-            println!("Computer choose to place at column: {}", column_to_place);
+impl From<io::Error> for Failure {
+    fn from(err: io::Error) -> Self {
+        match err.kind() {
+            io::ErrorKind::BrokenPipe => Failure::Closed,
+            _ => Failure::Error(format!("failed to write the output: {}", err)),
         }
+    }
+}
 
-        match game.place_disc(column_to_place) {
-            GameEvent::PlaceSuccess(_) => {
-                match game.check() {
-                    GameEvent::Draw => {
-                        println!("Game Draw!");
-                        break;
-                    }
-                    GameEvent::Player1Win => {
-                        println!("Player 1 Win!");
-                        break;
-                    },
-                    GameEvent::Player2Win => {
-                        println!("Player 2 Win!");
-                        break;
-                    },
-                    GameEvent::Ongoing => (),
-                    _ => println!("unexpected")
-                }
-            },
-            GameEvent::PlaceColumnFull => println!("column is full, try again"),
-            _ => println!("unexpected error, try again"),
+const USAGE: &str = "\
+usage: model [command] [flags]
+
+commands:
+    play        play a game (the default command)
+    analyze     score every move of a position with the AI, or with a file of recorded games
+                (model analyze FILE) class every move as best, inaccuracy, mistake or blunder
+    selfplay    let two difficulties play each other
+    solve       the exact result of every move of a position, boards up to 6 x 4
+    engine      talk the engine protocol on stdin and stdout, for other programs
+    replay      step through a saved game (model replay FILE), a move each time enter is pressed
+    help        show this message
+
+flags:
+    --game connect4|toot-otto     the game, connect4 by default
+    --size \"7 x 6\"                the board size, 7 x 6 for Connect 4 and 6 x 4 for TOOT-OTTO by default
+    --moves \"3 3 4\"               start from the position after these moves (play, analyze, solve)
+    --difficulty hard             easy, medium, hard, insane or adaptive, hard by default (play, analyze),
+                                  insane when reviewing recorded games
+    --human first|second|both|none  which players are typed in, first by default (play)
+    --ui tui|plain                the full screen UI, or lines of text for scripts (play)
+    --seed N                      the AI seed, to play the same game again (play, analyze, selfplay)
+    --player1 hard --player2 easy  the difficulties playing each other (selfplay)
+    --games 10                    the number of games (selfplay)
+    --opening 2                   random moves at the start of each game (selfplay)
+    --load FILE                   carry on with a saved game, the game flags come from the file (play)
+    --save FILE                   where save writes the game, saved_game.txt by default (play)
+    --delay MS                    play the saved game on its own, a move every MS milliseconds (replay)
+    --format text|json            how recorded games are reported (analyze FILE)
+
+while playing, type save [FILE] or load [FILE] instead of a move (s and l in the full screen UI)
+
+moves are a column counted from 0, with the letter in TOOT-OTTO: 3 or 3T";
+
+enum Command {
+    Play,
+    Analyze,
+    SelfPlay,
+    Solve,
+    Engine,
+    Replay,
+    Help,
+}
+
+struct Options {
+    command: Command,
+    game_type: GameType,
+    board_size: Option<BoardSize>,
+    moves: String,
+    difficulty: Option<Difficulty>,
+    humans: (bool, bool), // whether player 1 and player 2 are typed in
+    tui: Option<bool>,    // by default only when playing on a terminal
+    seed: Option<u64>,
+    player1: Difficulty,
+    player2: Difficulty,
+    games: usize,
+    opening: usize,
+    load: Option<String>,
+    save: Option<String>,
+    delay: Option<u64>,
+    json: bool,
+}
+
+impl Options {
+    fn difficulty(&self) -> Difficulty {
+        self.difficulty.unwrap_or(Difficulty::Hard)
+    }
+
+    fn board_size(&self) -> BoardSize {
+        self.board_size.unwrap_or(match self.game_type {
+            GameType::Connect4 => BoardSize::SevenBySix,
+            GameType::TOOTandOTTO => BoardSize::SixByFour,
+        })
+    }
+
+    // the game after --moves
+    fn start_game(&self, vs_ai: bool) -> Result<BoardGame, String> {
+        let board_size = self.board_size();
+        let mut game = BoardGame::new_game(&self.game_type, board_size.get_row(), board_size.get_column(), vs_ai);
+        for game_move in parse_moves(&game, &self.moves)? {
+            game.play_move(game_move);
         }
+        Ok(game)
     }
 }
 
-fn play_connect4_with_human(row: usize, column: usize) {
-    let mut game = BoardGame::new_connect4(row, column, false);
-    loop {
-        println!("{}", game.game_board);
-        if game.current_player == 1 {
-            println!("Player 1's turn, current disc: {}", game.player1.disc_type);
-        } else {
-            println!("Player 2's turn, current disc: {}", game.player2.disc_type);
-        }
-        println!("please type the column that you wish to insert your piece: ");
+fn parse_args() -> Result<Options, String> {
+    let mut args = std::env::args().skip(1).peekable();
+    let command = match args.peek().map(|arg| arg.as_str()) {
+        Some("play") => Command::Play,
+        Some("analyze") => Command::Analyze,
+        Some("selfplay") => Command::SelfPlay,
+        Some("solve") => Command::Solve,
+        Some("engine") => Command::Engine,
+        Some("replay") => Command::Replay,
+        Some("help") | Some("--help") | Some("-h") => Command::Help,
+        Some(arg) if !arg.starts_with("--") => return Err(format!("unknown command: {}", arg)),
+        _ => Command::Play,
+    };
+    if matches!(args.peek(), Some(arg) if !arg.starts_with("--")) {
+        args.next();
+    }
 
-        let column_to_place = read_input();
-        if column_to_place >= column {
-            println!("index out of bound, try again");
+    let mut options = Options {
+        command,
+        game_type: GameType::Connect4,
+        board_size: None,
+        moves: String::new(),
+        difficulty: None,
+        humans: (true, false),
+        tui: None,
+        seed: None,
+        player1: Difficulty::Hard,
+        player2: Difficulty::Hard,
+        games: 10,
+        opening: 2,
+        load: None,
+        save: None,
+        delay: None,
+        json: false,
+    };
+    while let Some(flag) = args.next() {
+        if flag == "--help" || flag == "-h" {
+            options.command = Command::Help;
             continue;
         }
-        match game.place_disc(column_to_place) {
-            GameEvent::PlaceSuccess(_) => {
-                match game.check() {
-                    GameEvent::Draw => {
-                        println!("Game Draw!");
-                        break;
-                    }
-                    GameEvent::Player1Win => {
-                        println!("Player 1 Win!");
-                        break;
-                    },
-                    GameEvent::Player2Win => {
-                        println!("Player 2 Win!");
-                        break;
-                    },
-                    GameEvent::Ongoing => (),
-                    _ => println!("unexpected")
+        // the file of `model replay FILE` or `model play FILE`
+        if !flag.starts_with("--") {
+            options.load = Some(flag);
+            continue;
+        }
+        let value = args.next().ok_or(format!("missing value for {}", flag))?;
+        match flag.as_str() {
+            "--game" => options.game_type = value.parse()?,
+            "--size" => options.board_size = Some(value.parse()?),
+            "--moves" => options.moves = value,
+            "--difficulty" => options.difficulty = Some(value.parse()?),
+            "--human" => {
+                options.humans = match value.as_str() {
+                    "first" => (true, false),
+                    "second" => (false, true),
+                    "both" => (true, true),
+                    "none" => (false, false),
+                    _ => return Err(format!("--human is first, second, both or none, not {}", value)),
+                }
+            },
+            "--ui" => {
+                options.tui = match value.as_str() {
+                    "tui" => Some(true),
+                    "plain" => Some(false),
+                    _ => return Err(format!("--ui is tui or plain, not {}", value)),
+                }
+            },
+            "--seed" => options.seed = Some(value.parse().map_err(|_| format!("invalid seed: {}", value))?),
+            "--player1" => options.player1 = value.parse()?,
+            "--player2" => options.player2 = value.parse()?,
+            "--games" => options.games = value.parse().map_err(|_| format!("invalid game count: {}", value))?,
+            "--opening" => options.opening = value.parse().map_err(|_| format!("invalid opening length: {}", value))?,
+            "--load" => options.load = Some(value),
+            "--save" => options.save = Some(value),
+            "--delay" => options.delay = Some(value.parse().map_err(|_| format!("invalid delay: {}", value))?),
+            "--format" => {
+                options.json = match value.as_str() {
+                    "text" => false,
+                    "json" => true,
+                    _ => return Err(format!("--format is text or json, not {}", value)),
                 }
             },
-            GameEvent::PlaceColumnFull => println!("column is full, try again"),
-            _ => println!("unexpected error, try again"),
+            _ => return Err(format!("unknown flag: {}", flag)),
+        }
+    }
+    if let Command::Replay = options.command {
+        if options.load.is_none() {
+            return Err(String::from("replay needs the saved game: model replay FILE"));
+        }
+    }
+    // --moves are played on the board of the other flags, a saved game brings its own
+    if options.load.is_none() {
+        options.start_game(true)?;
+    }
+    Ok(options)
+}
+
+fn new_ai(game_type: &GameType, board_size: BoardSize, difficulty: Difficulty, seed: u64) -> Box<dyn GameAI> {
+    let rows = board_size.get_row();
+    let columns = board_size.get_column();
+    match (difficulty, game_type) {
+        (Difficulty::Adaptive, _) => Box::new(AdaptiveAI::new(game_type, rows, columns, DEFAULT_LEVEL, seed)),
+        (_, GameType::Connect4) => Box::new(Connect4AI::new(rows, columns, difficulty, seed)),
+        (_, GameType::TOOTandOTTO) => Box::new(TootOttoAI::new(rows, columns, difficulty, seed)),
+    }
+}
+
+// like the Board's Display, with the column numbers under it
+fn write_board(out: &mut dyn Write, game: &BoardGame) -> io::Result<()> {
+    for row in &game.game_board.board {
+        let cells: Vec<String> = row.iter().map(|disc_type| disc_type.to_string()).collect();
+        writeln!(out, "{}", cells.join(" "))?;
+    }
+    let columns: Vec<String> = (0..game.game_board.board_columns).map(|column| column.to_string()).collect();
+    writeln!(out, "{}\n", columns.join(" "))
+}
+
+// while playing, the game carries on whatever happens to stdout
+fn print_board(game: &BoardGame) {
+    write_board(&mut io::stdout(), game).ok();
+}
+
+fn print_result(winner: usize) {
+    match winner {
+        0 => println!("Game Draw!"),
+        winner => println!("Player {} Win!", winner),
+    }
+}
+
+// what is typed during a game, a line can hold several moves
+struct Typed<I: Iterator<Item = String>> {
+    lines: I,
+    words: VecDeque<String>,
+}
+
+impl<I: Iterator<Item = String>> Typed<I> {
+    fn next_word(&mut self) -> Option<String> {
+        while self.words.is_empty() {
+            let line = self.lines.next()?;
+            self.words = line.split_whitespace().map(String::from).collect();
         }
+        self.words.pop_front()
+    }
+
+    // what's left of the line, for a file name
+    fn rest_of_line(&mut self) -> String {
+        self.words.drain(..).collect::<Vec<String>>().join(" ")
     }
 }
 
-fn play_toototto_with_computer(row: usize, column: usize, difficulty: ai::Difficulty) {
-    let mut game = BoardGame::new_toot_and_otto(row, column, false);
-    let mut ai = ai::TootOttoAI::new(row, column, difficulty);
+enum Input {
+    Move((usize, DiscType)),
+    Save(String), // an empty path for the default one
+    Load(String),
+}
 
+// what the player to move types next, None when the input ended or the player quit.
+// In TOOT-OTTO a column on its own uses the letter in hand, T or O on their own switch it.
+fn read_input<I: Iterator<Item = String>>(game: &mut BoardGame, typed: &mut Typed<I>) -> Option<Input> {
     loop {
-        println!("{}", game.game_board);
-        let column_to_place: usize;
-        let current_disc_type = game.get_current_disc_type();
-
-        if game.current_player == 1 {
-            println!("Player 1's turn, current disc: {}", game.player1.disc_type);
-            println!("please type the column that you wish to insert your piece: ");
-            println!("type '421' to switch your disc to 'T'"); 
-            println!("type '466' to switch your disc to 'O'"); 
-        
-            column_to_place = read_input();
-            if column_to_place == 421 {
-                game.change_disc_type(DiscType::T);
-                continue;
-            } else if column_to_place == 466 {
-                game.change_disc_type(DiscType::O);
-                continue;
-            } else if column_to_place >= column {
-                println!("index out of bound, try again");
-                continue;
-            }
+        let disc_type = game.get_current_disc_type();
+        println!("Player {}'s turn, current disc: {}", game.current_player, disc_type);
+        if game.game_type == GameType::TOOTandOTTO {
+            println!("type a column with T or O (e.g. 3T), T or O to switch your disc, save, load or quit:");
         } else {
-            println!("Computer's turn, computer disc: {}", game.player2.disc_type);
-            let clone_game = game.clone();
-            let (best_move, disc_type) = ai.find_best_move(clone_game);
-            column_to_place = best_move;
-            println!("Computer choose to place at column: {}", column_to_place);
-
-            // change the disc type
-            game.change_disc_type(disc_type);
+            println!("type a column, save, load or quit:");
         }
 
-        match game.place_disc(column_to_place) {
-            // reset the disc type that might be changed by computer
-            GameEvent::PlaceSuccess(_) => {
-                game.change_disc_type(current_disc_type);
-                match game.check() {
-                    GameEvent::Draw => {
-                        println!("Game Draw!");
-                        break;
-                    }
-                    GameEvent::Player1Win => {
-                        println!("Player 1 Win!");
-                        break;
-                    },
-                    GameEvent::Player2Win => {
-                        println!("Player 2 Win!");
-                        break;
-                    },
-                    GameEvent::Ongoing => (),
-                    _ => println!("unexpected")
+        let word = typed.next_word()?;
+        match word.to_uppercase().as_str() {
+            "QUIT" | "Q" => return None,
+            "SAVE" => return Some(Input::Save(typed.rest_of_line())),
+            "LOAD" => return Some(Input::Load(typed.rest_of_line())),
+            "T" => game.change_disc_type(DiscType::T),
+            "O" => game.change_disc_type(DiscType::O),
+            _ => {
+                let text = if game.game_type == GameType::TOOTandOTTO && word.chars().all(|c| c.is_ascii_digit()) {
+                    format!("{}{}", word, disc_type)
+                } else {
+                    word
+                };
+                match parse_move(game, &text) {
+                    Ok(game_move) => return Some(Input::Move(game_move)),
+                    Err(err) => println!("{}, try again", err),
                 }
             },
-            GameEvent::PlaceColumnFull => println!("column is full, try again"),
-            _ => println!("unexpected error, try again"),
         }
     }
 }
 
-fn play_toototto_with_human(row: usize, column: usize) {
-    let mut game = BoardGame::new_toot_and_otto(row, column, false);
-    loop {
-        println!("{}", game.game_board);
-        if game.current_player == 1 {
-            println!("Player 1's turn, current disc: {}", game.player1.disc_type);
+// the computer players of a game, None for the people. A loaded game gets new AIs with
+// the seed from the file, they don't carry on from where the saved ones were.
+fn players(file: &GameFile) -> Vec<Option<Box<dyn GameAI>>> {
+    vec![file.humans.0, file.humans.1].into_iter()
+        .zip(0..)
+        .map(|(human, index)| if human {
+            None
         } else {
-            println!("Player 2's turn, current disc: {}", game.player2.disc_type);
+            Some(new_ai(&file.game_type, file.board_size, file.difficulty, file.seed.unwrap_or_else(random_seed).wrapping_add(index)))
+        })
+        .collect()
+}
+
+// the game to play, from --load or else from the flags
+fn game_file(options: &Options) -> Result<GameFile, String> {
+    if let Some(path) = &options.load {
+        return GameFile::load(path);
+    }
+    let mut file = GameFile {
+        game_type: options.game_type.clone(),
+        board_size: options.board_size(),
+        humans: options.humans,
+        difficulty: options.difficulty(),
+        seed: options.seed,
+        moves: Vec::new(),
+    };
+    file.moves = parse_moves(&file.empty_game(), &options.moves)?;
+    Ok(file)
+}
+
+fn play(options: &Options) -> Result<i32, String> {
+    let mut file = game_file(options)?;
+    // saves go back to the loaded file by default
+    let save_path = options.save.clone()
+        .or_else(|| options.load.clone())
+        .unwrap_or_else(|| String::from(DEFAULT_SAVE));
+    // picked here rather than by the AIs so it can be shown and saved with the game
+    let seed = *file.seed.get_or_insert_with(random_seed);
+    let has_ai = !file.humans.0 || !file.humans.1;
+
+    if options.tui.unwrap_or_else(|| std::io::stdin().is_tty() && std::io::stdout().is_tty()) {
+        let code = Tui::new(file, save_path, players).run().map_err(|err| format!("terminal error: {}", err))?;
+        if has_ai {
+            println!("AI seed: {} (use --seed {} to replay this game)", seed, seed);
         }
-        println!("please type the column that you wish to insert your piece: ");
-        println!("type '421' to switch your disc to 'T'"); 
-        println!("type '466' to switch your disc to 'O'"); 
-    
-        let column_to_place = read_input();
-        if column_to_place == 421 {
-            game.change_disc_type(DiscType::T);
-            continue;
-        } else if column_to_place == 466 {
-            game.change_disc_type(DiscType::O);
-            continue;
-        } else if column_to_place >= column {
-            println!("index out of bound, try again");
-            continue;
+        return Ok(code);
+    }
+
+    if has_ai {
+        println!("AI seed: {} (use --seed {} to replay this game)", seed, seed);
+    }
+    Ok(play_plain(file, &save_path))
+}
+
+// the game as lines of text, for scripts and terminals without the full screen UI
+fn play_plain(mut file: GameFile, save_path: &str) -> i32 {
+    let (mut game, mut winner) = file.game();
+    let mut players = players(&file);
+    let stdin = std::io::stdin();
+    let mut typed = Typed {
+        lines: stdin.lock().lines().take_while(|line| line.is_ok()).filter_map(|line| line.ok()),
+        words: VecDeque::new(),
+    };
+    loop {
+        print_board(&game);
+        if let Some(winner) = winner {
+            print_result(winner);
+            return 0;
         }
 
-        match game.place_disc(column_to_place) {
-            GameEvent::PlaceSuccess(_) => {
-                match game.check() {
-                    GameEvent::Draw => {
-                        println!("Game Draw!");
-                        break;
+        let game_move = match &mut players[game.current_player - 1] {
+            Some(ai) => {
+                let game_move = ai.next_move(&game);
+                println!("Computer (player {}) plays {}", game.current_player, format_move(&file.game_type, game_move));
+                game_move
+            },
+            None => match read_input(&mut game, &mut typed) {
+                Some(Input::Move(game_move)) => game_move,
+                Some(Input::Save(path)) => {
+                    let path = if path.is_empty() { save_path.to_string() } else { path };
+                    match file.save(&path) {
+                        Ok(_) => println!("game saved to {}", path),
+                        Err(err) => println!("{}", err),
                     }
-                    GameEvent::Player1Win => {
-                        println!("Player 1 Win!");
-                        break;
-                    },
-                    GameEvent::Player2Win => {
-                        println!("Player 2 Win!");
-                        break;
-                    },
-                    GameEvent::Ongoing => (),
-                    _ => println!("unexpected")
+                    continue;
+                },
+                Some(Input::Load(path)) => {
+                    let path = if path.is_empty() { save_path.to_string() } else { path };
+                    match GameFile::load(&path) {
+                        Ok(loaded) => {
+                            println!("game loaded from {}", path);
+                            file = loaded;
+                            let (loaded_game, loaded_winner) = file.game();
+                            game = loaded_game;
+                            winner = loaded_winner;
+                            players = self::players(&file);
+                        },
+                        Err(err) => println!("{}", err),
+                    }
+                    continue;
+                },
+                None => {
+                    eprintln!("the game was left unfinished");
+                    return 1;
                 }
             },
-            GameEvent::PlaceColumnFull => println!("column is full, try again"),
-            _ => println!("unexpected error, try again"),
-        }
+        };
+        file.moves.push(game_move);
+        winner = game.play_move(game_move);
     }
 }
 
-fn change_difficulty() -> ai::Difficulty {
-    println!("Pick your difficulty: ");
-    println!("1: Easy");
-    println!("2: Medium");
-    println!("3: Hard");
-    println!("4: Insane");
+// steps through a saved game, a move each time enter is pressed or every --delay
+fn replay(options: &Options) -> Result<i32, Failure> {
+    let path = options.load.as_ref().expect("parse_args() checks for the file");
+    let file = GameFile::load(path)?;
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    write!(out, "{}", file)?;
+    writeln!(out)?;
 
-    loop {
-        let input = read_input();
-        match input {
-            1 => return ai::Difficulty::Easy,
-            2 => return ai::Difficulty::Medium,
-            3 => return ai::Difficulty::Hard,
-            4 => return ai::Difficulty::Insane,
-            _ => {
-                println!("invalid input");
-                continue;
+    let interactive = options.delay.is_none() && std::io::stdin().is_tty();
+    let stdin = std::io::stdin();
+    let mut lines = stdin.lock().lines();
+    let mut game = file.empty_game();
+    write_board(&mut out, &game)?;
+    for (index, game_move) in file.moves.iter().enumerate() {
+        if interactive {
+            writeln!(out, "enter for the next move, q to stop:")?;
+            match lines.next() {
+                Some(Ok(line)) if line.trim() != "q" => (),
+                _ => return Ok(0),
             }
+        } else if let Some(delay) = options.delay {
+            std::thread::sleep(std::time::Duration::from_millis(delay));
+        }
+        writeln!(out, "move {}: player {} plays {}", index + 1, game.current_player, format_move(&file.game_type, *game_move))?;
+        let winner = game.play_move(*game_move);
+        write_board(&mut out, &game)?;
+        if let Some(winner) = winner {
+            print_result(winner);
         }
     }
+    Ok(0)
 }
 
-fn main() {
-    let mut difficulty: ai::Difficulty = ai::Difficulty::Insane;
+// the games of a file for `analyze FILE`: a saved game, or one game per line written as its
+// moves, on the board of --game and --size. Blank lines and lines starting with # are skipped.
+fn read_games(options: &Options, path: &str) -> Result<Vec<GameFile>, String> {
+    let text = std::fs::read_to_string(path).map_err(|err| format!("failed to read {}: {}", path, err))?;
+    if text.lines().any(|line| line.trim_start().starts_with("game:")) {
+        return Ok(vec![text.parse().map_err(|err| format!("{}: {}", path, err))?]);
+    }
 
-    loop {
-        println!("pick a game: ");
-        println!("current AI difficulty: {}", difficulty.to_string());
-        println!("1: Connect 4 With Computer");
-        println!("2: Connect 4 With Human");
-        println!("3: TOOT and OTTO with Computer");
-        println!("4: TOOT and OTTO with Human");
-        println!("5: change difficulty vs Computer");
-        println!("6: quit");
-        let input = read_input();
-        match input {
-            1 => play_connect4_with_computer(6, 7, difficulty),
-            2 => play_connect4_with_human(6, 7),
-            3 => play_toototto_with_computer(4, 6, difficulty),
-            4 => play_toototto_with_human(4, 6),
-            5 => difficulty = change_difficulty(),
-            6 => break,
-            _ => {
-                println!("invalid input");
-                continue;
-            }
+    let mut games = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut file = GameFile {
+            game_type: options.game_type.clone(),
+            board_size: options.board_size(),
+            humans: (true, true),
+            difficulty: options.difficulty(),
+            seed: None,
+            moves: Vec::new(),
+        };
+        file.moves = parse_game(&file.empty_game(), line).map_err(|err| format!("{} line {}: {}", path, number + 1, err))?;
+        games.push(file);
+    }
+    Ok(games)
+}
+
+fn outcome_text(outcome: Option<MoveOutcome>) -> &'static str {
+    match outcome {
+        Some(MoveOutcome::Win) => "wins",
+        Some(MoveOutcome::Draw) => "draws",
+        Some(MoveOutcome::Loss) => "loses",
+        None => "",
+    }
+}
+
+fn review_json(game_type: &GameType, review: &MoveReview) -> String {
+    let outcome = |outcome| match outcome_text(outcome) {
+        "" => String::from("null"),
+        text => format!("\"{}\"", text),
+    };
+    format!(
+        "{{\"player\": {}, \"move\": \"{}\", \"best\": \"{}\", \"score\": {}, \"best_score\": {}, \
+         \"outcome\": {}, \"best_outcome\": {}, \"swing\": {}, \"class\": \"{}\"}}",
+        review.player,
+        format_move(game_type, review.game_move),
+        format_move(game_type, review.best_move),
+        review.score,
+        review.best_score,
+        outcome(review.outcome),
+        outcome(review.best_outcome),
+        review.swing,
+        review.class
+    )
+}
+
+// `analyze FILE`, every move of every game compared with the AI's choice
+fn review_games(options: &Options, path: &str) -> Result<i32, Failure> {
+    let games = read_games(options, path)?;
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    let seed = options.seed.unwrap_or_else(random_seed);
+    // deeper than Hard and still quick, the review is only as good as the AI
+    let difficulty = options.difficulty.unwrap_or(Difficulty::Insane);
+    let mut games_json = Vec::new();
+    for (number, file) in games.iter().enumerate() {
+        let rows = file.board_size.get_row();
+        let columns = file.board_size.get_column();
+        let reviews = match file.game_type {
+            GameType::Connect4 => {
+                let mut ai = Connect4AI::new(rows, columns, difficulty, seed);
+                review_game(file.empty_game(), &file.moves, &mut |game| ai.analyze(game))
+            },
+            GameType::TOOTandOTTO => {
+                let mut ai = TootOttoAI::new(rows, columns, difficulty, seed);
+                review_game(file.empty_game(), &file.moves, &mut |game| ai.analyze(game))
+            },
+        }.map_err(|err| format!("{} game {}: {}", path, number + 1, err))?;
+        let result = match file.game().1 {
+            Some(0) => String::from("draw"),
+            Some(winner) => format!("player {} won", winner),
+            None => String::from("unfinished"),
+        };
+
+        // moves of each class, for player 1 and player 2
+        let counts: Vec<Vec<usize>> = (1..=2)
+            .map(|player| {
+                MoveClass::to_vec().into_iter()
+                    .map(|class| reviews.iter().filter(|review| review.player == player && review.class == class).count())
+                    .collect()
+            })
+            .collect();
+
+        if options.json {
+            let moves: Vec<String> = reviews.iter().map(|review| format!("        {}", review_json(&file.game_type, review))).collect();
+            let summary: Vec<String> = counts.iter()
+                .map(|counts| {
+                    let classes: Vec<String> = MoveClass::to_vec().iter().zip(counts).map(|(class, count)| format!("\"{}\": {}", class, count)).collect();
+                    format!("{{{}}}", classes.join(", "))
+                })
+                .collect();
+            games_json.push(format!(
+                "    {{\n      \"game\": \"{}\",\n      \"size\": \"{}\",\n      \"result\": \"{}\",\n      \"players\": [{}],\n      \"moves\": [\n{}\n      ]\n    }}",
+                file.game_type.to_string(),
+                file.board_size.to_string(),
+                result,
+                summary.join(", "),
+                moves.join(",\n")
+            ));
+            continue;
+        }
+
+        writeln!(out, "game {}: {} on {}, {}", number + 1, file.game_type.to_string(), file.board_size.to_string(), result)?;
+        for (index, review) in reviews.iter().enumerate() {
+            let best = if review.class == MoveClass::Best {
+                String::new()
+            } else {
+                format!("best {} score {} {}", format_move(&file.game_type, review.best_move), review.best_score, outcome_text(review.best_outcome))
+            };
+            writeln!(out, 
+                "{:>4}. P{} {:>4} score {:>9} {:<5} swing {:>9} {:<10} {}",
+                index + 1,
+                review.player,
+                format_move(&file.game_type, review.game_move),
+                review.score,
+                outcome_text(review.outcome),
+                review.swing,
+                review.class,
+                best
+            )?;
+        }
+        for (player, counts) in counts.iter().enumerate() {
+            writeln!(out, 
+                "player {}: best {}, inaccuracies {}, mistakes {}, blunders {}",
+                player + 1,
+                counts[0],
+                counts[1],
+                counts[2],
+                counts[3]
+            )?;
+        }
+        writeln!(out)?;
+    }
+    if options.json {
+        writeln!(out, "{{\n  \"difficulty\": \"{}\",\n  \"seed\": {},\n  \"games\": [\n{}\n  ]\n}}", difficulty.to_string(), seed, games_json.join(",\n"))?;
+    }
+    Ok(0)
+}
+
+fn analyze(options: &Options) -> Result<i32, Failure> {
+    if let Some(path) = &options.load {
+        return review_games(options, path);
+    }
+    let game = options.start_game(true)?;
+    let board_size = options.board_size();
+    let seed = options.seed.unwrap_or_else(random_seed);
+    let evaluations = match options.game_type {
+        GameType::Connect4 => Connect4AI::new(board_size.get_row(), board_size.get_column(), options.difficulty(), seed).analyze(&game),
+        GameType::TOOTandOTTO => TootOttoAI::new(board_size.get_row(), board_size.get_column(), options.difficulty(), seed).analyze(&game),
+    };
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+
+    write_board(&mut out, &game)?;
+    writeln!(out, "Player {} to move, best move first:", game.current_player)?;
+    for evaluation in evaluations {
+        let outcome = outcome_text(evaluation.outcome);
+        writeln!(out, 
+            "{:>4} score {:>12} {:<5} line: {}",
+            format_move(&options.game_type, (evaluation.column, evaluation.disc_type)),
+            evaluation.score,
+            outcome,
+            format_moves(&options.game_type, &evaluation.principal_variation)
+        )?;
+    }
+    Ok(0)
+}
+
+fn selfplay(options: &Options) -> Result<i32, Failure> {
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    let board_size = options.board_size();
+    let seed = options.seed.unwrap_or_else(random_seed);
+    let mut rng = StdRng::seed_from_u64(seed);
+    writeln!(out, 
+        "{} against {} on {}, seed {}",
+        options.player1.to_string(),
+        options.player2.to_string(),
+        board_size.to_string(),
+        seed
+    )?;
+
+    let mut wins = [0; 3]; // draws, player 1 wins, player 2 wins
+    for number in 1..=options.games {
+        let game = BoardGame::new_game(&options.game_type, board_size.get_row(), board_size.get_column(), true);
+        let opening = random_opening(&game, options.opening, &mut rng);
+        let mut player1 = new_ai(&options.game_type, board_size, options.player1, rng.gen());
+        let mut player2 = new_ai(&options.game_type, board_size, options.player2, rng.gen());
+        let record = play_game(game, &opening, player1.as_mut(), player2.as_mut());
+        wins[record.winner] += 1;
+        let result = match record.winner {
+            0 => String::from("draw"),
+            winner => format!("player {} wins", winner),
+        };
+        writeln!(out, "game {}: {} ({})", number, result, format_moves(&options.game_type, &record.moves))?;
+    }
+    writeln!(out, "player 1 won {}, player 2 won {}, {} draws", wins[1], wins[2], wins[0])?;
+    Ok(0)
+}
+
+fn solve(options: &Options) -> Result<i32, Failure> {
+    let board_size = options.board_size();
+    let mut solver = Solver::with_table(&options.game_type, board_size.get_row(), board_size.get_column(), TABLE_SIZE)
+        .ok_or(format!("{} is too big for the solver", board_size.to_string()))?;
+    let game = options.start_game(true)?;
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+
+    write_board(&mut out, &game)?;
+    writeln!(out, "{}", solver.solve(&game))?;
+    for (game_move, solution) in solver.solve_moves(&game) {
+        writeln!(out, "{:>4}: {}", format_move(&options.game_type, game_move), solution)?;
+    }
+    Ok(0)
+}
+
+fn main() {
+    let options = match parse_args() {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("{}\n\n{}", err, USAGE);
+            std::process::exit(2);
+        }
+    };
+    let result = match options.command {
+        Command::Play => play(&options).map_err(Failure::from),
+        Command::Analyze => analyze(&options),
+        Command::SelfPlay => selfplay(&options),
+        Command::Solve => solve(&options),
+        Command::Engine => Ok(engine::run()),
+        Command::Replay => replay(&options),
+        Command::Help => writeln!(io::stdout().lock(), "{}", USAGE).map(|_| 0).map_err(Failure::from),
+    };
+    match result {
+        Ok(code) => std::process::exit(code),
+        Err(Failure::Closed) => std::process::exit(0),
+        Err(Failure::Error(err)) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
         }
     }
 }
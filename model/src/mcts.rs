@@ -0,0 +1,317 @@
+use std::time::{Duration, Instant};
+use rand::prelude::*;
+use rand::rngs::StdRng;
+use crate::game::BoardGame;
+use crate::disc::DiscType;
+use crate::ai::get_random_index;
+
+// exploration constant of the UCT formula, sqrt(2) is the textbook value
+const EXPLORATION: f64 = 1.41;
+
+// how much the search is allowed to do before returning a move
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum MctsLimit {
+    Playouts(u32),
+    // uses std::time::Instant, which is not available on wasm32-unknown-unknown,
+    // use Playouts for the web frontend.
+    // the number of playouts depends on the machine, so a seed alone won't replay the game
+    Time(Duration),
+}
+
+struct Node {
+    game_move: Option<(usize, DiscType)>, // None for the root
+    parent: Option<usize>,
+    children: Vec<usize>,
+    untried_moves: Vec<(usize, DiscType)>,
+    player: usize, // the player that made `game_move`
+    visits: u32,
+    wins: f64, // from the point of view of `player`, a draw counts as half a win
+    winner: Option<usize>, // set when the game is over at this node, 0 for a draw
+}
+
+impl Node {
+    fn new(game_move: Option<(usize, DiscType)>, parent: Option<usize>, player: usize, game: &BoardGame, winner: Option<usize>) -> Self {
+        let untried_moves = if winner.is_some() { Vec::new() } else { game.legal_moves() };
+        Self {
+            game_move,
+            parent,
+            children: Vec::new(),
+            untried_moves,
+            player,
+            visits: 0,
+            wins: 0.0,
+            winner,
+        }
+    }
+
+    fn uct(&self, parent_visits: u32) -> f64 {
+        let visits = self.visits as f64;
+        self.wins / visits + EXPLORATION * ((parent_visits as f64).ln() / visits).sqrt()
+    }
+}
+
+// Monte Carlo Tree Search (UCT) player, works for both Connect 4 and TOOT-OTTO
+pub struct MctsAI {
+    limit: MctsLimit,
+    reuse_tree: bool,
+    nodes: Vec<Node>,
+    root_game: Option<BoardGame>,
+    seed: u64,
+    rng: StdRng,
+}
+
+impl MctsAI {
+    pub fn new(limit: MctsLimit, reuse_tree: bool, seed: u64) -> Self {
+        Self {
+            limit,
+            reuse_tree,
+            nodes: Vec::new(),
+            root_game: None,
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    // forget the search tree, e.g. when a new game is started
+    pub fn reset(&mut self) {
+        self.nodes = Vec::new();
+        self.root_game = None;
+    }
+
+    // number of playouts that went through the current root
+    pub fn root_visits(&self) -> u32 {
+        if self.nodes.is_empty() { 0 } else { self.nodes[0].visits }
+    }
+
+    pub fn find_best_move(&mut self, game: BoardGame) -> (usize, DiscType) {
+        if !(self.reuse_tree && self.reroot(&game)) {
+            let player = if game.current_player == 1 { 2 } else { 1 };
+            self.nodes = vec![Node::new(None, None, player, &game, None)];
+            self.root_game = Some(game.clone());
+        }
+
+        // the clock is only read with a time limit, Instant::now() panics on wasm32
+        let start = match self.limit {
+            MctsLimit::Time(_) => Some(Instant::now()),
+            MctsLimit::Playouts(_) => None,
+        };
+        let mut playouts = 0;
+        loop {
+            let done = match self.limit {
+                MctsLimit::Playouts(max_playouts) => playouts >= max_playouts,
+                MctsLimit::Time(duration) => playouts > 0 && match start {
+                    Some(start) => start.elapsed() >= duration,
+                    None => true,
+                },
+            };
+            if done {
+                break;
+            }
+            self.iterate(&game);
+            playouts += 1;
+        }
+
+        let best_child = self.nodes[0].children.iter()
+            .max_by_key(|&&child| self.nodes[child].visits)
+            .copied();
+        match best_child {
+            Some(child) => self.nodes[child].game_move.unwrap(),
+            None => {
+                // game is already over, there is nothing sensible to return
+                match game.legal_moves().first() {
+                    Some(game_move) => *game_move,
+                    None => (0, DiscType::Empty), // not gonna be used
+                }
+            }
+        }
+    }
+
+    // one selection - expansion - simulation - backpropagation round
+    fn iterate(&mut self, root_game: &BoardGame) {
+        let mut game = root_game.clone();
+        let mut node = 0;
+
+        // selection
+        while self.nodes[node].untried_moves.is_empty() && !self.nodes[node].children.is_empty() {
+            let parent_visits = self.nodes[node].visits;
+            node = *self.nodes[node].children.iter()
+                .max_by(|&&a, &&b| {
+                    self.nodes[a].uct(parent_visits)
+                        .partial_cmp(&self.nodes[b].uct(parent_visits))
+                        .unwrap_or(std::cmp::Ordering::Equal)
+                })
+                .unwrap();
+            game.play_move(self.nodes[node].game_move.unwrap());
+        }
+
+        // expansion
+        let mut winner = if !self.nodes[node].untried_moves.is_empty() {
+            let index = get_random_index(self.nodes[node].untried_moves.len(), &mut self.rng);
+            let game_move = self.nodes[node].untried_moves.swap_remove(index);
+            let player = game.current_player;
+            let winner = game.play_move(game_move);
+            let child = Node::new(Some(game_move), Some(node), player, &game, winner);
+            self.nodes.push(child);
+            let child_index = self.nodes.len() - 1;
+            self.nodes[node].children.push(child_index);
+            node = child_index;
+            winner
+        } else {
+            self.nodes[node].winner
+        };
+
+        // simulation
+        while winner.is_none() {
+            let moves = game.legal_moves();
+            if moves.is_empty() {
+                winner = Some(0);
+            } else {
+                let game_move = moves[get_random_index(moves.len(), &mut self.rng)];
+                winner = game.play_move(game_move);
+            }
+        }
+        let winner = winner.unwrap();
+
+        // backpropagation
+        let mut current = Some(node);
+        while let Some(index) = current {
+            let node = &mut self.nodes[index];
+            node.visits += 1;
+            if winner == 0 {
+                node.wins += 0.5;
+            } else if winner == node.player {
+                node.wins += 1.0;
+            }
+            current = node.parent;
+        }
+    }
+
+    // look for the new position among the nodes one or two plies below the old root
+    // and make it the new root, returns false if it can't be found
+    fn reroot(&mut self, game: &BoardGame) -> bool {
+        let root_game = match &self.root_game {
+            Some(root_game) => root_game.clone(),
+            None => return false,
+        };
+        if root_game.game_board.board == game.game_board.board {
+            return true;
+        }
+
+        for &child in &self.nodes[0].children {
+            let mut child_game = root_game.clone();
+            child_game.play_move(self.nodes[child].game_move.unwrap());
+            if child_game.game_board.board == game.game_board.board {
+                self.compact(child, game);
+                return true;
+            }
+
+            for &grandchild in &self.nodes[child].children {
+                let mut grandchild_game = child_game.clone();
+                grandchild_game.play_move(self.nodes[grandchild].game_move.unwrap());
+                if grandchild_game.game_board.board == game.game_board.board {
+                    self.compact(grandchild, game);
+                    return true;
+                }
+            }
+        }
+
+        false
+    }
+
+    // keep only the subtree under `new_root`, so the tree doesn't grow forever
+    fn compact(&mut self, new_root: usize, game: &BoardGame) {
+        let mut old_nodes: Vec<Option<Node>> = std::mem::take(&mut self.nodes).into_iter().map(Some).collect();
+        let mut stack = vec![(new_root, None)];
+        while let Some((old_index, parent)) = stack.pop() {
+            let mut node = old_nodes[old_index].take().unwrap();
+            let new_index = self.nodes.len();
+            node.parent = parent;
+            for &child in &node.children {
+                stack.push((child, Some(new_index)));
+            }
+            node.children = Vec::new();
+            if let Some(parent) = parent {
+                self.nodes[parent].children.push(new_index);
+            }
+            self.nodes.push(node);
+        }
+        self.nodes[0].game_move = None;
+        self.root_game = Some(game.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::GameType;
+    use crate::notation::parse_moves;
+
+    fn connect4_after(moves: &str) -> BoardGame {
+        let mut game = BoardGame::new_game(&GameType::Connect4, 6, 7, false);
+        for game_move in parse_moves(&game, moves).unwrap() {
+            game.play_move(game_move);
+        }
+        game
+    }
+
+    fn subtree_size(ai: &MctsAI, node: usize) -> usize {
+        1 + ai.nodes[node].children.iter().map(|&child| subtree_size(ai, child)).sum::<usize>()
+    }
+
+    #[test]
+    fn takes_an_immediate_win() {
+        let mut ai = MctsAI::new(MctsLimit::Playouts(2000), false, 1);
+        assert_eq!(ai.find_best_move(connect4_after("0 6 0 6 0 5")), (0, DiscType::Red));
+    }
+
+    #[test]
+    fn blocks_an_immediate_loss() {
+        let mut ai = MctsAI::new(MctsLimit::Playouts(2000), false, 1);
+        assert_eq!(ai.find_best_move(connect4_after("0 6 0 6 0")), (0, DiscType::Yellow));
+    }
+
+    #[test]
+    fn same_seed_same_moves() {
+        let play = |seed| {
+            let mut ai = MctsAI::new(MctsLimit::Playouts(300), true, seed);
+            let mut game = connect4_after("");
+            let mut moves = Vec::new();
+            for _ in 0..6 {
+                let game_move = ai.find_best_move(game.clone());
+                game.play_move(game_move);
+                moves.push(game_move);
+            }
+            moves
+        };
+        assert_eq!(play(7), play(7));
+    }
+
+    #[test]
+    fn reroot_keeps_the_subtree() {
+        let mut ai = MctsAI::new(MctsLimit::Playouts(2000), true, 3);
+        let mut game = connect4_after("");
+        ai.find_best_move(game.clone());
+        let most_visited = |ai: &MctsAI, node: usize| *ai.nodes[node].children.iter()
+            .max_by_key(|&&child| ai.nodes[child].visits)
+            .unwrap();
+        let child = most_visited(&ai, 0);
+        let grandchild = most_visited(&ai, child);
+        let (visits, size) = (ai.nodes[grandchild].visits, subtree_size(&ai, grandchild));
+        game.play_move(ai.nodes[child].game_move.unwrap());
+        game.play_move(ai.nodes[grandchild].game_move.unwrap());
+
+        assert!(ai.reroot(&game));
+        assert_eq!(ai.root_visits(), visits);
+        assert_eq!(ai.nodes.len(), size);
+        assert_eq!(ai.nodes[0].parent, None);
+        for (index, node) in ai.nodes.iter().enumerate() {
+            for &child in &node.children {
+                assert_eq!(ai.nodes[child].parent, Some(index));
+            }
+        }
+    }
+}
@@ -0,0 +1,115 @@
+// Text form of moves for the command line tools
+//
+// A move is its column counted from 0, followed by the letter in TOOT-OTTO: "3" for
+// Connect 4 and "3T" or "2O" for TOOT-OTTO. A game is its moves separated by spaces
+// or commas, starting from the empty board.
+use crate::disc::DiscType;
+use crate::game::{BoardGame, GameType};
+
+pub fn format_move(game_type: &GameType, (column, disc_type): (usize, DiscType)) -> String {
+    match game_type {
+        GameType::Connect4 => column.to_string(),
+        GameType::TOOTandOTTO => format!("{}{}", column, disc_type),
+    }
+}
+
+pub fn format_moves(game_type: &GameType, moves: &[(usize, DiscType)]) -> String {
+    moves.iter()
+        .map(|game_move| format_move(game_type, *game_move))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+// a move for the player to move in `game`, only if it is legal there
+pub fn parse_move(game: &BoardGame, text: &str) -> Result<(usize, DiscType), String> {
+    let (column, disc_type) = match game.game_type {
+        GameType::Connect4 => (text, game.get_current_disc_type()),
+        GameType::TOOTandOTTO => {
+            let disc_type = match text.chars().last().map(|letter| letter.to_ascii_uppercase()) {
+                Some('T') => DiscType::T,
+                Some('O') => DiscType::O,
+                _ => return Err(format!("missing letter T or O in move: {}", text)),
+            };
+            (&text[..text.len() - 1], disc_type)
+        },
+    };
+    let column: usize = column.parse().map_err(|_| format!("invalid move: {}", text))?;
+    if column >= game.game_board.board_columns {
+        return Err(format!("no column {} on the board: {}", column, text));
+    }
+    if !game.legal_moves().contains(&(column, disc_type)) {
+        return Err(format!("column {} is full: {}", column, text));
+    }
+    Ok((column, disc_type))
+}
+
+fn parse(game: &BoardGame, text: &str, may_end: bool) -> Result<Vec<(usize, DiscType)>, String> {
+    let mut game = game.clone();
+    let mut moves = Vec::new();
+    let mut over = false;
+    for text in text.split(|c: char| c.is_whitespace() || c == ',').filter(|text| !text.is_empty()) {
+        if over {
+            return Err(format!("the game is over before move {}", text));
+        }
+        let game_move = parse_move(&game, text)?;
+        over = game.play_move(game_move).is_some();
+        if over && !may_end {
+            return Err(format!("the game is over after move {}", text));
+        }
+        moves.push(game_move);
+    }
+    Ok(moves)
+}
+
+// the moves of a game in progress, an error if one is illegal or the game ends
+pub fn parse_moves(game: &BoardGame, text: &str) -> Result<Vec<(usize, DiscType)>, String> {
+    parse(game, text, false)
+}
+
+// the moves of a game that may have ended with the last one
+pub fn parse_game(game: &BoardGame, text: &str) -> Result<Vec<(usize, DiscType)>, String> {
+    parse(game, text, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn connect4() -> BoardGame {
+        BoardGame::new_game(&GameType::Connect4, 6, 7, false)
+    }
+
+    fn toot_otto() -> BoardGame {
+        BoardGame::new_game(&GameType::TOOTandOTTO, 4, 6, false)
+    }
+
+    #[test]
+    fn moves_round_trip() {
+        let game = toot_otto();
+        let moves = parse_moves(&game, "3T, 2o 3O").unwrap();
+        assert_eq!(moves, vec![(3, DiscType::T), (2, DiscType::O), (3, DiscType::O)]);
+        assert_eq!(format_moves(&GameType::TOOTandOTTO, &moves), "3T 2O 3O");
+        assert_eq!(parse_moves(&game, &format_moves(&GameType::TOOTandOTTO, &moves)).unwrap(), moves);
+
+        let game = connect4();
+        let moves = parse_moves(&game, "3 3 4").unwrap();
+        assert_eq!(format_moves(&GameType::Connect4, &moves), "3 3 4");
+    }
+
+    #[test]
+    fn illegal_moves() {
+        assert!(parse_move(&connect4(), "x").is_err());
+        assert!(parse_move(&connect4(), "7").is_err());
+        assert!(parse_move(&toot_otto(), "3").is_err());
+        assert!(parse_moves(&connect4(), "0 0 0 0 0 0 0").is_err());
+    }
+
+    #[test]
+    fn moves_after_the_end() {
+        // player 1 connects four in column 0 with the last move
+        let won = "0 1 0 1 0 1 0";
+        assert!(parse_moves(&connect4(), won).is_err());
+        assert_eq!(parse_game(&connect4(), won).unwrap().len(), 7);
+        assert!(parse_game(&connect4(), "0 1 0 1 0 1 0 1").is_err());
+    }
+}
@@ -0,0 +1,67 @@
+// Runs independent searches on several threads. Threads are only used with the `parallel`
+// feature, without it (e.g. the wasm frontend) the items run one after another on the
+// calling thread and the results are the same.
+
+#[cfg(feature = "parallel")]
+use std::sync::{Arc, Mutex};
+#[cfg(feature = "parallel")]
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// f applied to every item, results in the same order as the items
+#[cfg(feature = "parallel")]
+pub(crate) fn map<T, R, F>(items: Vec<T>, threads: usize, f: F) -> Vec<R>
+where
+    T: Send + 'static,
+    R: Send + 'static,
+    F: Fn(T) -> R + Send + Sync + 'static,
+{
+    let threads = threads.min(items.len());
+    if threads <= 1 {
+        return items.into_iter().map(f).collect();
+    }
+
+    // threads take the next item as soon as they are done, searches can take very different times
+    let count = items.len();
+    let items: Arc<Vec<Mutex<Option<T>>>> = Arc::new(items.into_iter().map(|item| Mutex::new(Some(item))).collect());
+    let next = Arc::new(AtomicUsize::new(0));
+    let f = Arc::new(f);
+    let handles: Vec<_> = (0..threads).map(|_| {
+        let (items, next, f) = (Arc::clone(&items), Arc::clone(&next), Arc::clone(&f));
+        std::thread::spawn(move || {
+            let mut done = Vec::new();
+            loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                if index >= count {
+                    break;
+                }
+                let item = items[index].lock().unwrap().take().unwrap();
+                done.push((index, f(item)));
+            }
+            done
+        })
+    }).collect();
+    let mut results: Vec<(usize, R)> = handles.into_iter().flat_map(|handle| handle.join().unwrap()).collect();
+
+    results.sort_by_key(|(index, _)| *index);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+#[cfg(not(feature = "parallel"))]
+pub(crate) fn map<T, R, F>(items: Vec<T>, _threads: usize, f: F) -> Vec<R>
+where
+    F: Fn(T) -> R,
+{
+    items.into_iter().map(f).collect()
+}
+
+// a sensible thread count for the current machine
+#[cfg(feature = "parallel")]
+pub fn available_threads() -> usize {
+    num_cpus::get()
+}
+
+// always 1 without the `parallel` feature
+#[cfg(not(feature = "parallel"))]
+pub fn available_threads() -> usize {
+    1
+}
@@ -0,0 +1,137 @@
+// Reviews a finished game move by move, for `model analyze` on recorded games
+//
+// Every position of the game is analysed and the move played is compared with the AI's best
+// move there. The swing is how much the player's score dropped by not playing the best move,
+// in the AI's score units (see MoveEvaluation). A move that gives away a win, or turns a game
+// that wasn't lost into a loss, is a blunder whatever the swing.
+use std::fmt::Display;
+use crate::ai::{MoveEvaluation, MoveOutcome};
+use crate::disc::DiscType;
+use crate::game::BoardGame;
+use crate::notation::format_move;
+
+// smallest swings of each class, a three in a row is worth 27
+const INACCURACY_SWING: i64 = 10;
+const MISTAKE_SWING: i64 = 30;
+const BLUNDER_SWING: i64 = 80;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MoveClass {
+    Best,
+    Inaccuracy,
+    Mistake,
+    Blunder,
+}
+
+impl MoveClass {
+    pub fn to_vec() -> Vec<MoveClass> {
+        vec![MoveClass::Best, MoveClass::Inaccuracy, MoveClass::Mistake, MoveClass::Blunder]
+    }
+}
+
+impl Display for MoveClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::result::Result<(), std::fmt::Error> {
+        match self {
+            MoveClass::Best => write!(f, "best"),
+            MoveClass::Inaccuracy => write!(f, "inaccuracy"),
+            MoveClass::Mistake => write!(f, "mistake"),
+            MoveClass::Blunder => write!(f, "blunder"),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct MoveReview {
+    pub player: usize,
+    pub game_move: (usize, DiscType),
+    pub best_move: (usize, DiscType),
+    pub score: i64,      // of the move played, for the player who made it
+    pub best_score: i64,
+    pub outcome: Option<MoveOutcome>,      // proven result of the move played
+    pub best_outcome: Option<MoveOutcome>,
+    pub swing: i64,
+    pub class: MoveClass,
+}
+
+fn outcome_rank(outcome: Option<MoveOutcome>) -> i32 {
+    match outcome {
+        Some(MoveOutcome::Win) => 2,
+        Some(MoveOutcome::Loss) => 0,
+        _ => 1, // a draw or not proven either way
+    }
+}
+
+pub fn classify(best: &MoveEvaluation, played: &MoveEvaluation) -> MoveClass {
+    let swing = best.score - played.score;
+    if outcome_rank(played.outcome) < outcome_rank(best.outcome) || swing >= BLUNDER_SWING {
+        MoveClass::Blunder
+    } else if swing >= MISTAKE_SWING {
+        MoveClass::Mistake
+    } else if swing >= INACCURACY_SWING {
+        MoveClass::Inaccuracy
+    } else {
+        MoveClass::Best
+    }
+}
+
+// `analyze` scores every legal move of a position, best first, like SearchAI::analyze().
+// An error if a move played isn't among them, it is illegal there, or if moves follow the end of
+// the game.
+pub fn review_game(
+    mut game: BoardGame,
+    moves: &[(usize, DiscType)],
+    analyze: &mut dyn FnMut(&BoardGame) -> Vec<MoveEvaluation>,
+) -> Result<Vec<MoveReview>, String> {
+    let mut reviews = Vec::new();
+    for (number, game_move) in moves.iter().enumerate() {
+        let evaluations = analyze(&game);
+        let played = match evaluations.iter().find(|evaluation| (evaluation.column, evaluation.disc_type) == *game_move) {
+            Some(played) => played,
+            None => return Err(format!("move {} ({}) was not analysed", number + 1, format_move(&game.game_type, *game_move))),
+        };
+        let best = &evaluations[0];
+        reviews.push(MoveReview {
+            player: game.current_player,
+            game_move: *game_move,
+            best_move: (best.column, best.disc_type),
+            score: played.score,
+            best_score: best.score,
+            outcome: played.outcome,
+            best_outcome: best.outcome,
+            swing: best.score - played.score,
+            class: classify(best, played),
+        });
+        if game.play_move(*game_move).is_some() && number + 1 < moves.len() {
+            return Err(format!("the game is over after move {}, but {} more follow", number + 1, moves.len() - number - 1));
+        }
+    }
+    Ok(reviews)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::{Connect4AI, Difficulty, SearchAI};
+    use crate::game::GameType;
+    use crate::notation::parse_game;
+
+    fn review(moves: &str, extra: &[(usize, DiscType)]) -> Result<Vec<MoveReview>, String> {
+        let game = BoardGame::new_game(&GameType::Connect4, 6, 7, false);
+        let mut moves = parse_game(&game, moves).unwrap();
+        moves.extend_from_slice(extra);
+        let mut ai = Connect4AI::new(6, 7, Difficulty::Easy, 1);
+        review_game(game, &moves, &mut |game| ai.analyze(game))
+    }
+
+    #[test]
+    fn every_move_reviewed() {
+        let reviews = review("1 2 1 2 1 2 1", &[]).unwrap();
+        assert_eq!(reviews.len(), 7);
+        assert_eq!(reviews[6].class, MoveClass::Best);
+    }
+
+    #[test]
+    fn no_moves_after_a_win() {
+        assert!(review("1 2 1 2 1 2 1", &[(2, DiscType::Yellow)]).is_err());
+    }
+}
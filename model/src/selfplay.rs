@@ -0,0 +1,88 @@
+use rand::rngs::StdRng;
+use crate::adaptive::AdaptiveAI;
+use crate::ai::{Connect4AI, TootOttoAI, get_random_index};
+use crate::mcts::MctsAI;
+use crate::game::BoardGame;
+use crate::disc::DiscType;
+
+// anything that can pick a move for whoever's turn it is
+pub trait GameAI {
+    fn next_move(&mut self, game: &BoardGame) -> (usize, DiscType);
+}
+
+impl GameAI for Connect4AI {
+    fn next_move(&mut self, game: &BoardGame) -> (usize, DiscType) {
+        (self.find_best_move(game.clone()), game.get_current_disc_type())
+    }
+}
+
+impl GameAI for TootOttoAI {
+    fn next_move(&mut self, game: &BoardGame) -> (usize, DiscType) {
+        self.find_best_move(game.clone())
+    }
+}
+
+impl GameAI for AdaptiveAI {
+    fn next_move(&mut self, game: &BoardGame) -> (usize, DiscType) {
+        self.find_best_move(game.clone())
+    }
+}
+
+impl GameAI for MctsAI {
+    fn next_move(&mut self, game: &BoardGame) -> (usize, DiscType) {
+        self.find_best_move(game.clone())
+    }
+}
+
+pub struct GameRecord {
+    pub moves: Vec<(usize, DiscType)>,
+    pub winner: usize, // 0 for a draw
+}
+
+// random moves to start a game from, so matches between deterministic AIs don't repeat
+// stops early if a random move would end the game
+pub fn random_opening(game: &BoardGame, plies: usize, rng: &mut StdRng) -> Vec<(usize, DiscType)> {
+    let mut game = game.clone();
+    let mut opening = Vec::new();
+    for _ in 0..plies {
+        let moves = game.legal_moves();
+        if moves.is_empty() {
+            break;
+        }
+        let game_move = moves[get_random_index(moves.len(), rng)];
+        if game.play_move(game_move).is_some() {
+            break;
+        }
+        opening.push(game_move);
+    }
+
+    opening
+}
+
+// play the opening, then let the two AIs finish the game
+pub fn play_game(
+    mut game: BoardGame,
+    opening: &[(usize, DiscType)],
+    player1: &mut dyn GameAI,
+    player2: &mut dyn GameAI,
+) -> GameRecord {
+    let mut moves = Vec::new();
+    for game_move in opening {
+        moves.push(*game_move);
+        if let Some(winner) = game.play_move(*game_move) {
+            return GameRecord { moves, winner };
+        }
+    }
+
+    loop {
+        let game_move = if game.current_player == 1 {
+            player1.next_move(&game)
+        } else {
+            player2.next_move(&game)
+        };
+        moves.push(game_move);
+        if let Some(winner) = game.play_move(game_move) {
+            return GameRecord { moves, winner };
+        }
+    }
+}
@@ -0,0 +1,98 @@
+// Precomputed solver scores for the first moves of the small boards
+//
+// Solving a position close to the start can take the solver seconds to minutes, later ones
+// are quick. The tables hold the exact score of every position with up to a few discs, so
+// the solver never has to search far before it reaches one.
+//
+// A table file is 1 byte with the number of plies it covers, then one 5 byte record per
+// position sorted by key: the canonical key (see Solver::canonical_key) as a little endian
+// u32 and the score as an i8. Mirror images share a record. Positions where the game is
+// already over aren't stored. The files are made with `cargo run --release --bin solve`.
+use std::collections::HashMap;
+use crate::game::GameType;
+use crate::solver::{MoveResult, Position, Solver, TABLE_SIZE};
+
+const RECORD_SIZE: usize = 5;
+
+// the table embedded for a board size, None if the size has none
+fn embedded(game_type: &GameType, rows: usize, columns: usize) -> Option<&'static [u8]> {
+    match (game_type, columns, rows) {
+        (GameType::Connect4, 5, 4) => Some(include_bytes!("../tables/connect4_5x4.bin")),
+        (GameType::Connect4, 6, 4) => Some(include_bytes!("../tables/connect4_6x4.bin")),
+        (GameType::TOOTandOTTO, 5, 4) => Some(include_bytes!("../tables/toot_otto_5x4.bin")),
+        (GameType::TOOTandOTTO, 6, 4) => Some(include_bytes!("../tables/toot_otto_6x4.bin")),
+        _ => None,
+    }
+}
+
+// whether the board size has a table, i.e. the solver is fast enough to use while playing
+pub fn has_table(game_type: &GameType, rows: usize, columns: usize) -> bool {
+    embedded(game_type, rows, columns).is_some()
+}
+
+// (plies covered, score by canonical key)
+pub(crate) fn load(game_type: &GameType, rows: usize, columns: usize) -> Option<(usize, HashMap<u64, i8>)> {
+    let bytes = embedded(game_type, rows, columns)?;
+    // an empty file is a table that hasn't been generated yet
+    if bytes.is_empty() {
+        return None;
+    }
+    let records = &bytes[1..];
+    let mut book = HashMap::with_capacity(records.len() / RECORD_SIZE);
+    for record in records.chunks_exact(RECORD_SIZE) {
+        let key = u32::from_le_bytes([record[0], record[1], record[2], record[3]]);
+        book.insert(key as u64, record[4] as i8);
+    }
+    Some((bytes[0] as usize, book))
+}
+
+// every position after `plies` moves where the game isn't over yet, one per mirror pair
+fn positions_at(solver: &Solver, previous: &[Position]) -> Vec<Position> {
+    let mut positions = HashMap::new();
+    for position in previous {
+        for game_move in solver.moves(position) {
+            if let MoveResult::Ongoing(child) = solver.play(position, game_move) {
+                positions.entry(solver.canonical_key(&child)).or_insert(child);
+            }
+        }
+    }
+    let mut positions: Vec<(u64, Position)> = positions.into_iter().collect();
+    positions.sort_by_key(|(key, _)| *key);
+    positions.into_iter().map(|(_, position)| position).collect()
+}
+
+// solves every position with up to `plies` discs and returns the table file, `progress` is
+// called with (ply, positions solved at that ply, positions at that ply)
+pub fn generate<F>(game_type: &GameType, rows: usize, columns: usize, plies: usize, mut progress: F) -> Result<Vec<u8>, String>
+where
+    F: FnMut(usize, usize, usize),
+{
+    let mut solver = Solver::new(game_type, rows, columns, TABLE_SIZE).ok_or("the board is too big for the solver")?;
+    let mut levels = vec![vec![Position { mask: 0, bits: 0, plies: 0 }]];
+    for _ in 0..plies {
+        let next = positions_at(&solver, levels.last().unwrap());
+        levels.push(next);
+    }
+
+    // deepest first, the positions before them are then found in the book after one move
+    let mut book = HashMap::new();
+    for ply in (0..=plies).rev() {
+        let count = levels[ply].len();
+        for (solved, position) in levels[ply].iter().enumerate() {
+            let score = solver.score(position);
+            book.insert(solver.canonical_key(position), score as i8);
+            progress(ply, solved + 1, count);
+        }
+        solver.set_book(plies, book.clone());
+    }
+
+    let mut records: Vec<(u64, i8)> = book.into_iter().collect();
+    records.sort_by_key(|(key, _)| *key);
+    let mut bytes = Vec::with_capacity(1 + records.len() * RECORD_SIZE);
+    bytes.push(plies as u8);
+    for (key, score) in records {
+        bytes.extend_from_slice(&(key as u32).to_le_bytes());
+        bytes.push(score as u8);
+    }
+    Ok(bytes)
+}
@@ -0,0 +1,540 @@
+// Exact solver for small boards, used for perfect play and "forced win in N" on 5 x 4 and 6 x 4
+//
+// Positions are bitboards in the usual column major layout: every column takes rows + 1 bits,
+// the extra bit on top stays 0 so lines can't wrap into the next column. For Connect 4 `bits`
+// holds the discs of the player to move, for TOOT-OTTO it holds the T's.
+//
+// Scores are from the point of view of the player to move: a win with the game ending after
+// `plies` discs are on the board scores cells + 1 - plies, a loss the negative of that and a
+// draw 0. Since the score only depends on when the game ends, negamax just negates it.
+use std::collections::HashMap;
+use std::fmt::Display;
+use crate::game::{BoardGame, GameType};
+use crate::disc::DiscType;
+use crate::solution_table;
+
+// entries of the transposition table (8 bytes each): 16 MB to solve positions from scratch,
+// 2 MB is enough while playing, where the solution table leaves only short searches
+pub const TABLE_SIZE: usize = 1 << 21;
+pub const PLAYING_TABLE_SIZE: usize = 1 << 18;
+const EXACT: u8 = 0;
+const LOWER: u8 = 1;
+const UPPER: u8 = 2;
+
+// exact result of a position when both players play perfectly
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum Solution {
+    // `player` (1 or 2) wins, needing at most `moves` more moves of their own
+    Win { player: usize, moves: usize },
+    Draw,
+}
+
+impl Display for Solution {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::result::Result<(), std::fmt::Error> {
+        match self {
+            Solution::Win { player, moves: 0 } => write!(f, "player {} has won", player),
+            Solution::Win { player, moves } => {
+                let plural = if *moves == 1 { "" } else { "s" };
+                write!(f, "player {} can force a win in {} move{}", player, moves, plural)
+            },
+            Solution::Draw => write!(f, "the game is a draw with perfect play"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(crate) struct Position {
+    pub(crate) mask: u64,
+    pub(crate) bits: u64,
+    pub(crate) plies: usize,
+}
+
+// player 1 moves first, on even plies
+fn player1_to_move(position: &Position) -> bool {
+    position.plies & 1 == 0
+}
+
+#[derive(Clone, Copy)]
+struct Entry {
+    key: u32,
+    score: i8,
+    flag: u8,
+}
+
+// what a move leads to, before searching any further
+pub(crate) enum MoveResult {
+    Win,  // the player who moved wins
+    Loss, // TOOT-OTTO only, the player who moved spelled the other word
+    Draw,
+    Ongoing(Position),
+}
+
+pub struct Solver {
+    game_type: GameType,
+    rows: usize,
+    columns: usize,
+    column_order: Vec<usize>,
+    table: Vec<Entry>,
+    book: HashMap<u64, i8>, // precomputed scores by canonical key, see solution_table.rs
+    book_plies: usize,      // the book has every position with up to this many discs
+    nodes: u64,
+}
+
+impl Solver {
+    // keys have to fit in the 32 bits of the transposition table
+    pub fn fits(rows: usize, columns: usize) -> bool {
+        (rows + 1) * columns <= 32
+    }
+
+    // None for a board that doesn't fit, see fits(). The transposition table gets `table_size`
+    // entries, rounded up to a power of 2.
+    pub fn new(game_type: &GameType, rows: usize, columns: usize, table_size: usize) -> Option<Self> {
+        if !Self::fits(rows, columns) {
+            return None;
+        }
+        // center columns first, they usually give the best moves and the quickest cutoffs
+        let mut column_order: Vec<usize> = (0..columns).collect();
+        column_order.sort_by_key(|&column| (2 * column as isize - columns as isize + 1).abs());
+        Some(Self {
+            game_type: game_type.clone(),
+            rows,
+            columns,
+            column_order,
+            table: vec![Entry { key: u32::MAX, score: 0, flag: EXACT }; table_size.next_power_of_two()], // no position has this key
+            book: HashMap::new(),
+            book_plies: 0,
+            nodes: 0,
+        })
+    }
+
+    // a solver using the precomputed table for the board size, if there is one
+    pub fn with_table(game_type: &GameType, rows: usize, columns: usize, table_size: usize) -> Option<Self> {
+        let mut solver = Self::new(game_type, rows, columns, table_size)?;
+        if let Some((plies, book)) = solution_table::load(game_type, rows, columns) {
+            solver.set_book(plies, book);
+        }
+        Some(solver)
+    }
+
+    // positions searched since the solver was created
+    pub fn nodes(&self) -> u64 {
+        self.nodes
+    }
+
+    pub(crate) fn set_book(&mut self, plies: usize, book: HashMap<u64, i8>) {
+        self.book_plies = plies;
+        self.book = book;
+    }
+
+    fn cells(&self) -> usize {
+        self.rows * self.columns
+    }
+
+    fn bottom(&self, column: usize) -> u64 {
+        1 << (column * (self.rows + 1))
+    }
+
+    fn column_mask(&self, column: usize) -> u64 {
+        ((1 << self.rows) - 1) << (column * (self.rows + 1))
+    }
+
+    fn can_play(&self, position: &Position, column: usize) -> bool {
+        position.mask & (1 << (column * (self.rows + 1) + self.rows - 1)) == 0
+    }
+
+    // same key for the same position, never the same key for two positions
+    pub(crate) fn key(&self, position: &Position) -> u64 {
+        position.bits + position.mask
+    }
+
+    // the key of the left-right mirror image
+    pub(crate) fn mirrored_key(&self, position: &Position) -> u64 {
+        let key = self.key(position);
+        let height = self.rows + 1;
+        let column_bits = (1 << height) - 1;
+        let mut mirrored = 0;
+        for column in 0..self.columns {
+            let value = (key >> (column * height)) & column_bits;
+            mirrored |= value << ((self.columns - 1 - column) * height);
+        }
+        mirrored
+    }
+
+    // the same key for a position and its mirror image
+    pub(crate) fn canonical_key(&self, position: &Position) -> u64 {
+        std::cmp::min(self.key(position), self.mirrored_key(position))
+    }
+
+    // lines of 4 in `bits`, `shift` is the distance between neighbouring cells of a line
+    fn shifts(&self) -> [u32; 4] {
+        let height = (self.rows + 1) as u32;
+        [1, height, height - 1, height + 1]
+    }
+
+    fn has_four(&self, bits: u64) -> bool {
+        self.shifts().iter().any(|&shift| {
+            let pairs = bits & (bits >> shift);
+            pairs & (pairs >> (2 * shift)) != 0
+        })
+    }
+
+    // (TOOT on the board, OTTO on the board)
+    fn words(&self, mask: u64, t: u64) -> (bool, bool) {
+        let o = mask & !t;
+        let mut toot = false;
+        let mut otto = false;
+        for &shift in &self.shifts() {
+            toot |= t & (o >> shift) & (o >> (2 * shift)) & (t >> (3 * shift)) != 0;
+            otto |= o & (t >> shift) & (t >> (2 * shift)) & (o >> (3 * shift)) != 0;
+        }
+        (toot, otto)
+    }
+
+    fn board_mask(&self) -> u64 {
+        (0..self.columns).fold(0, |mask, column| mask | self.column_mask(column))
+    }
+
+    // empty cells that would finish a line of 4 with the given cells in positions 0 to 3,
+    // e.g. [t, o, o, t] for TOOT
+    fn gaps(&self, line: [u64; 4], empty: u64) -> u64 {
+        let mut gaps = 0;
+        for &shift in &self.shifts() {
+            for gap in 0..4 {
+                let mut start = !0;
+                for (k, &cells) in line.iter().enumerate() {
+                    let cells = if k == gap { empty } else { cells };
+                    start &= cells >> (k as u32 * shift);
+                }
+                gaps |= start << (gap as u32 * shift);
+            }
+        }
+        gaps & empty
+    }
+
+    // cells where the player who just made the move could finish their line or word later,
+    // moves making more of them are searched first
+    fn threats_after(&self, position: &Position, (column, disc): (usize, DiscType)) -> u32 {
+        let cell = (position.mask + self.bottom(column)) & self.column_mask(column);
+        let mask = position.mask | cell;
+        let empty = self.board_mask() & !mask;
+        let threats = match self.game_type {
+            GameType::Connect4 => {
+                let bits = position.bits | cell;
+                self.gaps([bits; 4], empty)
+            },
+            GameType::TOOTandOTTO => {
+                let t = if disc == DiscType::T { position.bits | cell } else { position.bits };
+                let o = mask & !t;
+                if player1_to_move(position) { self.gaps([t, o, o, t], empty) } else { self.gaps([o, t, t, o], empty) }
+            },
+        };
+        threats.count_ones()
+    }
+
+    pub(crate) fn position_of(&self, game: &BoardGame) -> Position {
+        let mut position = Position { mask: 0, bits: 0, plies: 0 };
+        let disc_to_move = if game.current_player == 1 { DiscType::Red } else { DiscType::Yellow };
+        for column in 0..self.columns {
+            for row in 0..self.rows {
+                // row 0 of the game board is the top
+                let disc = game.game_board.board[self.rows - 1 - row][column];
+                if disc == DiscType::Empty {
+                    continue;
+                }
+                let bit = 1 << (column * (self.rows + 1) + row);
+                position.mask |= bit;
+                position.plies += 1;
+                let set = match self.game_type {
+                    GameType::Connect4 => disc == disc_to_move,
+                    GameType::TOOTandOTTO => disc == DiscType::T,
+                };
+                if set {
+                    position.bits |= bit;
+                }
+            }
+        }
+        position
+    }
+
+    // every legal move, in the order they are searched
+    pub(crate) fn moves(&self, position: &Position) -> Vec<(usize, DiscType)> {
+        let mut moves = Vec::new();
+        for &column in &self.column_order {
+            if !self.can_play(position, column) {
+                continue;
+            }
+            match self.game_type {
+                GameType::Connect4 => {
+                    let disc = if player1_to_move(position) { DiscType::Red } else { DiscType::Yellow };
+                    moves.push((column, disc));
+                },
+                GameType::TOOTandOTTO => {
+                    moves.push((column, DiscType::T));
+                    moves.push((column, DiscType::O));
+                },
+            }
+        }
+        moves
+    }
+
+    pub(crate) fn play(&self, position: &Position, (column, disc): (usize, DiscType)) -> MoveResult {
+        let cell = (position.mask + self.bottom(column)) & self.column_mask(column);
+        let mask = position.mask | cell;
+        let plies = position.plies + 1;
+        match self.game_type {
+            GameType::Connect4 => {
+                if self.has_four(position.bits | cell) {
+                    return MoveResult::Win;
+                }
+                if plies == self.cells() {
+                    return MoveResult::Draw;
+                }
+                // the other player is to move now
+                MoveResult::Ongoing(Position { mask, bits: position.bits ^ position.mask, plies })
+            },
+            GameType::TOOTandOTTO => {
+                let bits = if disc == DiscType::T { position.bits | cell } else { position.bits };
+                let (toot, otto) = self.words(mask, bits);
+                // player 1 spells TOOT and moves on even plies
+                let mover_word = if player1_to_move(position) { toot } else { otto };
+                let other_word = if player1_to_move(position) { otto } else { toot };
+                if toot && otto {
+                    MoveResult::Draw
+                } else if mover_word {
+                    MoveResult::Win
+                } else if other_word {
+                    MoveResult::Loss
+                } else if plies == self.cells() {
+                    MoveResult::Draw
+                } else {
+                    MoveResult::Ongoing(Position { mask, bits, plies })
+                }
+            },
+        }
+    }
+
+    // score of a move for the player making it
+    fn move_score(&mut self, position: &Position, game_move: (usize, DiscType), alpha: i32, beta: i32) -> i32 {
+        let win = (self.cells() + 1 - (position.plies + 1)) as i32;
+        match self.play(position, game_move) {
+            MoveResult::Win => win,
+            MoveResult::Loss => -win,
+            MoveResult::Draw => 0,
+            MoveResult::Ongoing(child) => -self.negamax(&child, -beta, -alpha),
+        }
+    }
+
+    fn negamax(&mut self, position: &Position, mut alpha: i32, mut beta: i32) -> i32 {
+        self.nodes += 1;
+        let moves = self.moves(position);
+
+        // a win right now beats anything else
+        let win_now = (self.cells() - position.plies) as i32;
+        for &game_move in &moves {
+            if let MoveResult::Win = self.play(position, game_move) {
+                return win_now;
+            }
+        }
+
+        // otherwise the best is winning with the next own move, or a draw when the board
+        // fills up before that. In TOOT-OTTO the opponent can be forced to spell our word
+        // on their move, one ply earlier.
+        let earliest_win = match self.game_type {
+            GameType::Connect4 => win_now - 2,
+            GameType::TOOTandOTTO => win_now - 1,
+        };
+        let best_possible = std::cmp::max(earliest_win, 0);
+        if beta > best_possible {
+            beta = best_possible;
+            if alpha >= beta {
+                return beta;
+            }
+        }
+
+        if position.plies <= self.book_plies && !self.book.is_empty() {
+            if let Some(&score) = self.book.get(&self.canonical_key(position)) {
+                return score as i32;
+            }
+        }
+        let key = self.key(position);
+        let index = (key as usize) & (self.table.len() - 1);
+        let entry = self.table[index];
+        if entry.key as u64 == key {
+            let score = entry.score as i32;
+            match entry.flag {
+                EXACT => return score,
+                LOWER => alpha = std::cmp::max(alpha, score),
+                _ => beta = std::cmp::min(beta, score),
+            }
+            if alpha >= beta {
+                return score;
+            }
+        }
+
+        let mut moves: Vec<(u32, (usize, DiscType))> = moves.into_iter()
+            .map(|game_move| (self.threats_after(position, game_move), game_move))
+            .collect();
+        // stable, so moves with as many threats stay center first
+        moves.sort_by_key(|(threats, _)| std::cmp::Reverse(*threats));
+
+        let original_alpha = alpha;
+        let mut best = -(win_now + 1);
+        for (_, game_move) in moves {
+            let score = self.move_score(position, game_move, alpha, beta);
+            if score > best {
+                best = score;
+            }
+            if best > alpha {
+                alpha = best;
+            }
+            if alpha >= beta {
+                break;
+            }
+        }
+
+        let flag = if best <= original_alpha {
+            UPPER
+        } else if best >= beta {
+            LOWER
+        } else {
+            EXACT
+        };
+        self.table[index] = Entry { key: key as u32, score: best as i8, flag };
+        best
+    }
+
+    // exact score of a position for the player to move, see the top of the file
+    pub(crate) fn score(&mut self, position: &Position) -> i32 {
+        if position.plies == self.cells() {
+            return 0;
+        }
+        // narrow the score down with null window searches, they cut off a lot more than
+        // a single search with the full window
+        let bound = (self.cells() + 1) as i32;
+        let (mut min, mut max) = (-bound, bound);
+        while min < max {
+            let mut middle = min + (max - min) / 2;
+            // try around 0 first, most positions are close to a draw
+            if middle <= 0 && min / 2 < middle {
+                middle = min / 2;
+            } else if middle >= 0 && max / 2 > middle {
+                middle = max / 2;
+            }
+            let score = self.negamax(position, middle, middle + 1);
+            if score <= middle {
+                max = score;
+            } else {
+                min = score;
+            }
+        }
+        min
+    }
+
+    fn to_solution(&self, score: i32, plies: usize, player_to_move: usize) -> Solution {
+        if score == 0 {
+            return Solution::Draw;
+        }
+        // the game ends once `end` discs are on the board
+        let end = self.cells() + 1 - score.abs() as usize;
+        let (player, first_move) = if score > 0 {
+            (player_to_move, plies + 1)
+        } else {
+            (3 - player_to_move, plies + 2)
+        };
+        // 0 moves when the last move spelled the other player's word and the game is over. The
+        // player to move can also be forced to spell the other word, the winner then needs no
+        // move of their own but the game isn't over yet, which counts as 1.
+        let moves = if end <= plies { 0 } else { end.saturating_sub(first_move) / 2 + 1 };
+        Solution::Win { player, moves }
+    }
+
+    pub fn solve(&mut self, game: &BoardGame) -> Solution {
+        let position = self.position_of(game);
+        let score = self.score(&position);
+        self.to_solution(score, position.plies, game.current_player)
+    }
+
+    // exact score of a move for the player making it
+    fn exact_move_score(&mut self, position: &Position, game_move: (usize, DiscType)) -> i32 {
+        let win = (self.cells() + 1 - (position.plies + 1)) as i32;
+        match self.play(position, game_move) {
+            MoveResult::Win => win,
+            MoveResult::Loss => -win,
+            MoveResult::Draw => 0,
+            MoveResult::Ongoing(child) => -self.score(&child),
+        }
+    }
+
+    // every legal move with the result it leads to, for the player to move
+    pub fn solve_moves(&mut self, game: &BoardGame) -> Vec<((usize, DiscType), Solution)> {
+        let position = self.position_of(game);
+        self.moves(&position).into_iter()
+            .map(|game_move| {
+                let score = self.exact_move_score(&position, game_move);
+                // a move scored for the mover is a position scored for the other player, one ply later
+                let solution = self.to_solution(-score, position.plies + 1, 3 - game.current_player);
+                (game_move, solution)
+            })
+            .collect()
+    }
+
+    // the moves that keep the best result for the player to move: the quickest win, a draw,
+    // or else the slowest loss
+    pub fn best_moves(&mut self, game: &BoardGame) -> Vec<(usize, DiscType)> {
+        let position = self.position_of(game);
+        let scores: Vec<((usize, DiscType), i32)> = self.moves(&position).into_iter()
+            .map(|game_move| (game_move, self.exact_move_score(&position, game_move)))
+            .collect();
+        let best = scores.iter().map(|(_, score)| *score).max();
+        scores.into_iter()
+            .filter(|(_, score)| Some(*score) == best)
+            .map(|(game_move, _)| game_move)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notation::parse_moves;
+
+    fn game_after(game_type: &GameType, rows: usize, columns: usize, moves: &str) -> BoardGame {
+        let mut game = BoardGame::new_game(game_type, rows, columns, false);
+        for game_move in parse_moves(&game, moves).unwrap() {
+            game.play_move(game_move);
+        }
+        game
+    }
+
+    #[test]
+    fn boards_too_big_for_the_keys() {
+        assert!(Solver::new(&GameType::Connect4, 6, 7, 1 << 10).is_none());
+        assert!(Solver::new(&GameType::Connect4, 4, 6, 1 << 10).is_some());
+    }
+
+    #[test]
+    fn connect4_on_4_x_4_is_a_draw() {
+        let mut solver = Solver::new(&GameType::Connect4, 4, 4, 1 << 16).unwrap();
+        let game = BoardGame::new_game(&GameType::Connect4, 4, 4, false);
+        assert_eq!(solver.solve(&game), Solution::Draw);
+    }
+
+    #[test]
+    fn win_in_one() {
+        let mut solver = Solver::new(&GameType::Connect4, 4, 4, 1 << 16).unwrap();
+        let game = game_after(&GameType::Connect4, 4, 4, "0 1 0 1 0 1");
+        assert_eq!(solver.solve(&game), Solution::Win { player: 1, moves: 1 });
+        assert_eq!(solver.best_moves(&game), vec![(0, game.get_current_disc_type())]);
+    }
+
+    // every move of player 2 spells TOOT in the last empty cell
+    #[test]
+    fn forced_to_spell_the_other_word() {
+        let mut solver = Solver::new(&GameType::TOOTandOTTO, 4, 4, 1 << 16).unwrap();
+        let game = game_after(&GameType::TOOTandOTTO, 4, 4, "0O 1T 2O 1O 3O 0O 0O 2O 0T 3O 2O 2O 3T 3T 1O");
+        assert_eq!(solver.solve(&game), Solution::Win { player: 1, moves: 1 });
+        for (_, solution) in solver.solve_moves(&game) {
+            assert_eq!(solution, Solution::Win { player: 1, moves: 0 });
+        }
+    }
+}
@@ -0,0 +1,166 @@
+// Records the minimax search tree of Connect4AI and TootOttoAI, to debug the evaluation offline
+//
+// Every node is a move the search tried, with the alpha-beta window it was searched with and the
+// score it got (from the AI's point of view, like value()). A node whose score made the search
+// stop looking at its siblings is marked as a cutoff. The tree stops at the depth and node limits,
+// deeper moves are still searched but not recorded. Only single threaded searches are recorded,
+// and nothing is when Insane plays from the solver (see solver.rs).
+use crate::disc::DiscType;
+
+#[derive(Clone, Debug)]
+pub struct TraceNode {
+    pub id: usize,
+    pub parent: Option<usize>, // None for the moves of the AI at the root
+    pub depth: usize,          // 1 for the root moves
+    pub game_move: (usize, DiscType),
+    pub maximizing: bool,      // the AI made this move, its opponent otherwise
+    pub alpha: i64,
+    pub beta: i64,
+    pub score: Option<i64>,    // None if the search never finished the node
+    pub cutoff: bool,
+    pub principal_variation: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct SearchTrace {
+    pub max_depth: usize,
+    pub max_nodes: usize,
+    pub nodes: Vec<TraceNode>,
+    pub truncated: bool, // some nodes were left out because of the limits
+    open: Vec<Option<usize>>, // nodes being searched, None for the ones left out
+}
+
+impl SearchTrace {
+    pub fn new(max_depth: usize, max_nodes: usize) -> Self {
+        Self {
+            max_depth,
+            max_nodes,
+            nodes: Vec::new(),
+            truncated: false,
+            open: Vec::new(),
+        }
+    }
+
+    // a move is about to be searched, the returned handle goes back to exit()
+    pub(crate) fn enter(&mut self, game_move: (usize, DiscType), maximizing: bool, alpha: i64, beta: i64) -> Option<usize> {
+        let depth = self.open.len() + 1;
+        let parent_recorded = !matches!(self.open.last(), Some(None));
+        let node = if parent_recorded && depth <= self.max_depth && self.nodes.len() < self.max_nodes {
+            let id = self.nodes.len();
+            self.nodes.push(TraceNode {
+                id,
+                parent: self.open.last().copied().flatten(),
+                depth,
+                game_move,
+                maximizing,
+                alpha,
+                beta,
+                score: None,
+                cutoff: false,
+                principal_variation: false,
+            });
+            Some(id)
+        } else {
+            self.truncated |= parent_recorded;
+            None
+        };
+        self.open.push(node);
+        node
+    }
+
+    pub(crate) fn exit(&mut self, node: Option<usize>, score: i64, cutoff: bool) {
+        self.open.pop();
+        if let Some(id) = node {
+            self.nodes[id].score = Some(score);
+            self.nodes[id].cutoff = cutoff;
+        }
+    }
+
+    fn children(&self, parent: Option<usize>) -> impl Iterator<Item = &TraceNode> {
+        self.nodes.iter().filter(move |node| node.parent == parent)
+    }
+
+    // marks the line the score of the chosen move comes from: at every node the reply with
+    // the node's own score, the best one for whoever made it
+    pub fn mark_principal_variation(&mut self, chosen: (usize, DiscType)) {
+        for node in &mut self.nodes {
+            node.principal_variation = false;
+        }
+        let mut current = self.children(None)
+            .filter(|node| node.game_move == chosen)
+            .last()
+            .map(|node| node.id);
+        while let Some(id) = current {
+            self.nodes[id].principal_variation = true;
+            let score = self.nodes[id].score;
+            current = self.children(Some(id))
+                .find(|node| node.score.is_some() && node.score == score)
+                .map(|node| node.id);
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        let nodes: Vec<String> = self.nodes.iter()
+            .map(|node| {
+                format!(
+                    "    {{\"id\": {}, \"parent\": {}, \"depth\": {}, \"column\": {}, \"disc\": \"{}\", \"player\": \"{}\", \
+                     \"alpha\": {}, \"beta\": {}, \"score\": {}, \"cutoff\": {}, \"principal_variation\": {}}}",
+                    node.id,
+                    node.parent.map_or(String::from("null"), |parent| parent.to_string()),
+                    node.depth,
+                    node.game_move.0,
+                    node.game_move.1,
+                    if node.maximizing { "max" } else { "min" },
+                    node.alpha,
+                    node.beta,
+                    node.score.map_or(String::from("null"), |score| score.to_string()),
+                    node.cutoff,
+                    node.principal_variation
+                )
+            })
+            .collect();
+        format!(
+            "{{\n  \"max_depth\": {},\n  \"max_nodes\": {},\n  \"truncated\": {},\n  \"nodes\": [\n{}\n  ]\n}}\n",
+            self.max_depth,
+            self.max_nodes,
+            self.truncated,
+            nodes.join(",\n")
+        )
+    }
+
+    // the principal variation is drawn in bold red, cutoffs are grey
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph search {\n  node [shape=box, fontname=\"monospace\"];\n  root [label=\"root\"];\n");
+        for node in &self.nodes {
+            let score = node.score.map_or(String::from("?"), |score| score.to_string());
+            let mut label = format!(
+                "{} {} ({})\\nscore {}\\n[{}, {}]",
+                if node.maximizing { "max" } else { "min" },
+                node.game_move.0,
+                node.game_move.1,
+                score,
+                node.alpha,
+                node.beta
+            );
+            if node.cutoff {
+                label.push_str("\\ncutoff");
+            }
+            let style = if node.principal_variation {
+                ", color=red, penwidth=2"
+            } else if node.cutoff {
+                ", style=filled, fillcolor=lightgrey"
+            } else {
+                ""
+            };
+            dot.push_str(&format!("  n{} [label=\"{}\"{}];\n", node.id, label, style));
+            let parent = node.parent.map_or(String::from("root"), |parent| format!("n{}", parent));
+            let edge_style = if node.principal_variation { " [color=red, penwidth=2]" } else { "" };
+            dot.push_str(&format!("  {} -> n{}{};\n", parent, node.id, edge_style));
+        }
+        if self.truncated {
+            dot.push_str("  truncated [shape=plaintext, label=\"(truncated by the depth or node limit)\"];\n");
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
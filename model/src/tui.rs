@@ -0,0 +1,334 @@
+// Full screen terminal UI for `play`, part of the command line program (main.rs) and not the library
+//
+// Draws the board with coloured discs, a cursor over the columns moved with the arrow keys,
+// the list of moves on the side and a status line at the bottom. Only needs a terminal that
+// understands the usual ANSI sequences, so it works over SSH.
+use std::io::{stdout, Stdout, Write};
+use std::thread::sleep;
+use std::time::Duration;
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{poll, read, Event, KeyCode, KeyEvent, KeyModifiers};
+use crossterm::style::{Attribute, Color, Print, ResetColor, SetAttribute, SetForegroundColor};
+use crossterm::terminal::{disable_raw_mode, enable_raw_mode, size, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{execute, queue};
+use model::ai::random_seed;
+use model::disc::DiscType;
+use model::game::{BoardGame, GameType};
+use model::game_file::GameFile;
+use model::notation::format_move;
+use model::selfplay::GameAI;
+
+const LEFT: u16 = 2;
+const TOP: u16 = 1;
+const CELL_WIDTH: u16 = 4;
+// time for a disc to fall one row
+const DROP_DELAY: Duration = Duration::from_millis(40);
+const KEYS: &str = "←/→ move  ↓/enter drop  t/o or tab letter  u undo  s save  l load  q quit";
+
+// puts the terminal back even if the game panics
+struct Screen {
+    out: Stdout,
+}
+
+impl Screen {
+    fn open() -> std::io::Result<Self> {
+        enable_raw_mode()?;
+        let mut out = stdout();
+        execute!(out, EnterAlternateScreen, Hide)?;
+        Ok(Self { out })
+    }
+}
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        execute!(self.out, Show, LeaveAlternateScreen).ok();
+        disable_raw_mode().ok();
+    }
+}
+
+fn disc_color(disc_type: DiscType) -> Color {
+    match disc_type {
+        DiscType::Red => Color::Red,
+        DiscType::Yellow => Color::Yellow,
+        DiscType::T => Color::Cyan,
+        DiscType::O => Color::Magenta,
+        DiscType::Empty => Color::DarkGrey,
+    }
+}
+
+fn disc_symbol(disc_type: DiscType) -> &'static str {
+    match disc_type {
+        DiscType::Red | DiscType::Yellow => "●",
+        DiscType::T => "T",
+        DiscType::O => "O",
+        DiscType::Empty => "·",
+    }
+}
+
+type NewPlayers = fn(&GameFile) -> Vec<Option<Box<dyn GameAI>>>;
+
+pub struct Tui {
+    file: GameFile, // the settings of the game, its moves are kept in `moves`
+    save_path: String,
+    new_players: NewPlayers, // the players of a loaded game
+    game: BoardGame,
+    players: Vec<Option<Box<dyn GameAI>>>, // None for a player at the keyboard
+    moves: Vec<(usize, (usize, DiscType))>, // (player, move)
+    history: Vec<BoardGame>, // the game before each of the moves, for undo
+    cursor: usize,
+    letter: DiscType, // the letter in hand in TOOT-OTTO
+    winner: Option<usize>,
+    message: Option<String>, // shown instead of the usual status until the next key
+}
+
+impl Tui {
+    pub fn new(file: GameFile, save_path: String, new_players: NewPlayers) -> Self {
+        let mut tui = Self {
+            game: file.empty_game(),
+            players: Vec::new(),
+            file,
+            save_path,
+            new_players,
+            moves: Vec::new(),
+            history: Vec::new(),
+            cursor: 0,
+            letter: DiscType::T,
+            winner: None,
+            message: None,
+        };
+        tui.start(tui.file.clone());
+        tui
+    }
+
+    // replays the moves of `file` so they can be undone like the others
+    fn start(&mut self, mut file: GameFile) {
+        // a loaded file without a seed gets one, so saving it again keeps the game replayable
+        file.seed.get_or_insert_with(random_seed);
+        self.game = file.empty_game();
+        self.players = (self.new_players)(&file);
+        self.moves.clear();
+        self.history.clear();
+        self.winner = None;
+        for game_move in &file.moves {
+            self.history.push(self.game.clone());
+            self.moves.push((self.game.current_player, *game_move));
+            self.winner = self.game.play_move(*game_move);
+        }
+        self.cursor = self.game.game_board.board_columns / 2;
+        self.file = file;
+    }
+
+    fn title(&self) -> String {
+        let file = &self.file;
+        format!(
+            "{} on {}{}",
+            file.game_type.to_string(),
+            file.board_size.to_string(),
+            if !file.humans.0 || !file.humans.1 { format!(", {} computer", file.difficulty.to_string()) } else { String::new() }
+        )
+    }
+
+    fn save(&mut self) {
+        let mut file = self.file.clone();
+        file.moves = self.moves.iter().map(|(_, game_move)| *game_move).collect();
+        self.message = Some(match file.save(&self.save_path) {
+            Ok(_) => format!("game saved to {}", self.save_path),
+            Err(err) => err,
+        });
+    }
+
+    fn load(&mut self) {
+        match GameFile::load(&self.save_path) {
+            Ok(file) => {
+                self.start(file);
+                self.message = Some(format!("game loaded from {}", self.save_path));
+            },
+            Err(err) => self.message = Some(err),
+        }
+    }
+
+    // plays until the player quits, the exit code is 0 if the game was finished
+    pub fn run(mut self) -> std::io::Result<i32> {
+        let mut screen = Screen::open()?;
+        loop {
+            if self.winner.is_none() && self.players[self.game.current_player - 1].is_some() {
+                self.message = Some(format!("Computer (player {}) is thinking...", self.game.current_player));
+                self.draw(&mut screen.out, None)?;
+                let game_move = self.players[self.game.current_player - 1].as_mut().unwrap().next_move(&self.game);
+                self.message = None;
+                self.play(&mut screen.out, game_move)?;
+                // computers playing each other can still be stopped
+                if poll(Duration::from_millis(0))? {
+                    if let Event::Key(key) = read()? {
+                        if !self.handle_key(&mut screen.out, key)? {
+                            return Ok(if self.winner.is_some() { 0 } else { 1 });
+                        }
+                    }
+                }
+                continue;
+            }
+            self.draw(&mut screen.out, None)?;
+
+            let key = match read()? {
+                Event::Key(key) => key,
+                _ => continue, // a resize is redrawn on the next loop
+            };
+            self.message = None;
+            if !self.handle_key(&mut screen.out, key)? {
+                return Ok(if self.winner.is_some() { 0 } else { 1 });
+            }
+        }
+    }
+
+    // false to quit
+    fn handle_key(&mut self, out: &mut Stdout, key: KeyEvent) -> std::io::Result<bool> {
+        let columns = self.game.game_board.board_columns;
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(false),
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return Ok(false),
+            KeyCode::Left => self.cursor = (self.cursor + columns - 1) % columns,
+            KeyCode::Right => self.cursor = (self.cursor + 1) % columns,
+            KeyCode::Char(digit) if digit.is_ascii_digit() => {
+                let column = digit.to_digit(10).unwrap() as usize;
+                if column < columns {
+                    self.cursor = column;
+                }
+            },
+            KeyCode::Char('t') | KeyCode::Char('T') => self.letter = DiscType::T,
+            KeyCode::Char('o') | KeyCode::Char('O') => self.letter = DiscType::O,
+            KeyCode::Tab => self.letter = if self.letter == DiscType::T { DiscType::O } else { DiscType::T },
+            KeyCode::Char('u') | KeyCode::Backspace => self.undo(),
+            KeyCode::Char('s') => self.save(),
+            KeyCode::Char('l') => self.load(),
+            KeyCode::Down | KeyCode::Enter | KeyCode::Char(' ') if self.winner.is_none() => {
+                let game_move = (self.cursor, self.disc_in_hand());
+                if self.players[self.game.current_player - 1].is_some() {
+                    self.message = Some(String::from("it's the computer's turn"));
+                } else if !self.game.game_board.get_valid_columns().contains(&self.cursor) {
+                    self.message = Some(format!("column {} is full", self.cursor));
+                } else if self.game.legal_moves().contains(&game_move) {
+                    self.play(out, game_move)?;
+                } else {
+                    self.message = Some(format!("{} can't be played in column {}", game_move.1, self.cursor));
+                }
+            },
+            _ => (),
+        }
+        Ok(true)
+    }
+
+    fn disc_in_hand(&self) -> DiscType {
+        match self.game.game_type {
+            GameType::Connect4 => self.game.get_current_disc_type(),
+            GameType::TOOTandOTTO => self.letter,
+        }
+    }
+
+    fn play(&mut self, out: &mut Stdout, game_move: (usize, DiscType)) -> std::io::Result<()> {
+        let (column, disc_type) = game_move;
+        let landing = (0..self.game.game_board.board_rows)
+            .rev()
+            .find(|&row| self.game.game_board.board[row][column] == DiscType::Empty)
+            .unwrap_or(0);
+        for row in 0..=landing {
+            self.draw(out, Some((row, column, disc_type)))?;
+            sleep(DROP_DELAY);
+        }
+
+        self.history.push(self.game.clone());
+        self.moves.push((self.game.current_player, game_move));
+        self.winner = self.game.play_move(game_move);
+        Ok(())
+    }
+
+    fn status(&self) -> String {
+        if let Some(message) = &self.message {
+            return message.clone();
+        }
+        match self.winner {
+            Some(0) => String::from("Game Draw! u to undo, q to quit"),
+            Some(winner) => format!("Player {} Win! u to undo, q to quit", winner),
+            None => format!("Player {}'s turn", self.game.current_player),
+        }
+    }
+
+    // takes back moves until it's a person's turn again, a single move when computers play
+    // each other
+    fn undo(&mut self) {
+        if self.history.is_empty() {
+            self.message = Some(String::from("nothing to undo"));
+        }
+        let only_computers = self.players.iter().all(|player| player.is_some());
+        while let Some(game) = self.history.pop() {
+            self.game = game;
+            self.moves.pop();
+            self.winner = None;
+            if only_computers || self.players[self.game.current_player - 1].is_none() {
+                return;
+            }
+        }
+    }
+
+    // `falling` is a disc on its way down: (row, column, disc)
+    fn draw(&self, out: &mut Stdout, falling: Option<(usize, usize, DiscType)>) -> std::io::Result<()> {
+        let board = &self.game.game_board;
+        let (width, height) = size()?;
+        queue!(out, Clear(ClearType::All), MoveTo(LEFT, TOP), SetAttribute(Attribute::Bold), Print(self.title()), SetAttribute(Attribute::Reset))?;
+
+        // the disc in hand above the cursor's column
+        if self.winner.is_none() && self.players[self.game.current_player - 1].is_none() {
+            let disc_type = self.disc_in_hand();
+            queue!(
+                out,
+                MoveTo(LEFT + 2 + CELL_WIDTH * self.cursor as u16, TOP + 2),
+                SetForegroundColor(disc_color(disc_type)),
+                Print(disc_symbol(disc_type)),
+                ResetColor
+            )?;
+        }
+
+        for row in 0..board.board_rows {
+            queue!(out, MoveTo(LEFT, TOP + 3 + row as u16))?;
+            for column in 0..board.board_columns {
+                let disc_type = match falling {
+                    Some((falling_row, falling_column, disc_type)) if (falling_row, falling_column) == (row, column) => disc_type,
+                    _ => board.board[row][column],
+                };
+                queue!(out, Print("│ "), SetForegroundColor(disc_color(disc_type)), Print(disc_symbol(disc_type)), ResetColor, Print(" "))?;
+            }
+            queue!(out, Print("│"))?;
+        }
+        let bottom = TOP + 3 + board.board_rows as u16;
+        let line = vec!["───"; board.board_columns].join("┴");
+        queue!(out, MoveTo(LEFT, bottom), Print(format!("└{}┘", line)))?;
+        let numbers: String = (0..board.board_columns).map(|column| format!("{:^4}", column)).collect();
+        queue!(out, MoveTo(LEFT + 1, bottom + 1), Print(numbers))?;
+
+        queue!(out, MoveTo(LEFT, bottom + 3), Print(self.status()))?;
+        queue!(out, MoveTo(LEFT, bottom + 4), SetForegroundColor(Color::DarkGrey), Print(KEYS), ResetColor)?;
+        self.draw_moves(out, width, height)?;
+        out.flush()
+    }
+
+    // the latest moves that fit on the right of the board
+    fn draw_moves(&self, out: &mut Stdout, width: u16, height: u16) -> std::io::Result<()> {
+        let x = LEFT + CELL_WIDTH * self.game.game_board.board_columns as u16 + 6;
+        if x + 12 > width {
+            return Ok(()); // no room, the board matters more
+        }
+        queue!(out, MoveTo(x, TOP + 2), SetAttribute(Attribute::Bold), Print("Moves"), SetAttribute(Attribute::Reset))?;
+        let rows = height.saturating_sub(TOP + 4) as usize;
+        let first = self.moves.len().saturating_sub(rows);
+        for (index, (player, game_move)) in self.moves.iter().enumerate().skip(first) {
+            queue!(
+                out,
+                MoveTo(x, TOP + 3 + (index - first) as u16),
+                Print(format!("{:>3}. P{} ", index + 1, player)),
+                SetForegroundColor(disc_color(game_move.1)),
+                Print(format_move(&self.game.game_type, *game_move)),
+                ResetColor
+            )?;
+        }
+        Ok(())
+    }
+}